@@ -9,6 +9,8 @@ pub struct Diagnostics {
     pub deny_warnings: bool,
     /// Number of messages generated.
     pub count: u32,
+    /// The messages generated, if created with [`Diagnostics::capture`].
+    captured: Option<Vec<String>>,
 }
 
 impl Diagnostics {
@@ -17,19 +19,37 @@ impl Diagnostics {
         Diagnostics {
             deny_warnings,
             count: 0,
+            captured: None,
         }
     }
 
+    /// Creates a handler that collects the messages instead of displaying
+    /// them, so that tests can check them with [`Diagnostics::messages`].
+    pub fn capture() -> Diagnostics {
+        Diagnostics {
+            captured: Some(Vec::new()),
+            ..Diagnostics::new()
+        }
+    }
+
+    /// The messages collected by a handler created with
+    /// [`Diagnostics::capture`], in order.
+    pub fn messages(&self) -> &[String] {
+        self.captured.as_deref().unwrap_or_default()
+    }
+
     /// Displays a warning or error (depending on whether warnings are denied).
     ///
     /// Usually you want the [`warn_or_err!`] macro.
     pub fn warn_or_err(&mut self, args: fmt::Arguments<'_>) {
-        if self.deny_warnings {
+        self.count += 1;
+        if let Some(captured) = &mut self.captured {
+            captured.push(args.to_string());
+        } else if self.deny_warnings {
             eprintln!("error: {args}");
         } else {
             eprintln!("warning: {args}");
         }
-        self.count += 1;
     }
 }
 
@@ -0,0 +1,23 @@
+//! Subcommand for comparing two versions of the grammar.
+//!
+//! Each directory is the `src` directory of a copy of the Reference, such as
+//! a checkout of the base branch of a PR.
+
+use clap::ArgMatches;
+use diagnostics::Diagnostics;
+use grammar::GrammarLoader;
+use std::path::PathBuf;
+
+pub fn diff(matches: &ArgMatches) {
+    let old = matches.get_one::<PathBuf>("OLD").unwrap();
+    let new = matches.get_one::<PathBuf>("NEW").unwrap();
+    let mut diag = Diagnostics::new();
+    let old = GrammarLoader::new(old).load(&mut diag);
+    let new = GrammarLoader::new(new).load(&mut diag);
+    let diff = old.diff(&new);
+    if matches.get_flag("json") {
+        println!("{}", diff.to_json());
+    } else {
+        print!("{diff}");
+    }
+}
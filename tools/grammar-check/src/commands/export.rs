@@ -0,0 +1,12 @@
+//! Subcommand for exporting the grammar to a standard notation.
+
+use clap::ArgMatches;
+use diagnostics::Diagnostics;
+use grammar::ExportFormat;
+
+pub fn export(matches: &ArgMatches) {
+    let format = matches.get_one::<ExportFormat>("format").unwrap();
+    let mut diag = Diagnostics::new();
+    let grammar = grammar::load_grammar(&mut diag);
+    print!("{}", grammar.export(*format));
+}
@@ -0,0 +1,55 @@
+//! Subcommand for formatting the grammar blocks in the book.
+
+use clap::ArgMatches;
+use walkdir::WalkDir;
+
+pub fn fmt(matches: &ArgMatches) {
+    let check = matches.get_flag("check");
+    let src = grammar::book_src_dir();
+    let mut failed = false;
+    let mut unformatted = Vec::new();
+    for entry in WalkDir::new(&src).sort_by_file_name() {
+        let entry = entry.unwrap();
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("md") {
+            continue;
+        }
+        let relative = path.strip_prefix(&src).unwrap();
+        let content = std::fs::read_to_string(path).unwrap();
+        let formatted = match grammar::format_markdown(&content, relative) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                for e in errors {
+                    eprintln!(
+                        "error: failed to parse grammar in {}: {e}",
+                        relative.display()
+                    );
+                }
+                failed = true;
+                continue;
+            }
+        };
+        if formatted == content {
+            continue;
+        }
+        if check {
+            unformatted.push(relative.to_owned());
+        } else if let Err(e) = std::fs::write(path, formatted) {
+            eprintln!("error: failed to write {}: {e}", path.display());
+            failed = true;
+        } else {
+            println!("formatted {}", relative.display());
+        }
+    }
+    if !unformatted.is_empty() {
+        eprintln!("The grammar in these files is not formatted:");
+        for path in &unformatted {
+            eprintln!("    {}", path.display());
+        }
+        eprintln!("\nRun `cargo run -p grammar-check -- fmt` to format them.");
+        failed = true;
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -13,7 +13,7 @@ use crate::tools::{pm2, rustc};
 use crate::{Message, Tool, display_line};
 use clap::ArgMatches;
 use diagnostics::Diagnostics;
-use grammar::Grammar;
+use grammar::{Grammar, TokenTables};
 use parser::Edition;
 use parser::ParseError;
 use parser::coverage::Coverage;
@@ -118,6 +118,7 @@ fn compare_loop(
     let mut coverage = Coverage::default();
     let channel = opts.lock().unwrap().channel.clone();
     let edition = opts.lock().unwrap().edition();
+    let tables = grammar.token_tables();
     loop {
         let mut opts_l = opts.lock().unwrap();
         let Some((name, src)) = opts_l.next() else {
@@ -126,7 +127,7 @@ fn compare_loop(
         let tools = opts_l.tools.clone();
         drop(opts_l);
         let lexer_result = match std::panic::catch_unwind(AssertUnwindSafe(|| {
-            parser::lexer::tokenize(&grammar, &mut coverage, &src)
+            parser::lexer::tokenize(&grammar, &mut coverage, &src, edition)
         })) {
             Ok(r) => r,
             Err(_) => {
@@ -146,7 +147,7 @@ fn compare_loop(
 
         for tool in &*tools {
             match std::panic::catch_unwind(|| {
-                compare_src(lexer_result.clone(), &name, &src, *tool, edition)
+                compare_src(lexer_result.clone(), &name, &src, *tool, edition, &tables)
             }) {
                 Ok(Ok(())) => {}
                 Ok(Err(e)) => {
@@ -181,6 +182,7 @@ fn compare_src(
     src: &str,
     tool: Tool,
     edition: Edition,
+    tables: &TokenTables,
 ) -> Result<(), String> {
     let (tool_result, mut lexer_result) = match tool {
         Tool::RustcParse => {
@@ -216,7 +218,7 @@ fn compare_src(
             {
                 replace(&frontmatter.range);
             }
-            let pm2_result = pm2::tokenize(&stripped_src);
+            let pm2_result = pm2::tokenize(tables, &stripped_src);
             pm2::normalize(pm2_result, lexer_result, src)
         }
         _ => unreachable!(),
@@ -229,6 +231,7 @@ fn compare_src(
         lexer_result = Err(ParseError {
             byte_offset: invalid.range.start,
             message: format!("invalid token {}", invalid.name),
+            expected: Vec::new(),
         });
     }
 
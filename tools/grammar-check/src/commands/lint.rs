@@ -0,0 +1,23 @@
+//! Subcommand for checking the style of the grammar.
+
+use clap::ArgMatches;
+use diagnostics::Diagnostics;
+
+pub fn lint(matches: &ArgMatches) {
+    if matches.get_flag("list") {
+        for lint in grammar::LINTS {
+            println!("{}: {}", lint.name, lint.description);
+        }
+        for (name, description) in grammar::LOAD_CHECKS {
+            println!("{name}: {description}");
+        }
+        return;
+    }
+    let mut diag = Diagnostics::new();
+    let grammar = grammar::load_grammar(&mut diag);
+    grammar.lint(&mut diag);
+    if diag.count > 0 {
+        eprintln!("{} problems found", diag.count);
+        std::process::exit(1);
+    }
+}
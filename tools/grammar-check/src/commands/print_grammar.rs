@@ -3,26 +3,40 @@
 //! This is helpful for getting a consolidated capture of all the grammar
 //! rules in a plain text format for doing manual analysis and other
 //! debugging.
+//!
+//! `--json` prints the grammar in the JSON format described by
+//! `--json-schema`, for consumption by other tools.
 
 use clap::ArgMatches;
 use diagnostics::Diagnostics;
 
 pub fn print_grammar(matches: &ArgMatches) {
+    if matches.get_flag("json-schema") {
+        print!("{}", grammar::JSON_SCHEMA);
+        return;
+    }
     let debug = matches.get_flag("debug");
+    let json = matches.get_flag("json");
     let mut diag = Diagnostics::new();
     let grammar = grammar::load_grammar(&mut diag);
 
-    if debug {
+    if json {
+        println!("{}", grammar.to_json());
+    } else if debug {
         for name in &grammar.name_order {
             let production = grammar.productions.get(name).unwrap();
-            println!("{} ->", name);
+            println!("{} ->", production.name_with_parameters());
             println!("{:#?}", production.expression);
             println!();
         }
     } else {
         for name in &grammar.name_order {
             let production = grammar.productions.get(name).unwrap();
-            println!("{} -> {}", name, production.expression);
+            println!(
+                "{} -> {}",
+                production.name_with_parameters(),
+                production.expression
+            );
             println!();
         }
     }
@@ -0,0 +1,39 @@
+//! Subcommand for finding productions that match a query.
+//!
+//! See the `query` module of the `grammar` crate for the query syntax.
+
+use clap::ArgMatches;
+use diagnostics::Diagnostics;
+use grammar::Query;
+
+pub fn query(matches: &ArgMatches) {
+    // The query can be given as several arguments so that it doesn't need
+    // to be quoted.
+    let text = matches
+        .get_many::<String>("QUERY")
+        .unwrap()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let query: Query = match text.parse() {
+        Ok(query) => query,
+        Err(e) => {
+            eprintln!("error: invalid query: {e}");
+            std::process::exit(1);
+        }
+    };
+    let mut diag = Diagnostics::new();
+    let grammar = grammar::load_grammar(&mut diag);
+    let results = match grammar.query(&query) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+    };
+    if matches.get_flag("json") {
+        println!("{}", results.to_json());
+    } else {
+        print!("{results}");
+    }
+}
@@ -16,15 +16,12 @@ use diagnostics::Diagnostics;
 use grammar::{Expression, ExpressionKind, Grammar};
 use std::collections::{HashMap, HashSet};
 
-// Multi-character tokens that may need to be split
-const MULTI_CHAR_TOKENS: &[&str] = &[
-    "...", "..=", "<<=", ">>=", "!=", "%=", "&&", "&=", "*=", "+=", "-=", "->", "..", "/=", "::",
-    "<-", "<<", "<=", "==", "=>", ">=", ">>", "^=", "|=", "||",
-];
-
 pub fn split_check(_matches: &ArgMatches) {
     let mut diag = Diagnostics::new();
     let grammar = grammar::load_grammar(&mut diag);
+    let tables = grammar.token_tables();
+    // Multi-character tokens that may need to be split
+    let multi_char_tokens: Vec<&str> = tables.multi_char_punctuation().collect();
 
     println!("Checking grammar for potential token splitting locations...\n");
 
@@ -38,6 +35,7 @@ pub fn split_check(_matches: &ArgMatches) {
         let mut visited = HashSet::new();
         find_split_locations(
             &grammar,
+            &multi_char_tokens,
             &production.expression,
             &mut locations_in_prod,
             prod_name,
@@ -56,7 +54,7 @@ pub fn split_check(_matches: &ArgMatches) {
     if split_locations.is_empty() {
         println!("No potential token splitting locations found.");
     } else {
-        for token in MULTI_CHAR_TOKENS {
+        for token in &multi_char_tokens {
             if let Some(locations) = split_locations.get(token) {
                 println!("Token: `{}`", token);
                 println!("  Locations: {}", locations.len());
@@ -71,6 +69,7 @@ pub fn split_check(_matches: &ArgMatches) {
 
 fn find_split_locations<'a>(
     grammar: &'a Grammar,
+    tokens: &[&'a str],
     expr: &'a Expression,
     locations: &mut Vec<(&'a str, String)>,
     current_production: &str,
@@ -78,17 +77,17 @@ fn find_split_locations<'a>(
 ) {
     match &expr.kind {
         ExpressionKind::Grouped(e) => {
-            find_split_locations(grammar, e, locations, current_production, visited);
+            find_split_locations(grammar, tokens, e, locations, current_production, visited);
         }
         ExpressionKind::Alt(es) => {
             for e in es {
-                find_split_locations(grammar, e, locations, current_production, visited);
+                find_split_locations(grammar, tokens, e, locations, current_production, visited);
             }
         }
         ExpressionKind::Sequence(es) => {
             // Check for adjacent elements that might require token splitting
             for (i, e) in es.iter().enumerate() {
-                find_split_locations(grammar, e, locations, current_production, visited);
+                find_split_locations(grammar, tokens, e, locations, current_production, visited);
 
                 // Check if this element could combine with following elements
                 // Skip non-token-producing elements (Break, Comment) when looking for the next element
@@ -105,6 +104,7 @@ fn find_split_locations<'a>(
                         ) {
                             check_adjacent_for_splits(
                                 grammar,
+                                tokens,
                                 e,
                                 next,
                                 locations,
@@ -120,16 +120,23 @@ fn find_split_locations<'a>(
         | ExpressionKind::NegativeLookahead(e)
         | ExpressionKind::NegExpression(e)
         | ExpressionKind::Cut(e) => {
-            find_split_locations(grammar, e, locations, current_production, visited);
+            find_split_locations(grammar, tokens, e, locations, current_production, visited);
         }
         ExpressionKind::Repeat(e) | ExpressionKind::RepeatPlus(e) => {
-            find_split_locations(grammar, e, locations, current_production, visited);
+            find_split_locations(grammar, tokens, e, locations, current_production, visited);
             // Check if repeating this element could create a multi-char token
-            check_repeat_for_splits(grammar, e, locations, current_production, "repeat");
+            check_repeat_for_splits(grammar, tokens, e, locations, current_production, "repeat");
         }
         ExpressionKind::RepeatRange { expr: e, .. } | ExpressionKind::RepeatRangeNamed(e, _) => {
-            find_split_locations(grammar, e, locations, current_production, visited);
-            check_repeat_for_splits(grammar, e, locations, current_production, "repeat range");
+            find_split_locations(grammar, tokens, e, locations, current_production, visited);
+            check_repeat_for_splits(
+                grammar,
+                tokens,
+                e,
+                locations,
+                current_production,
+                "repeat range",
+            );
         }
         ExpressionKind::Nt(_nt) => {
             // Don't recurse into nonterminals - we only want to find direct uses
@@ -138,7 +145,7 @@ fn find_split_locations<'a>(
         }
         ExpressionKind::Terminal(term) => {
             // Check if this terminal is a multi-char token
-            for &multi_token in MULTI_CHAR_TOKENS {
+            for &multi_token in tokens {
                 if term == multi_token {
                     locations.push((
                         multi_token,
@@ -175,15 +182,16 @@ fn describe_expression(expr: &Expression) -> String {
 
 fn check_adjacent_for_splits<'a>(
     grammar: &'a Grammar,
+    tokens: &[&'a str],
     left: &'a Expression,
     right: &'a Expression,
     locations: &mut Vec<(&'a str, String)>,
     _current_production: &str,
 ) {
     // Get the possible ending tokens from the left expression
-    let left_endings = get_possible_endings(grammar, left);
+    let left_endings = get_possible_endings(grammar, tokens, left);
     // Get the possible starting tokens from the right expression
-    let right_starts = get_possible_starts(grammar, right);
+    let right_starts = get_possible_starts(grammar, tokens, right);
 
     // Get descriptions of the left and right elements
     let left_desc = describe_expression(left);
@@ -193,7 +201,7 @@ fn check_adjacent_for_splits<'a>(
     for left_end in &left_endings {
         for right_start in &right_starts {
             let combined = format!("{}{}", left_end, right_start);
-            for &multi_token in MULTI_CHAR_TOKENS {
+            for &multi_token in tokens {
                 if combined == multi_token {
                     // Exact match - the two elements combine to form the token
                     locations.push((
@@ -230,14 +238,15 @@ fn check_adjacent_for_splits<'a>(
 
 fn check_repeat_for_splits<'a>(
     grammar: &'a Grammar,
+    tokens: &[&'a str],
     expr: &'a Expression,
     locations: &mut Vec<(&'a str, String)>,
     _current_production: &str,
     repeat_type: &str,
 ) {
     // Get the possible endings and starts from the expression
-    let endings = get_possible_endings(grammar, expr);
-    let starts = get_possible_starts(grammar, expr);
+    let endings = get_possible_endings(grammar, tokens, expr);
+    let starts = get_possible_starts(grammar, tokens, expr);
 
     let expr_desc = describe_expression(expr);
 
@@ -245,7 +254,7 @@ fn check_repeat_for_splits<'a>(
     for ending in &endings {
         for start in &starts {
             let combined = format!("{}{}", ending, start);
-            for &multi_token in MULTI_CHAR_TOKENS {
+            for &multi_token in tokens {
                 if combined == multi_token {
                     locations.push((
                         multi_token,
@@ -268,14 +277,15 @@ fn check_repeat_for_splits<'a>(
     }
 }
 
-fn get_possible_endings(grammar: &Grammar, expr: &Expression) -> HashSet<String> {
+fn get_possible_endings(grammar: &Grammar, tokens: &[&str], expr: &Expression) -> HashSet<String> {
     let mut endings = HashSet::new();
-    get_possible_endings_impl(grammar, expr, &mut endings, &mut HashSet::new());
+    get_possible_endings_impl(grammar, tokens, expr, &mut endings, &mut HashSet::new());
     endings
 }
 
 fn get_possible_endings_impl(
     grammar: &Grammar,
+    tokens: &[&str],
     expr: &Expression,
     endings: &mut HashSet<String>,
     visited: &mut HashSet<String>,
@@ -296,11 +306,11 @@ fn get_possible_endings_impl(
         | ExpressionKind::RepeatRangeNamed(e, _)
         | ExpressionKind::NegExpression(e)
         | ExpressionKind::Cut(e) => {
-            get_possible_endings_impl(grammar, e, endings, visited);
+            get_possible_endings_impl(grammar, tokens, e, endings, visited);
         }
         ExpressionKind::Alt(es) => {
             for e in es {
-                get_possible_endings_impl(grammar, e, endings, visited);
+                get_possible_endings_impl(grammar, tokens, e, endings, visited);
             }
         }
         ExpressionKind::Sequence(es) => {
@@ -311,7 +321,7 @@ fn get_possible_endings_impl(
                     e.kind,
                     ExpressionKind::Break(_) | ExpressionKind::Comment(_)
                 ) {
-                    get_possible_endings_impl(grammar, e, endings, visited);
+                    get_possible_endings_impl(grammar, tokens, e, endings, visited);
                     break;
                 }
             }
@@ -319,13 +329,13 @@ fn get_possible_endings_impl(
         ExpressionKind::Nt(nt) => {
             if visited.insert(nt.clone()) {
                 if let Some(prod) = grammar.productions.get(nt) {
-                    get_possible_endings_impl(grammar, &prod.expression, endings, visited);
+                    get_possible_endings_impl(grammar, tokens, &prod.expression, endings, visited);
                 }
             }
         }
         ExpressionKind::Charset(chars) => {
             for ch in chars {
-                get_possible_endings_impl(grammar, ch, endings, visited);
+                get_possible_endings_impl(grammar, tokens, ch, endings, visited);
             }
         }
         ExpressionKind::CharacterRange(a, b) => {
@@ -340,7 +350,7 @@ fn get_possible_endings_impl(
             // Handle "Token" prose - it can be any token
             if text.to_lowercase().contains("token") {
                 // Add all characters that could be part of multi-char tokens
-                for &token in MULTI_CHAR_TOKENS {
+                for &token in tokens {
                     for ch in token.chars() {
                         endings.insert(ch.to_string());
                     }
@@ -353,14 +363,15 @@ fn get_possible_endings_impl(
     }
 }
 
-fn get_possible_starts(grammar: &Grammar, expr: &Expression) -> HashSet<String> {
+fn get_possible_starts(grammar: &Grammar, tokens: &[&str], expr: &Expression) -> HashSet<String> {
     let mut starts = HashSet::new();
-    get_possible_starts_impl(grammar, expr, &mut starts, &mut HashSet::new());
+    get_possible_starts_impl(grammar, tokens, expr, &mut starts, &mut HashSet::new());
     starts
 }
 
 fn get_possible_starts_impl(
     grammar: &Grammar,
+    tokens: &[&str],
     expr: &Expression,
     starts: &mut HashSet<String>,
     visited: &mut HashSet<String>,
@@ -380,15 +391,15 @@ fn get_possible_starts_impl(
         | ExpressionKind::RepeatRangeNamed(e, _)
         | ExpressionKind::NegExpression(e)
         | ExpressionKind::Cut(e) => {
-            get_possible_starts_impl(grammar, e, starts, visited);
+            get_possible_starts_impl(grammar, tokens, e, starts, visited);
         }
         ExpressionKind::Optional(e) => {
-            get_possible_starts_impl(grammar, e, starts, visited);
+            get_possible_starts_impl(grammar, tokens, e, starts, visited);
             // Optional also means the next element could be the start
         }
         ExpressionKind::Alt(es) => {
             for e in es {
-                get_possible_starts_impl(grammar, e, starts, visited);
+                get_possible_starts_impl(grammar, tokens, e, starts, visited);
             }
         }
         ExpressionKind::Sequence(es) => {
@@ -399,7 +410,7 @@ fn get_possible_starts_impl(
                     e.kind,
                     ExpressionKind::Break(_) | ExpressionKind::Comment(_)
                 ) {
-                    get_possible_starts_impl(grammar, e, starts, visited);
+                    get_possible_starts_impl(grammar, tokens, e, starts, visited);
                     break;
                 }
             }
@@ -407,13 +418,13 @@ fn get_possible_starts_impl(
         ExpressionKind::Nt(nt) => {
             if visited.insert(nt.clone()) {
                 if let Some(prod) = grammar.productions.get(nt) {
-                    get_possible_starts_impl(grammar, &prod.expression, starts, visited);
+                    get_possible_starts_impl(grammar, tokens, &prod.expression, starts, visited);
                 }
             }
         }
         ExpressionKind::Charset(chars) => {
             for ch in chars {
-                get_possible_starts_impl(grammar, ch, starts, visited);
+                get_possible_starts_impl(grammar, tokens, ch, starts, visited);
             }
         }
         ExpressionKind::CharacterRange(a, b) => {
@@ -427,7 +438,7 @@ fn get_possible_starts_impl(
             // Handle "Token" prose - it can be any token
             if text.to_lowercase().contains("token") {
                 // Add all characters that could be part of multi-char tokens
-                for &token in MULTI_CHAR_TOKENS {
+                for &token in tokens {
                     for ch in token.chars() {
                         starts.insert(ch.to_string());
                     }
@@ -4,6 +4,7 @@ use crate::tools::{pm2, rustc, rustc_lexer};
 use crate::{CommonOptions, Tool, display_line};
 use clap::ArgMatches;
 use diagnostics::Diagnostics;
+use grammar::{Grammar, TokenTables};
 use parser::Edition;
 use parser::coverage::Coverage;
 use parser::lexer::Tokens;
@@ -12,23 +13,24 @@ use std::ops::Range;
 pub fn tokenize(matches: &ArgMatches) {
     let (mut opts, _) = CommonOptions::new(matches, &[Tool::Reference]);
     opts.progress.finish_and_clear();
+    let mut diag = Diagnostics::new();
+    let grammar = grammar::load_grammar_with_frontmatter(&mut diag);
+    let tables = grammar.token_tables();
     for tool in &*opts.tools.clone() {
         while let Some((name, src)) = opts.next() {
             println!("------------------------------------------------------------");
             println!("tool `{tool}` token results for `{name}`:");
-            tokenize_src(&src, *tool, opts.edition());
+            tokenize_src(&grammar, &tables, &src, *tool, opts.edition());
             println!("------------------------------------------------------------");
         }
     }
 }
 
-fn tokenize_src(src: &str, tool: Tool, edition: Edition) {
+fn tokenize_src(grammar: &Grammar, tables: &TokenTables, src: &str, tool: Tool, edition: Edition) {
     let tokens = match tool {
         Tool::Reference => {
-            let mut diag = Diagnostics::new();
-            let grammar = grammar::load_grammar_with_frontmatter(&mut diag);
             let mut coverage = Coverage::default();
-            let tokens = parser::lexer::tokenize(&grammar, &mut coverage, src);
+            let tokens = parser::lexer::tokenize(grammar, &mut coverage, src, edition);
             if let Ok(Tokens {
                 shebang: Some(shebang),
                 ..
@@ -46,7 +48,7 @@ fn tokenize_src(src: &str, tool: Tool, edition: Edition) {
             tokens.map(|ts| ts.tokens)
         }
         Tool::RustcParse => rustc::tokenize(src, edition),
-        Tool::ProcMacro2 => pm2::tokenize(src),
+        Tool::ProcMacro2 => pm2::tokenize(tables, src),
         Tool::RustcLexer => rustc_lexer::tokenize(src),
     };
     let tokens = match tokens {
@@ -6,53 +6,91 @@
 use crate::{CommonOptions, Tool, display_line};
 use clap::ArgMatches;
 use diagnostics::Diagnostics;
+use parser::Edition;
+use parser::memo::Memo;
 use std::ops::Range;
 
 pub fn tree(matches: &ArgMatches) {
     let (mut opts, _) = CommonOptions::new(matches, &[Tool::Reference]);
     opts.progress.finish_and_clear();
     let production = matches.get_one::<String>("production").unwrap();
+    let tree_opts = TreeOptions {
+        memo: matches.get_flag("memo"),
+        recover: matches.get_flag("recover"),
+        edition: opts.edition(),
+    };
     for tool in &*opts.tools.clone() {
         while let Some((name, src)) = opts.next() {
             println!("------------------------------------------------------------");
             println!("tool `{tool}` tree results for `{name}`:");
-            display_tree(&src, *tool, production);
+            display_tree(&src, *tool, production, tree_opts);
             println!("------------------------------------------------------------");
         }
     }
 }
 
-fn display_tree(src: &str, tool: Tool, production: &str) {
+#[derive(Clone, Copy)]
+struct TreeOptions {
+    /// Memoize the productions, and print statistics about the memo table.
+    memo: bool,
+    /// Recover from errors, and print the partial tree and all the errors.
+    recover: bool,
+    edition: Edition,
+}
+
+fn display_tree(src: &str, tool: Tool, production: &str, opts: TreeOptions) {
     match tool {
-        Tool::Reference => display_reference_tree(src, production),
+        Tool::Reference => display_reference_tree(src, production, opts),
         _ => unimplemented!("{tool} not implemented yet"),
     }
 }
 
-fn display_reference_tree(src: &str, production: &str) {
+fn display_reference_tree(src: &str, production: &str, opts: TreeOptions) {
     let mut diag = Diagnostics::new();
     let grammar = grammar::load_grammar_with_frontmatter(&mut diag);
-    let node = match parser::tree::parse(&grammar, src, production) {
+    if opts.recover {
+        let (node, errors) =
+            parser::tree::parse_recovering(&grammar, src, production, opts.edition);
+        display_tree_node(src, &node, 0);
+        for e in &errors {
+            display_error(src, e);
+        }
+        return;
+    }
+    let result = if opts.memo {
+        let mut memo = Memo::default();
+        let result =
+            parser::tree::parse_with_memo(&grammar, src, production, opts.edition, &mut memo);
+        eprintln!("memo: {}", memo.stats());
+        result
+    } else {
+        parser::tree::parse(&grammar, src, production, opts.edition)
+    };
+    let node = match result {
         Ok(node) => node,
         Err(e) => {
-            eprintln!(
-                "error: {}\n\
-                {}",
-                e.message,
-                display_line(
-                    src,
-                    &Range {
-                        start: e.byte_offset,
-                        end: e.byte_offset + 1
-                    }
-                )
-            );
+            display_error(src, &e);
             std::process::exit(1);
         }
     };
     display_tree_node(src, &node, 0);
 }
 
+fn display_error(src: &str, e: &parser::ParseError) {
+    eprintln!(
+        "error: {}\n\
+        {}",
+        e.message,
+        display_line(
+            src,
+            &Range {
+                start: e.byte_offset,
+                end: e.byte_offset + 1
+            }
+        )
+    );
+}
+
 fn display_tree_node(src: &str, node: &parser::Node, indent: usize) {
     let node_text = &src[node.range.clone()];
     let display_text = if node_text.len() > 20 {
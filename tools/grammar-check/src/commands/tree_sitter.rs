@@ -0,0 +1,22 @@
+//! Subcommand for generating a tree-sitter grammar.
+//!
+//! The report of productions that need an external scanner, and of the
+//! places where the generated grammar is approximate, is printed to stdout.
+
+use clap::ArgMatches;
+use diagnostics::Diagnostics;
+use std::path::PathBuf;
+
+pub fn tree_sitter(matches: &ArgMatches) {
+    let name = matches.get_one::<String>("name").unwrap();
+    let output = matches.get_one::<PathBuf>("output").unwrap();
+    let mut diag = Diagnostics::new();
+    let grammar = grammar::load_grammar(&mut diag);
+    let ts = grammar.to_tree_sitter(name);
+    if let Err(e) = std::fs::write(output, &ts.grammar_js) {
+        eprintln!("error: failed to write {}: {e}", output.display());
+        std::process::exit(1);
+    }
+    println!("wrote {}\n", output.display());
+    print!("{}", ts.report());
+}
@@ -24,11 +24,17 @@ use walkdir::WalkDir;
 mod permute;
 mod test_cases;
 mod commands {
+    pub mod diff;
+    pub mod export;
+    pub mod fmt;
     pub mod lex_compare;
+    pub mod lint;
     pub mod print_grammar;
+    pub mod query;
     pub mod split_check;
     pub mod tokenize;
     pub mod tree;
+    pub mod tree_sitter;
 }
 mod tools {
     pub mod pm2;
@@ -364,6 +370,11 @@ fn main() {
                 .arg(
                     arg!(--production <NAME> "the production name to parse").default_value("Crate"),
                 )
+                .arg(arg!(--memo "memoize productions and print memo table statistics"))
+                .arg(
+                    arg!(--recover "recover from errors and print all of them")
+                        .conflicts_with("memo"),
+                )
                 .args(common_args()),
         )
         .subcommand(
@@ -373,7 +384,60 @@ fn main() {
         .subcommand(
             Command::new("print-grammar")
                 .about("Print the grammar to stdout")
-                .arg(arg!(--debug "Print using Debug format")),
+                .arg(arg!(--debug "Print using Debug format"))
+                .arg(arg!(--json "Print in the JSON format").conflicts_with("debug"))
+                .arg(
+                    arg!(--"json-schema" "Print the JSON Schema of the JSON format")
+                        .conflicts_with_all(["debug", "json"]),
+                ),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Compare the grammar in two copies of the book")
+                .arg(
+                    arg!(<OLD> "path to the `src` directory of the old version")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .arg(
+                    arg!(<NEW> "path to the `src` directory of the new version")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .arg(arg!(--json "Print the differences as JSON")),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Export the grammar to a standard notation")
+                .arg(
+                    arg!(--format <FORMAT> "notation to export to: w3c-ebnf, abnf, or iso-ebnf")
+                        .value_parser(clap::value_parser!(grammar::ExportFormat))
+                        .default_value("w3c-ebnf"),
+                ),
+        )
+        .subcommand(
+            Command::new("fmt")
+                .about("Format the grammar blocks in the book")
+                .arg(arg!(--check "Check that the grammar is formatted, without changing it")),
+        )
+        .subcommand(
+            Command::new("lint")
+                .about("Check the grammar for style problems")
+                .arg(arg!(--list "List the lints and what they check")),
+        )
+        .subcommand(
+            Command::new("query")
+                .about("List the productions that match a query")
+                .arg(arg!(<QUERY> ... "the query, such as `category:items and uses:Visibility`"))
+                .arg(arg!(--json "Print the results as JSON")),
+        )
+        .subcommand(
+            Command::new("tree-sitter")
+                .about("Generate a tree-sitter grammar.js from the grammar")
+                .arg(arg!(--name <NAME> "name of the language").default_value("rust_reference"))
+                .arg(
+                    arg!(--output <PATH> "where to write grammar.js")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .default_value("grammar.js"),
+                ),
         )
         .get_matches();
     match matches.subcommand() {
@@ -392,6 +456,24 @@ fn main() {
         Some(("print-grammar", sub_matches)) => {
             commands::print_grammar::print_grammar(sub_matches);
         }
+        Some(("diff", sub_matches)) => {
+            commands::diff::diff(sub_matches);
+        }
+        Some(("export", sub_matches)) => {
+            commands::export::export(sub_matches);
+        }
+        Some(("fmt", sub_matches)) => {
+            commands::fmt::fmt(sub_matches);
+        }
+        Some(("lint", sub_matches)) => {
+            commands::lint::lint(sub_matches);
+        }
+        Some(("query", sub_matches)) => {
+            commands::query::query(sub_matches);
+        }
+        Some(("tree-sitter", sub_matches)) => {
+            commands::tree_sitter::tree_sitter(sub_matches);
+        }
         _ => unreachable!(),
     }
 }
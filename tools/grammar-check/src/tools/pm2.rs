@@ -1,5 +1,6 @@
 //! The proc-macro2 tool.
 
+use grammar::TokenTables;
 use parser::lexer::Tokens;
 use parser::{Node, ParseError};
 use proc_macro2::{Spacing, TokenStream, TokenTree};
@@ -8,13 +9,14 @@ use std::ops::Range;
 use std::str::FromStr;
 use std::sync::LazyLock;
 
-pub fn tokenize(src: &str) -> Result<Vec<Node>, ParseError> {
+pub fn tokenize(tables: &TokenTables, src: &str) -> Result<Vec<Node>, ParseError> {
     let mut tokens = Vec::new();
     let stream = TokenStream::from_str(src).map_err(|e| ParseError {
         byte_offset: 0,
         message: e.to_string(),
+        expected: Vec::new(),
     })?;
-    tokens_from_ts(src, stream, &mut tokens)?;
+    tokens_from_ts(tables, src, stream, &mut tokens)?;
     Ok(tokens)
 }
 
@@ -61,7 +63,12 @@ static NUM_DOT: LazyLock<Regex> = LazyLock::new(|| {
     .unwrap()
 });
 
-fn tokens_from_ts(src: &str, ts: TokenStream, output: &mut Vec<Node>) -> Result<(), ParseError> {
+fn tokens_from_ts(
+    tables: &TokenTables,
+    src: &str,
+    ts: TokenStream,
+    output: &mut Vec<Node>,
+) -> Result<(), ParseError> {
     let trees: Vec<TokenTree> = ts.into_iter().collect();
     let mut i = 0;
     while i < trees.len() {
@@ -86,6 +93,7 @@ fn tokens_from_ts(src: &str, ts: TokenStream, output: &mut Vec<Node>) -> Result<
                     return Err(ParseError {
                         message: "RESERVED_TOKEN_DOUBLE_QUOTE".to_string(),
                         byte_offset: range.end,
+                        expected: Vec::new(),
                     });
                 }
                 if src[range.end..].chars().next() == Some('\'')
@@ -94,6 +102,7 @@ fn tokens_from_ts(src: &str, ts: TokenStream, output: &mut Vec<Node>) -> Result<
                     return Err(ParseError {
                         message: "RESERVED_TOKEN_SINGLE_QUOTE".to_string(),
                         byte_offset: range.end,
+                        expected: Vec::new(),
                     });
                 }
 
@@ -101,6 +110,7 @@ fn tokens_from_ts(src: &str, ts: TokenStream, output: &mut Vec<Node>) -> Result<
                     return Err(ParseError {
                         message: "RESERVED_TOKEN_POUND".to_string(),
                         byte_offset: range.start,
+                        expected: Vec::new(),
                     });
                 }
 
@@ -128,7 +138,7 @@ fn tokens_from_ts(src: &str, ts: TokenStream, output: &mut Vec<Node>) -> Result<
                     match &trees[i] {
                         TokenTree::Punct(next_p) => {
                             s.push(next_p.as_char());
-                            if is_valid_punctuation(&s) {
+                            if tables.is_punctuation(&s) {
                                 range.end = next_p.span().byte_range().end;
                                 current_spacing = next_p.spacing();
                                 i += 1;
@@ -153,6 +163,7 @@ fn tokens_from_ts(src: &str, ts: TokenStream, output: &mut Vec<Node>) -> Result<
                                     return Err(ParseError {
                                         message: "RESERVED_TOKEN_SINGLE_QUOTE".to_string(),
                                         byte_offset: prev_range.start,
+                                        expected: Vec::new(),
                                     });
                                 }
                             }
@@ -173,6 +184,7 @@ fn tokens_from_ts(src: &str, ts: TokenStream, output: &mut Vec<Node>) -> Result<
                     return Err(ParseError {
                         message: "RESERVED_GUARDED_STRING_LITERAL".to_string(),
                         byte_offset: range.start,
+                        expected: Vec::new(),
                     });
                 }
 
@@ -190,6 +202,7 @@ fn tokens_from_ts(src: &str, ts: TokenStream, output: &mut Vec<Node>) -> Result<
                     return Err(ParseError {
                         message: "bad E suffix".to_string(),
                         byte_offset: range.start,
+                        expected: Vec::new(),
                     });
                 }
 
@@ -203,6 +216,7 @@ fn tokens_from_ts(src: &str, ts: TokenStream, output: &mut Vec<Node>) -> Result<
                     return Err(ParseError {
                         message: "invalid byte or char literal".to_string(),
                         byte_offset: range.start,
+                        expected: Vec::new(),
                     });
                 }
 
@@ -211,6 +225,7 @@ fn tokens_from_ts(src: &str, ts: TokenStream, output: &mut Vec<Node>) -> Result<
                     return Err(ParseError {
                         message: "underscore suffix not allowed".to_string(),
                         byte_offset: range.start,
+                        expected: Vec::new(),
                     });
                 }
 
@@ -226,6 +241,7 @@ fn tokens_from_ts(src: &str, ts: TokenStream, output: &mut Vec<Node>) -> Result<
                         return Err(ParseError {
                             message: "reserved bin/oct/hex literal followed by .".to_string(),
                             byte_offset: range.start,
+                            expected: Vec::new(),
                         });
                     }
                 }
@@ -247,7 +263,7 @@ fn tokens_from_ts(src: &str, ts: TokenStream, output: &mut Vec<Node>) -> Result<
                         group.span_open().byte_range(),
                     ));
                 }
-                tokens_from_ts(src, group.stream(), output)?;
+                tokens_from_ts(tables, src, group.stream(), output)?;
                 if !delim_str.is_empty() {
                     let close_delim = match delim_str {
                         "(" => ")",
@@ -326,37 +342,6 @@ fn lit_to_reference(lit: &str) -> String {
     }
 }
 
-fn is_valid_punctuation(s: &str) -> bool {
-    matches!(
-        s,
-        "..."
-            | "..="
-            | "<<="
-            | ">>="
-            | "!="
-            | "%="
-            | "&&"
-            | "&="
-            | "*="
-            | "+="
-            | "-="
-            | "->"
-            | ".."
-            | "/="
-            | "::"
-            | "<-"
-            | "<<"
-            | "<="
-            | "=="
-            | "=>"
-            | ">="
-            | ">>"
-            | "^="
-            | "|="
-            | "||"
-    )
-}
-
 pub fn normalize(
     pm2_result: Result<Vec<Node>, ParseError>,
     reference_result: Result<Tokens, ParseError>,
@@ -371,6 +356,7 @@ pub fn normalize(
                 Err(ParseError {
                     message: "unexpected NBSP whitespace".to_string(),
                     byte_offset: e.byte_offset,
+                    expected: Vec::new(),
                 })
             } else {
                 pm2_result
@@ -144,6 +144,7 @@ pub fn tokenize(src: &str, edition: Edition) -> Result<Vec<Node>, ParseError> {
             ParseError {
                 byte_offset: byte_offset as usize,
                 message,
+                expected: Vec::new(),
             }
         })
     })
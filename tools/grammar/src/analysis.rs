@@ -0,0 +1,609 @@
+//! Well-formedness analysis of the grammar.
+//!
+//! [`Grammar::analyze`] computes which productions can match the empty
+//! string (nullable), and the FIRST and FOLLOW sets of every production.
+//! These are used by [`check_well_formed`] to find problems that would cause
+//! the parser interpreter to loop forever or to ignore part of the grammar:
+//!
+//! - Left recursion, where a production can reach itself without consuming
+//!   any input.
+//! - A `*` or `+` repetition of an expression that can match the empty
+//!   string.
+//! - Alternatives that can never match, because an earlier alternative never
+//!   fails (ordered choice always picks the first one that matches).
+
+use crate::charset::CharSet;
+use crate::lint::is_allowed;
+use crate::{Expression, ExpressionKind, Grammar, Production};
+use diagnostics::{Diagnostics, warn_or_err};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
+
+/// An element that can appear in a FIRST or FOLLOW set.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Symbol {
+    /// A literal string, such as a keyword or punctuation.
+    Terminal(String),
+    /// A lexer production used as a token by a syntax production.
+    Token(String),
+    /// A single character from the given set.
+    Chars(CharSet),
+    /// A character described by prose.
+    Prose(String),
+    /// The end of the input.
+    End,
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Symbol::Terminal(s) => write!(f, "`{s}`"),
+            Symbol::Token(name) => write!(f, "{name}"),
+            Symbol::Chars(set) => write!(f, "{set}"),
+            Symbol::Prose(s) => write!(f, "<{s}>"),
+            Symbol::End => write!(f, "end of input"),
+        }
+    }
+}
+
+/// The results of [`Grammar::analyze`].
+#[derive(Debug, Default)]
+pub struct Analysis {
+    /// Productions that can succeed without consuming any input.
+    nullable: HashSet<String>,
+    /// Productions that always succeed.
+    infallible: HashSet<String>,
+    first: HashMap<String, BTreeSet<Symbol>>,
+    follow: HashMap<String, BTreeSet<Symbol>>,
+}
+
+impl Grammar {
+    /// Computes the nullable, FIRST, and FOLLOW sets of every production.
+    ///
+    /// Within syntax productions, a lexer production is treated as a single
+    /// [`Symbol::Token`]. Within lexer productions, nonterminals are expanded
+    /// down to characters and terminals.
+    pub fn analyze(&self) -> Analysis {
+        let mut analysis = Analysis::default();
+        analysis.compute_nullable(self);
+        analysis.compute_first(self);
+        analysis.compute_follow(self);
+        analysis
+    }
+}
+
+impl Analysis {
+    /// Whether the production can succeed without consuming any input.
+    pub fn is_nullable(&self, name: &str) -> bool {
+        self.nullable.contains(name)
+    }
+
+    /// The symbols that the production can start with.
+    pub fn first(&self, name: &str) -> Option<&BTreeSet<Symbol>> {
+        self.first.get(name)
+    }
+
+    /// The symbols that can come after the production.
+    pub fn follow(&self, name: &str) -> Option<&BTreeSet<Symbol>> {
+        self.follow.get(name)
+    }
+
+    /// Whether the expression can succeed without consuming any input.
+    pub fn expr_nullable(&self, e: &Expression) -> bool {
+        match &e.kind {
+            ExpressionKind::Grouped(e) | ExpressionKind::Cut(e) | ExpressionKind::RepeatPlus(e) => {
+                self.expr_nullable(e)
+            }
+            ExpressionKind::Alt(es) => es.iter().any(|e| self.expr_nullable(e)),
+            ExpressionKind::Sequence(es) => es.iter().all(|e| self.expr_nullable(e)),
+            ExpressionKind::RepeatRange { expr, min, .. } => {
+                min.unwrap_or(0) == 0 || self.expr_nullable(expr)
+            }
+            ExpressionKind::Optional(_)
+            | ExpressionKind::Repeat(_)
+            | ExpressionKind::NegativeLookahead(_)
+            // The named count may be zero.
+            | ExpressionKind::RepeatRangeNamed(..)
+            | ExpressionKind::Break(_)
+            | ExpressionKind::Comment(_) => true,
+            ExpressionKind::Nt(nt) => self.nullable.contains(nt),
+            ExpressionKind::Terminal(s) => s.is_empty(),
+            ExpressionKind::Prose(_)
+            | ExpressionKind::Charset(_)
+            | ExpressionKind::CharacterRange(..)
+            | ExpressionKind::NegExpression(_)
+            | ExpressionKind::Unicode(_) => false,
+        }
+    }
+
+    /// Whether the expression can never fail.
+    fn expr_infallible(&self, e: &Expression) -> bool {
+        match &e.kind {
+            ExpressionKind::Grouped(e) | ExpressionKind::Cut(e) | ExpressionKind::RepeatPlus(e) => {
+                self.expr_infallible(e)
+            }
+            ExpressionKind::Alt(es) => es.iter().any(|e| self.expr_infallible(e)),
+            ExpressionKind::Sequence(es) => es.iter().all(|e| self.expr_infallible(e)),
+            ExpressionKind::RepeatRange { expr, min, .. } => {
+                min.unwrap_or(0) == 0 || self.expr_infallible(expr)
+            }
+            ExpressionKind::Optional(_)
+            | ExpressionKind::Repeat(_)
+            | ExpressionKind::Break(_)
+            | ExpressionKind::Comment(_) => true,
+            ExpressionKind::Nt(nt) => self.infallible.contains(nt),
+            ExpressionKind::Terminal(s) => s.is_empty(),
+            ExpressionKind::NegativeLookahead(_)
+            | ExpressionKind::RepeatRangeNamed(..)
+            | ExpressionKind::Prose(_)
+            | ExpressionKind::Charset(_)
+            | ExpressionKind::CharacterRange(..)
+            | ExpressionKind::NegExpression(_)
+            | ExpressionKind::Unicode(_) => false,
+        }
+    }
+
+    /// The symbols that the expression can start with, when used in the
+    /// given production.
+    pub fn expr_first(
+        &self,
+        grammar: &Grammar,
+        prod: &Production,
+        e: &Expression,
+    ) -> BTreeSet<Symbol> {
+        let mut set = BTreeSet::new();
+        self.add_first(grammar, prod, e, &mut set);
+        set
+    }
+
+    fn add_first(
+        &self,
+        grammar: &Grammar,
+        prod: &Production,
+        e: &Expression,
+        set: &mut BTreeSet<Symbol>,
+    ) {
+        match &e.kind {
+            ExpressionKind::Grouped(e)
+            | ExpressionKind::Cut(e)
+            | ExpressionKind::Optional(e)
+            | ExpressionKind::Repeat(e)
+            | ExpressionKind::RepeatPlus(e)
+            | ExpressionKind::RepeatRange { expr: e, .. }
+            | ExpressionKind::RepeatRangeNamed(e, _) => self.add_first(grammar, prod, e, set),
+            ExpressionKind::Alt(es) => {
+                for e in es {
+                    self.add_first(grammar, prod, e, set);
+                }
+            }
+            ExpressionKind::Sequence(es) => {
+                for e in es {
+                    self.add_first(grammar, prod, e, set);
+                    if !self.expr_nullable(e) {
+                        break;
+                    }
+                }
+            }
+            // Lookahead doesn't consume anything.
+            ExpressionKind::NegativeLookahead(_)
+            | ExpressionKind::Break(_)
+            | ExpressionKind::Comment(_) => {}
+            ExpressionKind::Nt(nt) => {
+                if prod.category != "lexer"
+                    && grammar
+                        .productions
+                        .get(nt)
+                        .is_some_and(|p| p.category == "lexer")
+                {
+                    set.insert(Symbol::Token(nt.clone()));
+                } else if let Some(first) = self.first.get(nt) {
+                    set.extend(first.iter().cloned());
+                }
+            }
+            ExpressionKind::Terminal(s) => {
+                if !s.is_empty() {
+                    set.insert(Symbol::Terminal(s.clone()));
+                }
+            }
+            ExpressionKind::Prose(s) => {
+                set.insert(Symbol::Prose(s.clone()));
+            }
+            ExpressionKind::Charset(es) => match grammar.char_set(e) {
+                Some(chars) => {
+                    set.insert(Symbol::Chars(chars));
+                }
+                None => {
+                    for e in es {
+                        self.add_first(grammar, prod, e, set);
+                    }
+                }
+            },
+            ExpressionKind::CharacterRange(start, end) => {
+                set.insert(Symbol::Chars(CharSet::from_range(
+                    start.get_ch() as u32,
+                    end.get_ch() as u32,
+                )));
+            }
+            ExpressionKind::NegExpression(inner) => {
+                let chars = match grammar.char_set(inner) {
+                    Some(chars) => chars.complement(),
+                    None => CharSet::all(),
+                };
+                set.insert(Symbol::Chars(chars));
+            }
+            ExpressionKind::Unicode((ch, _)) => {
+                set.insert(Symbol::Chars(CharSet::from_char(*ch)));
+            }
+        }
+    }
+
+    fn compute_nullable(&mut self, grammar: &Grammar) {
+        // Iterate until a fixed point is reached. Both sets only grow.
+        loop {
+            let mut changed = false;
+            for prod in grammar.productions.values() {
+                if !self.nullable.contains(&prod.name) && self.expr_nullable(&prod.expression) {
+                    self.nullable.insert(prod.name.clone());
+                    changed = true;
+                }
+                if !self.infallible.contains(&prod.name) && self.expr_infallible(&prod.expression) {
+                    self.infallible.insert(prod.name.clone());
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    fn compute_first(&mut self, grammar: &Grammar) {
+        loop {
+            let mut changed = false;
+            for name in &grammar.name_order {
+                let prod = &grammar.productions[name];
+                let first = self.expr_first(grammar, prod, &prod.expression);
+                let entry = self.first.entry(name.clone()).or_default();
+                if *entry != first {
+                    *entry = first;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    fn compute_follow(&mut self, grammar: &Grammar) {
+        for prod in grammar.productions.values() {
+            let follow = self.follow.entry(prod.name.clone()).or_default();
+            if prod.is_root {
+                follow.insert(Symbol::End);
+            }
+        }
+        loop {
+            let mut follow = self.follow.clone();
+            for name in &grammar.name_order {
+                let prod = &grammar.productions[name];
+                let after = self.follow[name].clone();
+                self.add_follow(grammar, prod, &prod.expression, &after, &mut follow);
+            }
+            if follow == self.follow {
+                break;
+            }
+            self.follow = follow;
+        }
+    }
+
+    /// Adds to `follow` the FOLLOW entries of the nonterminals in `e`, where
+    /// `after` is what can come after `e`.
+    fn add_follow(
+        &self,
+        grammar: &Grammar,
+        prod: &Production,
+        e: &Expression,
+        after: &BTreeSet<Symbol>,
+        follow: &mut HashMap<String, BTreeSet<Symbol>>,
+    ) {
+        match &e.kind {
+            ExpressionKind::Grouped(e) | ExpressionKind::Cut(e) | ExpressionKind::Optional(e) => {
+                self.add_follow(grammar, prod, e, after, follow)
+            }
+            ExpressionKind::Repeat(e)
+            | ExpressionKind::RepeatPlus(e)
+            | ExpressionKind::RepeatRange { expr: e, .. }
+            | ExpressionKind::RepeatRangeNamed(e, _) => {
+                let mut after = after.clone();
+                self.add_first(grammar, prod, e, &mut after);
+                self.add_follow(grammar, prod, e, &after, follow);
+            }
+            ExpressionKind::Alt(es) => {
+                for e in es {
+                    self.add_follow(grammar, prod, e, after, follow);
+                }
+            }
+            ExpressionKind::Sequence(es) => {
+                let mut after = after.clone();
+                for e in es.iter().rev() {
+                    self.add_follow(grammar, prod, e, &after, follow);
+                    if !self.expr_nullable(e) {
+                        after.clear();
+                    }
+                    self.add_first(grammar, prod, e, &mut after);
+                }
+            }
+            ExpressionKind::Nt(nt) => {
+                if let Some(set) = follow.get_mut(nt) {
+                    set.extend(after.iter().cloned());
+                }
+            }
+            ExpressionKind::NegativeLookahead(_)
+            | ExpressionKind::Terminal(_)
+            | ExpressionKind::Prose(_)
+            | ExpressionKind::Break(_)
+            | ExpressionKind::Comment(_)
+            | ExpressionKind::Charset(_)
+            | ExpressionKind::CharacterRange(..)
+            | ExpressionKind::NegExpression(_)
+            | ExpressionKind::Unicode(_) => {}
+        }
+    }
+
+    /// Calls the callback for every nonterminal that can be reached from `e`
+    /// without consuming any input.
+    fn visit_left_nts<'a>(&self, e: &'a Expression, callback: &mut dyn FnMut(&'a Expression)) {
+        match &e.kind {
+            ExpressionKind::Grouped(e)
+            | ExpressionKind::Cut(e)
+            | ExpressionKind::Optional(e)
+            | ExpressionKind::Repeat(e)
+            | ExpressionKind::RepeatPlus(e)
+            | ExpressionKind::RepeatRange { expr: e, .. }
+            | ExpressionKind::RepeatRangeNamed(e, _)
+            // Lookahead runs its expression at the current position.
+            | ExpressionKind::NegativeLookahead(e)
+            | ExpressionKind::NegExpression(e) => self.visit_left_nts(e, callback),
+            ExpressionKind::Alt(es) | ExpressionKind::Charset(es) => {
+                for e in es {
+                    self.visit_left_nts(e, callback);
+                }
+            }
+            ExpressionKind::Sequence(es) => {
+                for e in es {
+                    self.visit_left_nts(e, callback);
+                    if !self.expr_nullable(e) {
+                        break;
+                    }
+                }
+            }
+            ExpressionKind::Nt(_) => callback(e),
+            ExpressionKind::Terminal(_)
+            | ExpressionKind::Prose(_)
+            | ExpressionKind::Break(_)
+            | ExpressionKind::Comment(_)
+            | ExpressionKind::CharacterRange(..)
+            | ExpressionKind::Unicode(_) => {}
+        }
+    }
+}
+
+/// Checks the grammar for left recursion, repetitions of nullable
+/// expressions, and unreachable alternatives.
+pub(crate) fn check_well_formed(grammar: &Grammar, diag: &mut Diagnostics) {
+    let analysis = grammar.analyze();
+    check_left_recursion(grammar, &analysis, diag);
+    for name in &grammar.name_order {
+        let prod = &grammar.productions[name];
+        prod.expression.visit(&mut |e| match &e.kind {
+            ExpressionKind::Repeat(inner)
+            | ExpressionKind::RepeatPlus(inner)
+            | ExpressionKind::RepeatRange {
+                expr: inner,
+                max: None,
+                ..
+            } if analysis.expr_nullable(inner) => {
+                warn_or_err!(
+                    diag,
+                    "{}: in `{name}`, `{e}` repeats an expression that can match \
+                     the empty string, which would loop forever",
+                    e.span
+                );
+            }
+            ExpressionKind::Alt(es) => {
+                let mut es = es.iter();
+                let Some(winner) = es.by_ref().find(|e| analysis.expr_infallible(e)) else {
+                    return;
+                };
+                for unreachable in es {
+                    warn_or_err!(
+                        diag,
+                        "{}: in `{name}`, alternative `{unreachable}` can never match, \
+                         because the earlier alternative `{winner}` always succeeds",
+                        unreachable.span
+                    );
+                }
+            }
+            _ => {}
+        });
+    }
+}
+
+/// Reports each cycle of productions that can reach themselves without
+/// consuming any input.
+///
+/// Cycles that go through a production with `// lint-allow(left-recursion)`
+/// are not reported, but every other cycle is, even if it goes through a
+/// production that is part of an allowed cycle. The expression grammar is
+/// intentionally written in a left-recursive form such as
+/// ``Expression `+` Expression``, with precedence and associativity specified
+/// by the operator precedence table instead, so each of those productions
+/// allows it.
+fn check_left_recursion(grammar: &Grammar, analysis: &Analysis, diag: &mut Diagnostics) {
+    // The edges of the graph of left calls, in grammar order.
+    let edges: HashMap<&str, Vec<&str>> = grammar
+        .name_order
+        .iter()
+        .map(|name| {
+            let mut nts = Vec::new();
+            analysis.visit_left_nts(&grammar.productions[name].expression, &mut |e| {
+                if let ExpressionKind::Nt(nt) = &e.kind
+                    && grammar.productions.contains_key(nt)
+                    && !nts.contains(&nt.as_str())
+                {
+                    nts.push(nt.as_str());
+                }
+            });
+            (name.as_str(), nts)
+        })
+        .collect();
+    let allowed: HashSet<&str> = grammar
+        .name_order
+        .iter()
+        .filter(|name| is_allowed(&grammar.productions[*name], "left-recursion"))
+        .map(String::as_str)
+        .collect();
+    // Each production is reported at most once, as part of the first cycle
+    // found that contains it.
+    let mut reported = HashSet::new();
+    for name in &grammar.name_order {
+        if reported.contains(name.as_str()) || allowed.contains(name.as_str()) {
+            continue;
+        }
+        let Some(cycle) = find_cycle(&edges, &allowed, name) else {
+            continue;
+        };
+        reported.extend(cycle.iter().copied());
+        let path = cycle
+            .iter()
+            .chain([&name.as_str()])
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        warn_or_err!(
+            diag,
+            "{}: `{name}` is left-recursive ({path}), which would loop forever",
+            grammar.productions[name].span
+        );
+    }
+}
+
+/// Returns the shortest path of left calls from `start` back to itself that
+/// doesn't go through any of the `allowed` productions.
+fn find_cycle<'a>(
+    edges: &HashMap<&'a str, Vec<&'a str>>,
+    allowed: &HashSet<&str>,
+    start: &'a str,
+) -> Option<Vec<&'a str>> {
+    // Breadth-first search, remembering how each production was reached.
+    let mut parent: HashMap<&str, &str> = HashMap::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(name) = queue.pop_front() {
+        for &next in &edges[name] {
+            if next == start {
+                let mut path = vec![name];
+                while let Some(&p) = parent.get(path.last().unwrap()) {
+                    path.push(p);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if !parent.contains_key(next) && !allowed.contains(next) {
+                parent.insert(next, name);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Symbol, check_well_formed};
+    use crate::CharSet;
+    use crate::Grammar;
+    use diagnostics::Diagnostics;
+
+    #[test]
+    fn nullable_first_follow() {
+        let g = Grammar::grammar_from_str(
+            "@root A -> B? C `;`\n\nB -> `b`*\n\nC -> `c` | B `d`",
+            "test",
+        )
+        .unwrap();
+        let analysis = g.analyze();
+        assert!(analysis.is_nullable("B"));
+        assert!(!analysis.is_nullable("C"));
+        let terminals = |set: &std::collections::BTreeSet<Symbol>| {
+            set.iter().map(|s| s.to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            terminals(analysis.first("A").unwrap()),
+            ["`b`", "`c`", "`d`"]
+        );
+        assert_eq!(terminals(analysis.follow("A").unwrap()), ["end of input"]);
+        assert_eq!(
+            terminals(analysis.follow("B").unwrap()),
+            ["`b`", "`c`", "`d`"]
+        );
+        assert_eq!(terminals(analysis.follow("C").unwrap()), ["`;`"]);
+    }
+
+    #[test]
+    fn tokens_in_syntax() {
+        let mut g = Grammar::grammar_from_str("@root A -> ID `=` ID", "items").unwrap();
+        let lexer = "ID -> [`a`-`z`]+";
+        crate::parser::parse_grammar(
+            lexer,
+            0..lexer.len(),
+            &mut g,
+            "lexer",
+            std::path::Path::new("lexer.md"),
+        )
+        .unwrap();
+        let analysis = g.analyze();
+        assert_eq!(
+            analysis.first("A").unwrap().iter().collect::<Vec<_>>(),
+            [&Symbol::Token("ID".to_string())]
+        );
+        assert_eq!(
+            analysis.first("ID").unwrap().iter().collect::<Vec<_>>(),
+            [&Symbol::Chars(CharSet::from_range(0x61, 0x7A))]
+        );
+    }
+
+    #[test]
+    fn problems() {
+        let g = Grammar::grammar_from_str(
+            "@root A -> B `+` | `x`\n\n\
+             B -> C? A\n\n\
+             C -> `c`\n\n\
+             D -> (C?)* `d`\n\n\
+             E -> C* | `e` | C\n\n\
+             // lint-allow(left-recursion)\n\
+             Add -> Expression `+` Expression\n\n\
+             Expression -> Add | G | `x`\n\n\
+             G -> Expression `!`\n\n\
+             F -> F `f` | `f`",
+            "test",
+        )
+        .unwrap();
+        let mut diag = Diagnostics::capture();
+        check_well_formed(&g, &mut diag);
+        assert_eq!(
+            diag.messages(),
+            [
+                ":1:1: `A` is left-recursive (A -> B -> A), which would loop forever",
+                ":14:1: `Expression` is left-recursive (Expression -> G -> Expression), \
+                 which would loop forever",
+                ":18:1: `F` is left-recursive (F -> F), which would loop forever",
+                ":7:6: in `D`, `(C?)*` repeats an expression that can match \
+                 the empty string, which would loop forever",
+                ":9:11: in `E`, alternative ``e`` can never match, \
+                 because the earlier alternative `C*` always succeeds",
+                ":9:17: in `E`, alternative `C` can never match, \
+                 because the earlier alternative `C*` always succeeds",
+            ]
+        );
+    }
+}
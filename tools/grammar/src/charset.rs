@@ -0,0 +1,323 @@
+//! Sets of characters matched by an expression.
+//!
+//! [`CharSet`] is a set of code points. [`Grammar::char_set`] computes the
+//! set for an expression that matches a single character, such as
+//! ``[`a`-`z` `_`]``, ``~[`"` LF]``, or a nonterminal referring to a
+//! production like `LF -> U+000A`.
+
+use crate::{Expression, ExpressionKind, Grammar};
+use diagnostics::{Diagnostics, warn_or_err};
+use std::fmt::{self, Display, Formatter};
+
+/// The largest code point, used for "any character".
+pub(crate) const MAX_CHAR: u32 = char::MAX as u32;
+
+/// Limit on how deep to follow nonterminals when resolving a character set.
+const MAX_DEPTH: u32 = 8;
+
+/// A set of code points.
+///
+/// This is stored as a sorted list of inclusive ranges that don't overlap or
+/// touch, so two sets with the same code points are equal. The code points
+/// include the surrogates (U+D800 to U+DFFF), so the complement of a set
+/// may contain them even though they aren't characters.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CharSet {
+    ranges: Vec<(u32, u32)>,
+}
+
+impl CharSet {
+    /// Returns the empty set.
+    pub fn new() -> CharSet {
+        CharSet::default()
+    }
+
+    /// Returns the set of every code point.
+    pub fn all() -> CharSet {
+        CharSet {
+            ranges: vec![(0, MAX_CHAR)],
+        }
+    }
+
+    /// Returns the set of code points from `start` to `end`, inclusive.
+    ///
+    /// This is empty if `start` is greater than `end`.
+    pub fn from_range(start: u32, end: u32) -> CharSet {
+        CharSet::from_ranges([(start, end)])
+    }
+
+    /// Returns the set containing a single character.
+    pub fn from_char(ch: char) -> CharSet {
+        CharSet::from_range(ch as u32, ch as u32)
+    }
+
+    /// Returns the union of the given inclusive ranges.
+    ///
+    /// The ranges can be in any order and can overlap. Inverted ranges are
+    /// ignored, and ranges are clamped to [`char::MAX`].
+    pub fn from_ranges(ranges: impl IntoIterator<Item = (u32, u32)>) -> CharSet {
+        let mut ranges: Vec<_> = ranges
+            .into_iter()
+            .filter(|(start, end)| start <= end && *start <= MAX_CHAR)
+            .map(|(start, end)| (start, end.min(MAX_CHAR)))
+            .collect();
+        ranges.sort();
+        let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        CharSet { ranges: merged }
+    }
+
+    /// The ranges of the set, in order.
+    pub fn ranges(&self) -> &[(u32, u32)] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The number of code points in the set.
+    pub fn len(&self) -> u32 {
+        self.ranges.iter().map(|(start, end)| end - start + 1).sum()
+    }
+
+    pub fn contains(&self, ch: char) -> bool {
+        let ch = ch as u32;
+        self.ranges
+            .iter()
+            .any(|(start, end)| (*start..=*end).contains(&ch))
+    }
+
+    /// Returns the code points in either set.
+    pub fn union(&self, other: &CharSet) -> CharSet {
+        CharSet::from_ranges(self.ranges.iter().chain(&other.ranges).copied())
+    }
+
+    /// Returns the code points in both sets.
+    pub fn intersection(&self, other: &CharSet) -> CharSet {
+        let mut ranges = Vec::new();
+        for (a_start, a_end) in &self.ranges {
+            for (b_start, b_end) in &other.ranges {
+                let start = *a_start.max(b_start);
+                let end = *a_end.min(b_end);
+                if start <= end {
+                    ranges.push((start, end));
+                }
+            }
+        }
+        CharSet::from_ranges(ranges)
+    }
+
+    /// Returns the code points in this set that are not in `other`.
+    pub fn difference(&self, other: &CharSet) -> CharSet {
+        self.intersection(&other.complement())
+    }
+
+    /// Returns every code point that is not in this set.
+    pub fn complement(&self) -> CharSet {
+        let mut ranges = Vec::new();
+        let mut next = 0;
+        for (start, end) in &self.ranges {
+            if *start > next {
+                ranges.push((next, start - 1));
+            }
+            next = end + 1;
+        }
+        if next <= MAX_CHAR {
+            ranges.push((next, MAX_CHAR));
+        }
+        CharSet { ranges }
+    }
+
+    /// Whether the sets have any code points in common.
+    pub fn intersects(&self, other: &CharSet) -> bool {
+        !self.intersection(other).is_empty()
+    }
+
+    /// Whether every code point in this set is also in `other`.
+    pub fn is_subset(&self, other: &CharSet) -> bool {
+        self.difference(other).is_empty()
+    }
+
+    /// Whether every code point in the set is ASCII.
+    pub fn is_ascii(&self) -> bool {
+        self.ranges.last().is_none_or(|(_, end)| *end <= 0x7F)
+    }
+}
+
+impl Display for CharSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, (start, end)) in self.ranges.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "U+{start:04X}")?;
+            if start != end {
+                write!(f, "-U+{end:04X}")?;
+            }
+        }
+        write!(f, "]")
+    }
+}
+
+impl Grammar {
+    /// Returns the set of characters matched by an expression that matches
+    /// exactly one character.
+    ///
+    /// Returns `None` if the expression can match something other than a
+    /// single character, or if it has a suffix that may further restrict it.
+    pub fn char_set(&self, e: &Expression) -> Option<CharSet> {
+        self.char_set_depth(e, 0)
+    }
+
+    fn char_set_depth(&self, e: &Expression, depth: u32) -> Option<CharSet> {
+        if e.suffix.is_some() {
+            return None;
+        }
+        let set = match &e.kind {
+            ExpressionKind::Terminal(s) => {
+                let mut chars = s.chars();
+                let ch = chars.next()?;
+                if chars.next().is_some() {
+                    return None;
+                }
+                CharSet::from_char(ch)
+            }
+            ExpressionKind::Unicode((ch, _)) => CharSet::from_char(*ch),
+            ExpressionKind::CharacterRange(start, end) => {
+                CharSet::from_range(start.get_ch() as u32, end.get_ch() as u32)
+            }
+            ExpressionKind::Grouped(e) => self.char_set_depth(e, depth)?,
+            ExpressionKind::Sequence(es) => {
+                // Such as `LF -> U+000A // Line feed`.
+                let mut es = es.iter().filter(|e| {
+                    !matches!(
+                        e.kind,
+                        ExpressionKind::Break(_) | ExpressionKind::Comment(_)
+                    )
+                });
+                let e = es.next()?;
+                if es.next().is_some() {
+                    return None;
+                }
+                self.char_set_depth(e, depth)?
+            }
+            ExpressionKind::Charset(es) | ExpressionKind::Alt(es) => {
+                let mut set = CharSet::new();
+                for e in es {
+                    set = set.union(&self.char_set_depth(e, depth)?);
+                }
+                set
+            }
+            ExpressionKind::NegExpression(e) => self.char_set_depth(e, depth)?.complement(),
+            ExpressionKind::Nt(nt) if depth < MAX_DEPTH => {
+                let prod = self.productions.get(nt)?;
+                self.char_set_depth(&prod.expression, depth + 1)?
+            }
+            _ => return None,
+        };
+        Some(set)
+    }
+}
+
+/// Checks for character ranges whose start is after their end, and for
+/// negated character sets that can't match any character.
+pub(crate) fn check_char_sets(grammar: &Grammar, diag: &mut Diagnostics) {
+    // Every Unicode scalar value, which is what `~` can match.
+    let chars = CharSet::from_ranges([(0, 0xD7FF), (0xE000, MAX_CHAR)]);
+    for name in &grammar.name_order {
+        if grammar.productions[name].is_instance() {
+            continue;
+        }
+        grammar.productions[name]
+            .expression
+            .visit(&mut |e| match &e.kind {
+                ExpressionKind::CharacterRange(start, end) if start.get_ch() > end.get_ch() => {
+                    warn_or_err!(
+                        diag,
+                        "{}: in `{name}`, character range `{}` is empty, \
+                     because {start} is after {end}",
+                        e.span,
+                        e.kind
+                    );
+                }
+                ExpressionKind::NegExpression(inner)
+                    if grammar
+                        .char_set(inner)
+                        .is_some_and(|set| chars.is_subset(&set)) =>
+                {
+                    warn_or_err!(
+                        diag,
+                        "{}: in `{name}`, `{e}` can never match, because `{inner}` \
+                     includes every character",
+                        e.span
+                    );
+                }
+                _ => {}
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CharSet;
+    use crate::Grammar;
+    use diagnostics::Diagnostics;
+
+    #[test]
+    fn algebra() {
+        let a = CharSet::from_ranges([(0x61, 0x66), (0x30, 0x39), (0x63, 0x6A)]);
+        assert_eq!(a.ranges(), [(0x30, 0x39), (0x61, 0x6A)]);
+        assert_eq!(a.len(), 20);
+        assert!(a.contains('j') && !a.contains('k'));
+        let b = CharSet::from_range(0x65, 0x7A);
+        assert_eq!(a.union(&b).ranges(), [(0x30, 0x39), (0x61, 0x7A)]);
+        assert_eq!(a.intersection(&b).ranges(), [(0x65, 0x6A)]);
+        assert_eq!(a.difference(&b).ranges(), [(0x30, 0x39), (0x61, 0x64)]);
+        assert_eq!(a.complement().complement(), a);
+        assert_eq!(a.union(&a.complement()), CharSet::all());
+        assert!(CharSet::from_range(0x7A, 0x61).is_empty());
+        assert!(a.is_ascii());
+        assert!(!a.complement().is_ascii());
+        assert_eq!(a.to_string(), "[U+0030-U+0039 U+0061-U+006A]");
+    }
+
+    #[test]
+    fn expressions() {
+        let g = Grammar::grammar_from_str(
+            "A -> [`a`-`z` LF `_`]\n\n\
+             B -> ~[`\"` LF]\n\n\
+             LF -> U+000A // line feed\n\n\
+             C -> `ab`",
+            "lexer",
+        )
+        .unwrap();
+        let set = |name: &str| g.char_set(&g.productions[name].expression);
+        assert_eq!(
+            set("A").unwrap().ranges(),
+            [(0x0A, 0x0A), (0x5F, 0x5F), (0x61, 0x7A)]
+        );
+        let b = set("B").unwrap();
+        assert!(!b.contains('"') && !b.contains('\n') && b.contains('é'));
+        assert!(!b.is_ascii());
+        assert_eq!(set("C"), None);
+    }
+
+    #[test]
+    fn check() {
+        let g = Grammar::grammar_from_str(
+            "A -> [`z`-`a`] ~[U+0000-U+10FFFF] ~[U+0000-U+D7FF U+E000-U+10FFFF] ~[`a`]",
+            "lexer",
+        )
+        .unwrap();
+        let mut diag = Diagnostics::new();
+        super::check_char_sets(&g, &mut diag);
+        assert_eq!(diag.count, 3);
+    }
+}
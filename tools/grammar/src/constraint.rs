@@ -0,0 +1,565 @@
+//! Constraints that are written in English in the grammar.
+//!
+//! Some rules can't be expressed with the grammar syntax, and are instead
+//! written as a suffix (`` _except `b`_ ``) or as prose
+//! (``<`XID_Start` defined by Unicode>``). The text of these follows one of a
+//! small number of forms, which are parsed into a [`Constraint`] so that
+//! tools can check and evaluate them.
+//!
+//! Suffixes:
+//!
+//! - ``except `b` or [StructExpression]`` --- [`Constraint::Except`]. The
+//!   exclusions are separated with `or` or `and`, and are one of:
+//!     - `` `b` `` --- [`Exclusion::Terminal`]
+//!     - `[StructExpression]` --- [`Exclusion::Production`]
+//!     - `[delimiters][lex.token.delim]` --- [`Exclusion::Delimiters`]
+//!     - `a [strict][lex.keywords.strict] or
+//!       [reserved][lex.keywords.reserved] keyword` ---
+//!       [`Exclusion::Keywords`]
+//!     - ``end with a `}` `` --- [`Exclusion::EndsWith`]
+//! - `immediately followed by LF` --- [`Constraint::FollowedBy`]
+//! - `not immediately followed by LF` --- [`Constraint::NotFollowedBy`]
+//! - `valid hex char value` --- [`Constraint::HexCharValue`]
+//!
+//! Prose:
+//!
+//! - `` `XID_Start` defined by Unicode `` --- [`Constraint::Property`]
+
+use crate::{Expression, ExpressionKind, Grammar};
+use diagnostics::{Diagnostics, warn_or_err};
+use std::fmt::{self, Display, Formatter};
+
+/// The tokens that are excluded by [`Exclusion::Delimiters`].
+pub const DELIMITERS: &[&str] = &["{", "}", "[", "]", "(", ")"];
+
+/// A machine-readable form of a suffix or prose.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Constraint {
+    /// `except A or B`
+    ///
+    /// The expression does not match if what it matched is matched by any
+    /// of the exclusions.
+    Except(Vec<Exclusion>, Conjunction),
+    /// `immediately followed by A`
+    ///
+    /// The expression only matches if `A` matches right after it.
+    FollowedBy(Lookahead),
+    /// `not immediately followed by A`
+    ///
+    /// The expression only matches if `A` does not match right after it.
+    NotFollowedBy(Lookahead),
+    /// `` `XID_Start` defined by Unicode ``
+    ///
+    /// Prose that matches a single character with the given property.
+    Property(UnicodeProperty),
+    /// `valid hex char value`
+    ///
+    /// What the expression matched, with `_` removed, must be the
+    /// hexadecimal value of a Unicode scalar value. Unlike the other
+    /// constraints, this is an error rather than a failure to match.
+    HexCharValue,
+}
+
+/// One of the exclusions of [`Constraint::Except`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Exclusion {
+    /// `` `b` ``
+    Terminal(String),
+    /// `[StructExpression]`
+    Production(String),
+    /// `[delimiters][lex.token.delim]`
+    ///
+    /// One of the [`DELIMITERS`].
+    Delimiters,
+    /// `a [strict][lex.keywords.strict] or [reserved][lex.keywords.reserved] keyword`
+    ///
+    /// The strings are the keyword classes, such as `strict`. Each class is
+    /// defined by the production returned by [`keyword_production`].
+    Keywords(Vec<String>),
+    /// ``end with a `}` ``
+    EndsWith(String),
+}
+
+/// How the exclusions of [`Constraint::Except`] are joined.
+///
+/// This only affects how the constraint is written. Both mean that any of
+/// the exclusions applies.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Conjunction {
+    Or,
+    And,
+}
+
+/// What must (or must not) follow an expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Lookahead {
+    /// `` `b` ``
+    Terminal(String),
+    /// `LF`
+    Production(String),
+}
+
+/// A Unicode character property used in prose.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnicodeProperty {
+    XidStart,
+    XidContinue,
+}
+
+/// Returns the name of the production that lists the keywords of the given
+/// class, such as `STRICT_KEYWORDS` for `strict`.
+pub fn keyword_production(class: &str) -> String {
+    format!("{}_KEYWORDS", class.to_uppercase())
+}
+
+impl Constraint {
+    /// Parses the text of a suffix, without the surrounding underscores.
+    pub fn parse_suffix(suffix: &str) -> Result<Constraint, String> {
+        let mut cursor = Cursor(suffix);
+        let constraint = if cursor.eat("except ") {
+            let mut exclusions = vec![cursor.exclusion()?];
+            let mut conjunction = None;
+            while !cursor.0.is_empty() {
+                let next = if cursor.eat(" or ") {
+                    Conjunction::Or
+                } else if cursor.eat(" and ") {
+                    Conjunction::And
+                } else {
+                    return Err(format!("expected `or` or `and` at `{}`", cursor.0));
+                };
+                if conjunction.is_some_and(|c| c != next) {
+                    return Err("cannot mix `or` and `and`".to_string());
+                }
+                conjunction = Some(next);
+                exclusions.push(cursor.exclusion()?);
+            }
+            Constraint::Except(exclusions, conjunction.unwrap_or(Conjunction::Or))
+        } else if cursor.eat("immediately followed by ") {
+            Constraint::FollowedBy(cursor.lookahead()?)
+        } else if cursor.eat("not immediately followed by ") {
+            Constraint::NotFollowedBy(cursor.lookahead()?)
+        } else if cursor.eat("valid hex char value") {
+            Constraint::HexCharValue
+        } else {
+            return Err("unknown form".to_string());
+        };
+        if !cursor.0.is_empty() {
+            return Err(format!("unexpected text `{}`", cursor.0));
+        }
+        Ok(constraint)
+    }
+
+    /// Parses the text of prose, without the surrounding angle brackets.
+    pub fn parse_prose(prose: &str) -> Result<Constraint, String> {
+        [UnicodeProperty::XidStart, UnicodeProperty::XidContinue]
+            .into_iter()
+            .find(|p| prose == format!("`{}` defined by Unicode", p.name()))
+            .map(Constraint::Property)
+            .ok_or_else(|| "unknown form".to_string())
+    }
+
+    /// The names of the productions that this constraint refers to.
+    pub fn productions(&self) -> Vec<String> {
+        match self {
+            Constraint::Except(exclusions, _) => exclusions
+                .iter()
+                .flat_map(|exclusion| match exclusion {
+                    Exclusion::Production(name) => vec![name.clone()],
+                    Exclusion::Keywords(classes) => {
+                        classes.iter().map(|c| keyword_production(c)).collect()
+                    }
+                    Exclusion::Terminal(_) | Exclusion::Delimiters | Exclusion::EndsWith(_) => {
+                        Vec::new()
+                    }
+                })
+                .collect(),
+            Constraint::FollowedBy(Lookahead::Production(name))
+            | Constraint::NotFollowedBy(Lookahead::Production(name)) => vec![name.clone()],
+            Constraint::FollowedBy(Lookahead::Terminal(_))
+            | Constraint::NotFollowedBy(Lookahead::Terminal(_))
+            | Constraint::Property(_)
+            | Constraint::HexCharValue => Vec::new(),
+        }
+    }
+
+    /// Renders the constraint as markdown.
+    ///
+    /// `link` is called with the name of each production that the
+    /// constraint refers to. If it returns a destination, the name is
+    /// rendered as a link to it. Otherwise, this is the same as the text the
+    /// constraint was parsed from.
+    pub fn render(&self, link: &dyn Fn(&str) -> Option<String>) -> String {
+        let production = |name: &str, bracketed: bool| match link(name) {
+            Some(dest) => format!("[{name}]({dest})"),
+            None if bracketed => format!("[{name}]"),
+            None => name.to_string(),
+        };
+        let lookahead = |l: &Lookahead| match l {
+            Lookahead::Terminal(s) => format!("`{s}`"),
+            Lookahead::Production(name) => production(name, false),
+        };
+        match self {
+            Constraint::Except(exclusions, conjunction) => {
+                let separator = match conjunction {
+                    Conjunction::Or => " or ",
+                    Conjunction::And => " and ",
+                };
+                let exclusions: Vec<_> = exclusions
+                    .iter()
+                    .map(|exclusion| match exclusion {
+                        Exclusion::Terminal(s) => format!("`{s}`"),
+                        Exclusion::Production(name) => production(name, true),
+                        Exclusion::Delimiters => "[delimiters][lex.token.delim]".to_string(),
+                        Exclusion::Keywords(classes) => {
+                            let classes: Vec<_> = classes
+                                .iter()
+                                .map(|c| format!("[{c}][lex.keywords.{c}]"))
+                                .collect();
+                            format!("a {} keyword", classes.join(" or "))
+                        }
+                        Exclusion::EndsWith(s) => format!("end with a `{s}`"),
+                    })
+                    .collect();
+                format!("except {}", exclusions.join(separator))
+            }
+            Constraint::FollowedBy(l) => format!("immediately followed by {}", lookahead(l)),
+            Constraint::NotFollowedBy(l) => {
+                format!("not immediately followed by {}", lookahead(l))
+            }
+            Constraint::Property(p) => format!("`{}` defined by Unicode", p.name()),
+            Constraint::HexCharValue => "valid hex char value".to_string(),
+        }
+    }
+}
+
+impl Display for Constraint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(&|_| None))
+    }
+}
+
+impl UnicodeProperty {
+    /// The name of the property as written by Unicode.
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnicodeProperty::XidStart => "XID_Start",
+            UnicodeProperty::XidContinue => "XID_Continue",
+        }
+    }
+}
+
+impl Expression {
+    /// Sets the suffix of this expression, and the [`Expression::constraint`]
+    /// parsed from it.
+    pub fn set_suffix(&mut self, suffix: Option<String>) {
+        self.constraint = suffix
+            .as_deref()
+            .and_then(|suffix| Constraint::parse_suffix(suffix).ok());
+        self.suffix = suffix;
+    }
+}
+
+/// Parses the constraints of the suffixes of the expression and every
+/// expression nested inside it, such as after they were loaded from JSON.
+pub(crate) fn parse_constraints(e: &mut Expression) {
+    let suffix = e.suffix.take();
+    e.set_suffix(suffix);
+    match &mut e.kind {
+        ExpressionKind::Grouped(e)
+        | ExpressionKind::Optional(e)
+        | ExpressionKind::NegativeLookahead(e)
+        | ExpressionKind::Repeat(e)
+        | ExpressionKind::RepeatPlus(e)
+        | ExpressionKind::RepeatRange { expr: e, .. }
+        | ExpressionKind::RepeatRangeNamed(e, _)
+        | ExpressionKind::NegExpression(e)
+        | ExpressionKind::Cut(e) => parse_constraints(e),
+        ExpressionKind::Alt(es) | ExpressionKind::Sequence(es) | ExpressionKind::Charset(es) => {
+            for e in es {
+                parse_constraints(e);
+            }
+        }
+        ExpressionKind::Nt(_)
+        | ExpressionKind::Terminal(_)
+        | ExpressionKind::Prose(_)
+        | ExpressionKind::Break(_)
+        | ExpressionKind::Comment(_)
+        | ExpressionKind::Unicode(_)
+        | ExpressionKind::CharacterRange(..) => {}
+    }
+}
+
+/// A position in the text of a constraint.
+struct Cursor<'a>(&'a str);
+
+impl<'a> Cursor<'a> {
+    /// Advances past `prefix` if the text starts with it.
+    fn eat(&mut self, prefix: &str) -> bool {
+        match self.0.strip_prefix(prefix) {
+            Some(rest) => {
+                self.0 = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Parses `` `text` ``.
+    fn terminal(&mut self) -> Result<String, String> {
+        if !self.eat("`") {
+            return Err(format!("expected `` ` `` at `{}`", self.0));
+        }
+        let Some((text, rest)) = self.0.split_once('`') else {
+            return Err("unterminated `` ` ``".to_string());
+        };
+        self.0 = rest;
+        Ok(text.to_string())
+    }
+
+    /// Parses `[text]`.
+    fn bracketed(&mut self) -> Option<&'a str> {
+        let (text, rest) = self.0.strip_prefix('[')?.split_once(']')?;
+        self.0 = rest;
+        Some(text)
+    }
+
+    /// Parses `[text]` or `[text][dest]`.
+    fn link(&mut self) -> Result<(&'a str, Option<&'a str>), String> {
+        let text = self
+            .bracketed()
+            .ok_or_else(|| format!("expected a link at `{}`", self.0))?;
+        let dest = if self.0.starts_with('[') {
+            Some(
+                self.bracketed()
+                    .ok_or_else(|| format!("unterminated link at `{}`", self.0))?,
+            )
+        } else {
+            None
+        };
+        Ok((text, dest))
+    }
+
+    /// Parses a production name, either bare or as `[Name]`.
+    fn production(&mut self, bracketed: bool) -> Result<String, String> {
+        let name = if bracketed {
+            match self.link()? {
+                (name, None) => name,
+                (_, Some(dest)) => return Err(format!("unexpected link to `{dest}`")),
+            }
+        } else {
+            let end = self
+                .0
+                .find(|ch: char| !ch.is_alphanumeric() && ch != '_')
+                .unwrap_or(self.0.len());
+            let (name, rest) = self.0.split_at(end);
+            self.0 = rest;
+            name
+        };
+        if name.is_empty() || !name.chars().all(|ch| ch.is_alphanumeric() || ch == '_') {
+            return Err(format!("expected a production name, found `{name}`"));
+        }
+        Ok(name.to_string())
+    }
+
+    fn exclusion(&mut self) -> Result<Exclusion, String> {
+        if self.0.starts_with('`') {
+            return Ok(Exclusion::Terminal(self.terminal()?));
+        }
+        if self.eat("end with a ") {
+            return Ok(Exclusion::EndsWith(self.terminal()?));
+        }
+        if self.eat("a ") {
+            let mut classes = Vec::new();
+            loop {
+                let (class, dest) = self.link()?;
+                if dest != Some(&format!("lex.keywords.{class}")) {
+                    return Err(format!(
+                        "expected keyword class `{class}` to link to `lex.keywords.{class}`"
+                    ));
+                }
+                classes.push(class.to_string());
+                if !self.eat(" or ") {
+                    break;
+                }
+            }
+            if !self.eat(" keyword") {
+                return Err(format!("expected `keyword` at `{}`", self.0));
+            }
+            return Ok(Exclusion::Keywords(classes));
+        }
+        if self.eat("[delimiters][lex.token.delim]") {
+            return Ok(Exclusion::Delimiters);
+        }
+        Ok(Exclusion::Production(self.production(true)?))
+    }
+
+    fn lookahead(&mut self) -> Result<Lookahead, String> {
+        if self.0.starts_with('`') {
+            Ok(Lookahead::Terminal(self.terminal()?))
+        } else {
+            Ok(Lookahead::Production(self.production(false)?))
+        }
+    }
+}
+
+/// Checks that every suffix and prose is a recognized constraint, and that
+/// the productions they refer to exist.
+pub(crate) fn check_constraints(grammar: &Grammar, diag: &mut Diagnostics) {
+    for name in &grammar.name_order {
+        let prod = &grammar.productions[name];
+        if prod.is_instance() {
+            continue;
+        }
+        prod.expression.visit(&mut |e| {
+            let mut check = |what: &str, text: &str, result: Result<Constraint, String>| {
+                let constraint = match result {
+                    Ok(constraint) => constraint,
+                    Err(msg) => {
+                        warn_or_err!(
+                            diag,
+                            "{}: in `{name}`, {what} `{text}` is not a recognized constraint: {msg}",
+                            e.span
+                        );
+                        return;
+                    }
+                };
+                for nt in constraint.productions() {
+                    if !grammar.productions.contains_key(&nt) {
+                        warn_or_err!(
+                            diag,
+                            "{}: in `{name}`, {what} `{text}` refers to `{nt}`, \
+                             which is not defined",
+                            e.span
+                        );
+                    }
+                }
+            };
+            if let Some(suffix) = &e.suffix {
+                let result = match &e.constraint {
+                    Some(constraint) => Ok(constraint.clone()),
+                    // Parse it again for the reason it isn't recognized.
+                    None => Constraint::parse_suffix(suffix),
+                };
+                check("suffix", suffix, result);
+            }
+            if let ExpressionKind::Prose(prose) = &e.kind {
+                check("prose", prose, Constraint::parse_prose(prose));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GrammarLoader;
+
+    #[test]
+    fn parse_suffix() {
+        assert_eq!(
+            Constraint::parse_suffix("except `b` or `br`"),
+            Ok(Constraint::Except(
+                vec![
+                    Exclusion::Terminal("b".to_string()),
+                    Exclusion::Terminal("br".to_string())
+                ],
+                Conjunction::Or
+            ))
+        );
+        assert_eq!(
+            Constraint::parse_suffix("except `$` and [delimiters][lex.token.delim]"),
+            Ok(Constraint::Except(
+                vec![Exclusion::Terminal("$".to_string()), Exclusion::Delimiters],
+                Conjunction::And
+            ))
+        );
+        assert_eq!(
+            Constraint::parse_suffix("except [LazyBooleanExpression] or end with a `}`"),
+            Ok(Constraint::Except(
+                vec![
+                    Exclusion::Production("LazyBooleanExpression".to_string()),
+                    Exclusion::EndsWith("}".to_string())
+                ],
+                Conjunction::Or
+            ))
+        );
+        assert_eq!(
+            Constraint::parse_suffix(
+                "except a [strict][lex.keywords.strict] or [reserved][lex.keywords.reserved] keyword"
+            )
+            .unwrap()
+            .productions(),
+            ["STRICT_KEYWORDS", "RESERVED_KEYWORDS"]
+        );
+        assert_eq!(
+            Constraint::parse_suffix("not immediately followed by LF"),
+            Ok(Constraint::NotFollowedBy(Lookahead::Production(
+                "LF".to_string()
+            )))
+        );
+        assert_eq!(
+            Constraint::parse_prose("`XID_Continue` defined by Unicode"),
+            Ok(Constraint::Property(UnicodeProperty::XidContinue))
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for text in [
+            "except `b` or `c` or `r`",
+            "except [delimiters][lex.token.delim] and [MacroRepOp]",
+            "except a [strict][lex.keywords.strict] or [reserved][lex.keywords.reserved] keyword",
+            "except [LazyBooleanExpression] or end with a `}`",
+            "immediately followed by LF",
+            "not immediately followed by `#`",
+            "valid hex char value",
+        ] {
+            assert_eq!(Constraint::parse_suffix(text).unwrap().to_string(), text);
+        }
+        let c = Constraint::parse_suffix("except [A] or `a`").unwrap();
+        assert_eq!(
+            c.render(&|name| Some(format!("#{name}"))),
+            "except [A](#A) or `a`"
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        for text in [
+            "except",
+            "except `b` or",
+            "except `b` or `c` and `d`",
+            "except [delimiters][lex.token.other]",
+            "except a [strict][lex.keywords.reserved] keyword",
+            "immediately followed by",
+            "followed by LF",
+            "some other text",
+        ] {
+            assert!(Constraint::parse_suffix(text).is_err(), "{text}");
+        }
+        assert!(Constraint::parse_prose("any character").is_err());
+    }
+
+    #[test]
+    fn check() {
+        let mut diag = Diagnostics::capture();
+        GrammarLoader::new(env!("CARGO_MANIFEST_DIR"))
+            .filter(|_| false)
+            .snippet(
+                "test.md",
+                "lexer",
+                "@root A -> B _except [C]_ <`XID_Start` defined by Unicode> <prose>\n\n\
+                 B -> `b` _immediately followed by `x`_ `c` _a suffix_",
+            )
+            .load(&mut diag);
+        assert_eq!(
+            diag.messages(),
+            [
+                "test.md:1:12: in `A`, suffix `except [C]` refers to `C`, which is not defined",
+                "test.md:1:60: in `A`, prose `prose` is not a recognized constraint: unknown form",
+                "test.md:3:40: in `B`, suffix `a suffix` is not a recognized constraint: \
+                 unknown form",
+            ]
+        );
+    }
+}
@@ -0,0 +1,406 @@
+//! Comparison of two versions of the grammar.
+//!
+//! This compares the meaning of the productions rather than their text.
+//! Line breaks, comments, and expression IDs are ignored, so reformatting a
+//! production or moving it to another chapter is not a change.
+
+use crate::parameters::names_in;
+use crate::{Expression, ExpressionKind, Grammar, Production};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+
+/// The differences between two versions of the grammar.
+///
+/// Created with [`Grammar::diff`]. Each list is sorted by name.
+#[derive(Debug, Default, Serialize)]
+pub struct GrammarDiff {
+    /// Productions that only exist in the new grammar.
+    pub added: Vec<ProductionDiff>,
+    /// Productions that only exist in the old grammar.
+    pub removed: Vec<ProductionDiff>,
+    /// Productions that were given a new name without changing what they
+    /// match.
+    pub renamed: Vec<ProductionDiff>,
+    /// Productions whose expression changed.
+    pub changed: Vec<ProductionDiff>,
+}
+
+/// A production that is different between two versions of the grammar.
+#[derive(Debug, Serialize)]
+pub struct ProductionDiff {
+    /// The name of the production. For a removed production, this is the
+    /// name in the old grammar.
+    pub name: String,
+    /// The previous name of a renamed production.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_name: Option<String>,
+    /// The expression in the old grammar, on a single line.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    /// The expression in the new grammar, on a single line.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+    /// The roots that can reach the production.
+    ///
+    /// This uses the new grammar, except for removed productions, which use
+    /// the old grammar.
+    pub roots: Vec<String>,
+}
+
+impl Grammar {
+    /// Compares this grammar with a newer version of it.
+    pub fn diff(&self, new: &Grammar) -> GrammarDiff {
+        let mut removed: Vec<_> = self
+            .name_order
+            .iter()
+            .filter(|name| !self.productions[*name].is_instance())
+            .filter(|name| !new.productions.contains_key(*name))
+            .map(|name| name.as_str())
+            .collect();
+        let mut added: Vec<_> = new
+            .name_order
+            .iter()
+            .filter(|name| !new.productions[*name].is_instance())
+            .filter(|name| !self.productions.contains_key(*name))
+            .map(|name| name.as_str())
+            .collect();
+        removed.sort();
+        added.sort();
+
+        // A removed production is a rename of an added one if they are the
+        // same, other than references to themselves.
+        let mut renames = HashMap::new();
+        for old_name in &removed {
+            let old_expr = &self.productions[*old_name].expression;
+            let Some(new_name) = added.iter().find(|new_name| {
+                !renames.values().any(|n| n == *new_name)
+                    && same(
+                        old_expr,
+                        &new.productions[**new_name].expression,
+                        &HashMap::from([(*old_name, **new_name)]),
+                    )
+            }) else {
+                continue;
+            };
+            renames.insert(*old_name, *new_name);
+        }
+
+        let old_roots = Roots::new(self);
+        let new_roots = Roots::new(new);
+        let mut diff = GrammarDiff::default();
+        for name in &added {
+            if renames.values().any(|n| n == name) {
+                continue;
+            }
+            diff.added.push(ProductionDiff {
+                name: name.to_string(),
+                old_name: None,
+                old: None,
+                new: Some(text(&new.productions[*name])),
+                roots: new_roots.reaching(name),
+            });
+        }
+        for name in &removed {
+            if let Some(new_name) = renames.get(name) {
+                diff.renamed.push(ProductionDiff {
+                    name: new_name.to_string(),
+                    old_name: Some(name.to_string()),
+                    old: None,
+                    new: None,
+                    roots: new_roots.reaching(new_name),
+                });
+                continue;
+            }
+            diff.removed.push(ProductionDiff {
+                name: name.to_string(),
+                old_name: None,
+                old: Some(text(&self.productions[*name])),
+                new: None,
+                roots: old_roots.reaching(name),
+            });
+        }
+        diff.renamed.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut common: Vec<_> = self
+            .name_order
+            .iter()
+            .filter(|name| !self.productions[*name].is_instance())
+            .filter(|name| new.productions.contains_key(*name))
+            .collect();
+        common.sort();
+        for name in common {
+            let old_prod = &self.productions[name];
+            let new_prod = &new.productions[name];
+            if old_prod.edition == new_prod.edition
+                && old_prod.parameters == new_prod.parameters
+                && same(&old_prod.expression, &new_prod.expression, &renames)
+            {
+                continue;
+            }
+            diff.changed.push(ProductionDiff {
+                name: name.clone(),
+                old_name: None,
+                old: Some(text(old_prod)),
+                new: Some(text(new_prod)),
+                roots: new_roots.reaching(name),
+            });
+        }
+        diff
+    }
+}
+
+impl GrammarDiff {
+    /// Whether the two grammars have the same meaning.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.renamed.is_empty()
+            && self.changed.is_empty()
+    }
+
+    /// Converts the differences to JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl Display for GrammarDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no changes");
+        }
+        let roots = |f: &mut Formatter<'_>, p: &ProductionDiff| {
+            if p.roots.is_empty() {
+                writeln!(f, "    not reachable from any root")
+            } else {
+                writeln!(f, "    reachable from: {}", p.roots.join(", "))
+            }
+        };
+        for p in &self.added {
+            writeln!(f, "added `{}`", p.name)?;
+            writeln!(f, "    + {}", p.new.as_ref().unwrap())?;
+            roots(f, p)?;
+        }
+        for p in &self.removed {
+            writeln!(f, "removed `{}`", p.name)?;
+            writeln!(f, "    - {}", p.old.as_ref().unwrap())?;
+            roots(f, p)?;
+        }
+        for p in &self.renamed {
+            writeln!(
+                f,
+                "renamed `{}` to `{}`",
+                p.old_name.as_ref().unwrap(),
+                p.name
+            )?;
+            roots(f, p)?;
+        }
+        for p in &self.changed {
+            writeln!(f, "changed `{}`", p.name)?;
+            writeln!(f, "    - {}", p.old.as_ref().unwrap())?;
+            writeln!(f, "    + {}", p.new.as_ref().unwrap())?;
+            roots(f, p)?;
+        }
+        Ok(())
+    }
+}
+
+/// The productions reachable from each root of a grammar.
+struct Roots<'a> {
+    reachable: Vec<(&'a str, HashSet<&'a str>)>,
+}
+
+impl<'a> Roots<'a> {
+    fn new(grammar: &'a Grammar) -> Roots<'a> {
+        let mut roots: Vec<_> = grammar
+            .productions
+            .values()
+            .filter(|p| p.is_root)
+            .map(|p| p.name.as_str())
+            .collect();
+        roots.sort();
+        let reachable = roots
+            .into_iter()
+            .map(|root| {
+                let mut seen = HashSet::from([root]);
+                let mut stack = vec![root];
+                while let Some(name) = stack.pop() {
+                    let Some(prod) = grammar.productions.get(name) else {
+                        continue;
+                    };
+                    prod.expression.visit(&mut |e| {
+                        if let ExpressionKind::Nt(nt) = &e.kind {
+                            for name in names_in(nt).into_iter().chain([nt.as_str()]) {
+                                if seen.insert(name) {
+                                    stack.push(name);
+                                }
+                            }
+                        }
+                    });
+                }
+                (root, seen)
+            })
+            .collect();
+        Roots { reachable }
+    }
+
+    /// Returns the roots that can reach the given production.
+    fn reaching(&self, name: &str) -> Vec<String> {
+        self.reachable
+            .iter()
+            .filter(|(_, seen)| seen.contains(name))
+            .map(|(root, _)| root.to_string())
+            .collect()
+    }
+}
+
+/// Whether two expressions have the same meaning.
+///
+/// `renames` maps names in the old expression to names in the new one.
+fn same(old: &Expression, new: &Expression, renames: &HashMap<&str, &str>) -> bool {
+    use ExpressionKind::*;
+
+    if old.suffix != new.suffix || old.footnote != new.footnote || old.edition != new.edition {
+        return false;
+    }
+    let same_all = |old: &[&Expression], new: &[&Expression]| {
+        old.len() == new.len() && old.iter().zip(new).all(|(a, b)| same(a, b, renames))
+    };
+    match (&old.kind, &new.kind) {
+        (Sequence(_), _) | (_, Sequence(_)) => same_all(&elements(old), &elements(new)),
+        (Alt(a), Alt(b)) | (Charset(a), Charset(b)) => {
+            same_all(&a.iter().collect::<Vec<_>>(), &b.iter().collect::<Vec<_>>())
+        }
+        (Grouped(a), Grouped(b))
+        | (Optional(a), Optional(b))
+        | (NegativeLookahead(a), NegativeLookahead(b))
+        | (Repeat(a), Repeat(b))
+        | (RepeatPlus(a), RepeatPlus(b))
+        | (NegExpression(a), NegExpression(b))
+        | (Cut(a), Cut(b)) => same(a, b, renames),
+        (
+            RepeatRange {
+                expr: a,
+                name: a_name,
+                min: a_min,
+                max: a_max,
+                limit: a_limit,
+            },
+            RepeatRange {
+                expr: b,
+                name: b_name,
+                min: b_min,
+                max: b_max,
+                limit: b_limit,
+            },
+        ) => {
+            a_name == b_name
+                && a_min == b_min
+                && a_max == b_max
+                && a_limit.to_string() == b_limit.to_string()
+                && same(a, b, renames)
+        }
+        (RepeatRangeNamed(a, a_name), RepeatRangeNamed(b, b_name)) => {
+            a_name == b_name && same(a, b, renames)
+        }
+        (Nt(a), Nt(b)) => renames.get(a.as_str()).copied().unwrap_or(a) == b,
+        (Terminal(_), Terminal(_))
+        | (Prose(_), Prose(_))
+        | (Unicode(_), Unicode(_))
+        | (CharacterRange(..), CharacterRange(..)) => old.kind.to_string() == new.kind.to_string(),
+        _ => false,
+    }
+}
+
+/// The elements of a sequence, without line breaks and comments.
+fn elements(e: &Expression) -> Vec<&Expression> {
+    match &e.kind {
+        ExpressionKind::Sequence(es) => es
+            .iter()
+            .filter(|e| {
+                !matches!(
+                    e.kind,
+                    ExpressionKind::Break(_) | ExpressionKind::Comment(_)
+                )
+            })
+            .collect(),
+        _ => vec![e],
+    }
+}
+
+/// The text of a production after the `->`, on a single line.
+fn text(p: &Production) -> String {
+    let edition = p
+        .edition
+        .map(|edition| format!("@edition({edition}) "))
+        .unwrap_or_default();
+    let parameters = if p.is_generic() {
+        format!("<{}> ", p.parameters.join(", "))
+    } else {
+        String::new()
+    };
+    (edition + &parameters + &p.expression.to_string())
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Grammar;
+
+    fn diff(old: &str, new: &str) -> String {
+        let old = Grammar::grammar_from_str(old, "test").unwrap();
+        let new = Grammar::grammar_from_str(new, "test").unwrap();
+        old.diff(&new).to_string()
+    }
+
+    #[test]
+    fn unchanged() {
+        assert_eq!(
+            diff(
+                "@root A -> B (`x` | C) // comment\n\nB -> `b`\n\nC -> `c`",
+                "C -> `c`\n\n@root A ->\n    B\n    (\n        `x`\n      | C\n    )\n\nB -> `b`",
+            ),
+            "no changes\n"
+        );
+    }
+
+    #[test]
+    fn changes() {
+        assert_eq!(
+            diff(
+                "@root A -> B C?\n\nB -> `b`\n\nC -> `c`\n\n@root D -> B",
+                "@root A -> B C*\n\nB -> `b`\n\nC -> `c`\n\n@root E -> B `e`",
+            ),
+            "\
+added `E`
+    + B `e`
+    reachable from: E
+removed `D`
+    - B
+    reachable from: D
+changed `A`
+    - B C?
+    + B C*
+    reachable from: A
+"
+        );
+    }
+
+    #[test]
+    fn renamed() {
+        let old = Grammar::grammar_from_str("@root A -> B*\n\nB -> `b` B?", "test").unwrap();
+        let new = Grammar::grammar_from_str("@root A -> C*\n\nC -> `b` C?", "test").unwrap();
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.to_string(),
+            "renamed `B` to `C`\n    reachable from: A\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(&diff.to_json()).unwrap();
+        assert_eq!(json["renamed"][0]["old_name"], "B");
+        assert_eq!(json["renamed"][0]["roots"][0], "A");
+        assert!(json["renamed"][0].get("old").is_none());
+    }
+}
@@ -3,7 +3,20 @@ use std::fmt::{Display, Formatter};
 
 impl Display for Expression {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        match &self.kind {
+        if let Some(edition) = &self.edition {
+            write!(f, "@edition({edition}) ")?;
+        }
+        write!(f, "{}", self.kind)?;
+        if let Some(suffix) = &self.suffix {
+            write!(f, " _{suffix}_")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ExpressionKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
             ExpressionKind::Grouped(e) => write!(f, "({e})")?,
             ExpressionKind::Alt(es) => {
                 for (i, e) in es.iter().enumerate() {
@@ -59,9 +72,6 @@ impl Display for Expression {
             ExpressionKind::Cut(e) => write!(f, "^ {e}")?,
             ExpressionKind::Unicode((_, s)) => write!(f, "U+{s}")?,
         }
-        if let Some(suffix) = &self.suffix {
-            write!(f, " _{suffix}_")?;
-        }
         Ok(())
     }
 }
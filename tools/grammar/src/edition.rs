@@ -0,0 +1,204 @@
+//! Rust editions, and the `@edition(..)` annotation in the grammar.
+
+use crate::{Expression, Production, RangeLimit};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// A Rust edition.
+///
+/// The default is the latest edition.
+#[derive(
+    Clone, Copy, PartialEq, PartialOrd, Debug, Default, Eq, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Edition {
+    #[serde(rename = "2015")]
+    Edition2015,
+    #[serde(rename = "2018")]
+    Edition2018,
+    #[serde(rename = "2021")]
+    Edition2021,
+    #[default]
+    #[serde(rename = "2024")]
+    Edition2024,
+}
+
+impl Edition {
+    /// Every edition, from oldest to newest.
+    pub const ALL: &[Edition] = &[
+        Edition::Edition2015,
+        Edition::Edition2018,
+        Edition::Edition2021,
+        Edition::Edition2024,
+    ];
+
+    /// The year of the edition, such as `"2018"`.
+    pub fn year(self) -> &'static str {
+        match self {
+            Edition::Edition2015 => "2015",
+            Edition::Edition2018 => "2018",
+            Edition::Edition2021 => "2021",
+            Edition::Edition2024 => "2024",
+        }
+    }
+}
+
+impl FromStr for Edition {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        Edition::ALL
+            .iter()
+            .copied()
+            .find(|edition| edition.year() == s)
+            .ok_or(())
+    }
+}
+
+impl Display for Edition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.year())
+    }
+}
+
+/// The editions that an expression or production applies to, from an
+/// annotation like `@edition(2018..)`.
+///
+/// The range is written like a Rust range of edition years, such as
+/// `2018..`, `..2021`, `2015..=2018`, or `2018..2024`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditionRange {
+    /// The first edition, or `None` for every edition before `end`.
+    pub start: Option<Edition>,
+    /// The end of the range, or `None` for every edition after `start`.
+    pub end: Option<Edition>,
+    /// Whether `end` is included.
+    pub limit: RangeLimit,
+}
+
+impl EditionRange {
+    /// Whether the range includes the given edition.
+    pub fn contains(&self, edition: Edition) -> bool {
+        self.start.is_none_or(|start| start <= edition)
+            && self.end.is_none_or(|end| match self.limit {
+                RangeLimit::HalfOpen => edition < end,
+                RangeLimit::Closed => edition <= end,
+            })
+    }
+
+    /// The editions in the range, from oldest to newest.
+    pub fn editions(&self) -> impl Iterator<Item = Edition> {
+        Edition::ALL
+            .iter()
+            .copied()
+            .filter(|edition| self.contains(*edition))
+    }
+
+    /// Describes the range in English, such as "2018 edition and later".
+    pub fn description(&self) -> String {
+        let editions: Vec<_> = self.editions().collect();
+        let (Some(first), Some(last)) = (editions.first(), editions.last()) else {
+            return "no editions".to_string();
+        };
+        match (self.start, self.end) {
+            (None, None) => "all editions".to_string(),
+            (Some(_), None) => format!("{first} edition and later"),
+            (None, Some(_)) => format!("{last} edition and earlier"),
+            (Some(_), Some(_)) if first == last => format!("{first} edition only"),
+            (Some(_), Some(_)) => format!("{first} to {last} editions"),
+        }
+    }
+}
+
+impl FromStr for EditionRange {
+    type Err = String;
+
+    /// Parses the text inside `@edition(..)`.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (start, end, limit) = if let Some((start, end)) = s.split_once("..=") {
+            (start, end, RangeLimit::Closed)
+        } else if let Some((start, end)) = s.split_once("..") {
+            (start, end, RangeLimit::HalfOpen)
+        } else {
+            return Err(format!(
+                "expected a range of editions like `2018..`, found `{s}`"
+            ));
+        };
+        let edition = |year: &str| -> Result<Option<Edition>, String> {
+            if year.is_empty() {
+                return Ok(None);
+            }
+            year.parse()
+                .map(Some)
+                .map_err(|()| format!("unknown edition `{year}`"))
+        };
+        let range = EditionRange {
+            start: edition(start)?,
+            end: edition(end)?,
+            limit,
+        };
+        if limit == RangeLimit::Closed && range.end.is_none() {
+            return Err("expected an edition after `..=`".to_string());
+        }
+        if range.editions().next().is_none() {
+            return Err(format!("edition range `{s}` is empty"));
+        }
+        Ok(range)
+    }
+}
+
+impl Display for EditionRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(start) = self.start {
+            write!(f, "{start}")?;
+        }
+        write!(f, "{}", self.limit)?;
+        if let Some(end) = self.end {
+            write!(f, "{end}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Expression {
+    /// Whether the expression applies to the given edition, which is true
+    /// unless it has an `@edition(..)` annotation excluding it.
+    pub fn in_edition(&self, edition: Edition) -> bool {
+        self.edition.is_none_or(|range| range.contains(edition))
+    }
+}
+
+impl Production {
+    /// Whether the production applies to the given edition, which is true
+    /// unless it has an `@edition(..)` annotation excluding it.
+    pub fn in_edition(&self, edition: Edition) -> bool {
+        self.edition.is_none_or(|range| range.contains(edition))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Edition, EditionRange};
+
+    #[test]
+    fn ranges() {
+        let range = |s: &str| s.parse::<EditionRange>();
+        for (s, editions, description) in [
+            ("2018..", "2018 2021 2024", "2018 edition and later"),
+            ("..2021", "2015 2018", "2018 edition and earlier"),
+            ("2015..=2018", "2015 2018", "2015 to 2018 editions"),
+            ("2024..", "2024", "2024 edition and later"),
+            ("2018..2024", "2018 2021", "2018 to 2021 editions"),
+            ("2021..=2021", "2021", "2021 edition only"),
+        ] {
+            let r = range(s).unwrap();
+            assert_eq!(r.to_string(), s);
+            let years: Vec<_> = r.editions().map(Edition::year).collect();
+            assert_eq!(years.join(" "), editions);
+            assert_eq!(r.description(), description);
+        }
+        assert!(range("2018").is_err());
+        assert!(range("2019..").is_err());
+        assert!(range("2021..2018").is_err());
+        assert!(range("2018..=").is_err());
+    }
+}
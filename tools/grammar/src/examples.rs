@@ -0,0 +1,251 @@
+//! Examples of the input that a production accepts and rejects.
+//!
+//! A `grammar-test` code block names a production in its language string,
+//! and lists examples that the production must accept or reject:
+//!
+//! ````markdown
+//! ```grammar-test,LetStatement
+//! accept: let x = 1;
+//! reject: let = 1;
+//! accept:
+//!     let x = {
+//!         1
+//!     };
+//! ```
+//! ````
+//!
+//! An example is either on the same line as `accept:` or `reject:`, or on
+//! the lines after it indented by four spaces. Blank lines and lines
+//! starting with `//` between examples are ignored.
+//!
+//! The `parser` crate runs the examples.
+
+use crate::{Grammar, LineColumn, Span};
+use diagnostics::{Diagnostics, warn_or_err};
+use regex::Regex;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::LazyLock;
+
+pub static GRAMMAR_TEST_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?ms)^```grammar-test,([^\n]+)\n(.*?)^```").unwrap());
+
+/// The examples from a `grammar-test` block.
+#[derive(Clone, Debug)]
+pub struct GrammarTest {
+    /// The name of the production the examples are parsed with.
+    pub production: String,
+    pub examples: Vec<Example>,
+    /// Location of the block in the markdown source, not including the
+    /// fences.
+    pub span: Span,
+}
+
+/// An input that a production should accept or reject.
+#[derive(Clone, Debug)]
+pub struct Example {
+    pub source: String,
+    pub expect: Expect,
+    /// Location of the example in the markdown source, starting at the
+    /// `accept:` or `reject:`.
+    pub span: Span,
+}
+
+/// Whether an [`Example`] should parse.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Expect {
+    Accept,
+    Reject,
+}
+
+/// The indentation of the lines of an example that doesn't fit on one
+/// line.
+const INDENT: &str = "    ";
+
+/// Parses the `grammar-test` blocks in a markdown file, and adds them to
+/// the grammar.
+///
+/// `path` is recorded in the spans, and should be relative to the book's
+/// `src` directory. Lines that aren't examples are reported to `diag`.
+pub(crate) fn parse_grammar_tests(
+    content: &str,
+    path: &Path,
+    grammar: &mut Grammar,
+    diag: &mut Diagnostics,
+) {
+    for cap in GRAMMAR_TEST_RE.captures_iter(content) {
+        let block = cap.get(2).unwrap().range();
+        let mut test = GrammarTest {
+            production: cap[1].trim().to_string(),
+            examples: Vec::new(),
+            span: span(content, path, block.clone()),
+        };
+        let mut lines = lines(content, block).peekable();
+        while let Some((start, line)) = lines.next() {
+            if line.trim().is_empty() || line.starts_with("//") {
+                continue;
+            }
+            let (expect, rest) = if let Some(rest) = line.strip_prefix("accept:") {
+                (Expect::Accept, rest)
+            } else if let Some(rest) = line.strip_prefix("reject:") {
+                (Expect::Reject, rest)
+            } else {
+                let span = span(content, path, start..start + line.len());
+                warn_or_err!(
+                    diag,
+                    "{span}: expected `accept:` or `reject:` in grammar-test block, found `{line}`"
+                );
+                continue;
+            };
+            let mut end = start + line.len();
+            let source = if rest.trim().is_empty() {
+                let mut source_lines = Vec::new();
+                while let Some((line_start, line)) =
+                    lines.next_if(|(_, l)| l.starts_with(INDENT) || l.trim().is_empty())
+                {
+                    source_lines.push(line.strip_prefix(INDENT).unwrap_or(""));
+                    if !line.trim().is_empty() {
+                        end = line_start + line.len();
+                    }
+                }
+                while source_lines.last().is_some_and(|l| l.trim().is_empty()) {
+                    source_lines.pop();
+                }
+                source_lines.join("\n")
+            } else {
+                rest.strip_prefix(' ').unwrap_or(rest).to_string()
+            };
+            let span = span(content, path, start..end);
+            if source.is_empty() {
+                warn_or_err!(diag, "{span}: expected an example after `{line}`");
+                continue;
+            }
+            test.examples.push(Example {
+                source,
+                expect,
+                span,
+            });
+        }
+        grammar.tests.push(test);
+    }
+}
+
+/// Checks that the productions named by `grammar-test` blocks exist.
+pub(crate) fn check_grammar_tests(grammar: &Grammar, diag: &mut Diagnostics) {
+    for test in &grammar.tests {
+        if !grammar.productions.contains_key(&test.production) {
+            warn_or_err!(
+                diag,
+                "{}: grammar-test block for undefined production `{}`",
+                test.span,
+                test.production
+            );
+        }
+    }
+}
+
+/// Returns the lines in a range of the content, along with the byte offset
+/// where each starts.
+fn lines(content: &str, range: Range<usize>) -> impl Iterator<Item = (usize, &str)> {
+    let start = range.start;
+    content[range]
+        .split_inclusive('\n')
+        .scan(start, |offset, line| {
+            let line_start = *offset;
+            *offset += line.len();
+            Some((line_start, line.strip_suffix('\n').unwrap_or(line)))
+        })
+}
+
+/// Converts a byte range of a markdown file to a [`Span`].
+fn span(content: &str, path: &Path, range: Range<usize>) -> Span {
+    let line_column = |index: usize| {
+        let before = &content[..index];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        LineColumn {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    };
+    Span {
+        path: path.to_owned(),
+        start: line_column(range.start),
+        end: line_column(range.end),
+        range,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Expect, parse_grammar_tests};
+    use crate::{Grammar, LineColumn};
+    use diagnostics::Diagnostics;
+    use std::path::Path;
+
+    fn parse(content: &str) -> (Grammar, u32) {
+        let mut grammar = Grammar::default();
+        let mut diag = Diagnostics::new();
+        parse_grammar_tests(content, Path::new("test.md"), &mut grammar, &mut diag);
+        (grammar, diag.count)
+    }
+
+    #[test]
+    fn examples() {
+        let (g, count) = parse(
+            "# Test\n\
+             \n\
+             ```grammar-test,LetStatement\n\
+             // A comment.\n\
+             accept: let x = 1;\n\
+             reject: let = 1;\n\
+             \n\
+             accept:\n    \
+                 let x = {\n\
+             \n        \
+                     1\n    \
+                 };\n\
+             \n\
+             reject:  let\n\
+             ```\n",
+        );
+        assert_eq!(count, 0);
+        assert_eq!(g.tests.len(), 1);
+        let test = &g.tests[0];
+        assert_eq!(test.production, "LetStatement");
+        assert_eq!(test.span.start, LineColumn { line: 4, column: 1 });
+        let examples: Vec<_> = test
+            .examples
+            .iter()
+            .map(|e| (e.expect, e.source.as_str(), e.span.start.line))
+            .collect();
+        assert_eq!(
+            examples,
+            [
+                (Expect::Accept, "let x = 1;", 5),
+                (Expect::Reject, "let = 1;", 6),
+                (Expect::Accept, "let x = {\n\n    1\n};", 8),
+                (Expect::Reject, " let", 14),
+            ]
+        );
+        assert_eq!(
+            test.examples[2].span.end,
+            LineColumn {
+                line: 12,
+                column: 7
+            }
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let (g, count) = parse(
+            "```grammar-test,A\n\
+             accept `a`\n\
+             accept:\n\
+             reject: b\n\
+             ```\n",
+        );
+        assert_eq!(count, 2);
+        assert_eq!(g.tests[0].examples.len(), 1);
+    }
+}
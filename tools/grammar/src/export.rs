@@ -0,0 +1,761 @@
+//! Exporting the grammar to standard grammar notations.
+//!
+//! Constructs that have no equivalent in the target notation (such as cut
+//! and negative lookahead) are written as comments next to the closest
+//! approximation, so that it is clear where the exported grammar is less
+//! precise than the Reference.
+//!
+//! None of the notations have parameters, so productions with parameters
+//! are written as their instances, with names such as
+//! `CommaList_Expression` for `CommaList<Expression>`.
+
+use crate::charset::{CharSet, MAX_CHAR};
+use crate::parameters::identifier;
+use crate::{Character, Expression, ExpressionKind, Grammar, Production, RangeLimit};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A standard notation that the grammar can be exported to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// The EBNF notation from the W3C XML specification, as accepted by
+    /// railroad diagram generators.
+    W3cEbnf,
+    /// Augmented BNF from RFC 5234.
+    ///
+    /// Underscores in production names are replaced with hyphens.
+    Abnf,
+    /// The EBNF notation from ISO/IEC 14977.
+    IsoEbnf,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "w3c-ebnf" => Ok(ExportFormat::W3cEbnf),
+            "abnf" => Ok(ExportFormat::Abnf),
+            "iso-ebnf" => Ok(ExportFormat::IsoEbnf),
+            _ => Err(format!("invalid export format: {s}")),
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            ExportFormat::W3cEbnf => write!(f, "w3c-ebnf"),
+            ExportFormat::Abnf => write!(f, "abnf"),
+            ExportFormat::IsoEbnf => write!(f, "iso-ebnf"),
+        }
+    }
+}
+
+impl Grammar {
+    /// Converts the grammar to the given notation.
+    ///
+    /// Productions are written in the order they were discovered, followed
+    /// by the instances of productions with parameters.
+    pub fn export(&self, format: ExportFormat) -> String {
+        let mut exporter = Exporter {
+            grammar: self,
+            format,
+            out: String::new(),
+        };
+        for name in &self.name_order {
+            let prod = &self.productions[name];
+            if !prod.is_generic() {
+                exporter.production(prod);
+            }
+        }
+        exporter.out
+    }
+}
+
+struct Exporter<'a> {
+    grammar: &'a Grammar,
+    format: ExportFormat,
+    out: String,
+}
+
+impl Exporter<'_> {
+    fn production(&mut self, prod: &Production) {
+        for comment in &prod.comments {
+            if let ExpressionKind::Comment(text) = &comment.kind {
+                self.comment(text.trim());
+                self.out.push('\n');
+            }
+        }
+        if let Some(edition) = &prod.edition {
+            self.comment(&edition.description());
+            self.out.push('\n');
+        }
+        let name = self.name(&prod.name);
+        let define = match self.format {
+            ExportFormat::W3cEbnf => "::=",
+            ExportFormat::Abnf | ExportFormat::IsoEbnf => "=",
+        };
+        self.out.push_str(&format!("{name} {define} "));
+        self.expr_or_empty(&prod.expression);
+        self.trim_end();
+        if self.format == ExportFormat::IsoEbnf {
+            self.out.push_str(" ;");
+        }
+        self.out.push_str("\n\n");
+    }
+
+    /// Writes the expression, and returns whether anything other than
+    /// whitespace and comments was written.
+    fn expr(&mut self, e: &Expression) -> bool {
+        let written = match &e.kind {
+            ExpressionKind::Grouped(e) => {
+                self.out.push('(');
+                self.expr_or_empty(e);
+                self.out.push(')');
+                true
+            }
+            ExpressionKind::Alt(es) => {
+                let sep = match self.format {
+                    ExportFormat::W3cEbnf | ExportFormat::IsoEbnf => "|",
+                    ExportFormat::Abnf => "/",
+                };
+                for (i, e) in es.iter().enumerate() {
+                    if i > 0 {
+                        self.space();
+                        self.out.push_str(sep);
+                        self.out.push(' ');
+                    }
+                    self.expr_or_empty(e);
+                }
+                true
+            }
+            ExpressionKind::Sequence(es) => {
+                // The edition is noted after the last element, rather than
+                // after a comment or line break at the end.
+                let edition = e.edition;
+                let last = es
+                    .iter()
+                    .rposition(|e| !e.is_break() && !matches!(e.kind, ExpressionKind::Comment(_)));
+                // End of the previous element that was written as something
+                // other than comments or whitespace.
+                let mut prev_end = None;
+                for (i, e) in es.iter().enumerate() {
+                    let start = self.out.len();
+                    if self.expr(e) {
+                        self.seq_separator(prev_end, start);
+                        prev_end = Some(self.out.trim_end().len());
+                    }
+                    if Some(i) == last
+                        && let Some(edition) = edition
+                    {
+                        self.annotate(&edition.description());
+                    }
+                }
+                prev_end.is_some()
+            }
+            ExpressionKind::Optional(e) => {
+                match self.format {
+                    ExportFormat::W3cEbnf => {
+                        self.operand(e);
+                        self.out.push('?');
+                    }
+                    ExportFormat::Abnf | ExportFormat::IsoEbnf => self.bracketed('[', e, ']'),
+                }
+                true
+            }
+            ExpressionKind::NegativeLookahead(e) => {
+                self.annotate(&format!("not followed by {e}"));
+                false
+            }
+            ExpressionKind::Repeat(e) => {
+                self.repeat(e);
+                true
+            }
+            ExpressionKind::RepeatPlus(e) => {
+                match self.format {
+                    ExportFormat::W3cEbnf => {
+                        self.operand(e);
+                        self.out.push('+');
+                    }
+                    ExportFormat::Abnf => {
+                        self.out.push_str("1*");
+                        self.operand(e);
+                    }
+                    ExportFormat::IsoEbnf => {
+                        self.operand(e);
+                        self.out.push_str(", ");
+                        self.repeat(e);
+                    }
+                }
+                true
+            }
+            ExpressionKind::RepeatRange {
+                expr,
+                name,
+                min,
+                max,
+                limit,
+            } => {
+                self.repeat_range(expr, *min, *max, *limit);
+                if let Some(name) = name {
+                    self.annotate(&format!("repetition count bound to `{name}`"));
+                }
+                true
+            }
+            ExpressionKind::RepeatRangeNamed(e, name) => {
+                self.repeat(e);
+                self.annotate(&format!("repeated `{name}` times"));
+                true
+            }
+            ExpressionKind::Nt(nt) => {
+                let name = self.name(nt);
+                self.out.push_str(&name);
+                true
+            }
+            ExpressionKind::Terminal(s) => {
+                self.terminal(s);
+                true
+            }
+            ExpressionKind::Prose(s) => self.prose(s),
+            ExpressionKind::Break(indent) => {
+                self.trim_end();
+                self.out.push('\n');
+                self.out.push_str(&" ".repeat((*indent).max(1)));
+                false
+            }
+            ExpressionKind::Comment(text) => {
+                self.space();
+                self.comment(text.trim());
+                false
+            }
+            ExpressionKind::Charset(es) => {
+                self.charset(e, es);
+                true
+            }
+            ExpressionKind::CharacterRange(start, end) => {
+                self.character_range(start, end);
+                true
+            }
+            ExpressionKind::NegExpression(e) => {
+                self.neg_expression(e);
+                true
+            }
+            ExpressionKind::Cut(e) => {
+                self.annotate("cut: no backtracking past this point");
+                self.space();
+                self.expr(e)
+            }
+            ExpressionKind::Unicode((ch, _)) => {
+                self.code_point(*ch as u32);
+                true
+            }
+        };
+        if let Some(suffix) = &e.suffix {
+            self.annotate(suffix);
+        }
+        if !matches!(e.kind, ExpressionKind::Sequence(_))
+            && let Some(edition) = &e.edition
+        {
+            self.annotate(&edition.description());
+        }
+        written
+    }
+
+    /// Writes the expression, using an empty string in ABNF if the
+    /// expression only contains comments.
+    fn expr_or_empty(&mut self, e: &Expression) {
+        if !self.expr(e) && self.format == ExportFormat::Abnf {
+            self.space();
+            self.out.push_str("\"\"");
+        }
+    }
+
+    /// Writes an expression that a repetition operator applies to, adding
+    /// parentheses if needed.
+    fn operand(&mut self, e: &Expression) {
+        if self.is_primary(e) {
+            self.expr(e);
+        } else {
+            self.out.push('(');
+            self.expr_or_empty(e);
+            self.out.push(')');
+        }
+    }
+
+    /// Whether the expression is written as a single unit that can be the
+    /// operand of a repetition operator.
+    fn is_primary(&self, e: &Expression) -> bool {
+        if e.suffix.is_some() || e.edition.is_some() {
+            return false;
+        }
+        match &e.kind {
+            ExpressionKind::Grouped(_)
+            | ExpressionKind::Nt(_)
+            | ExpressionKind::Terminal(_)
+            | ExpressionKind::Charset(_)
+            | ExpressionKind::CharacterRange(..)
+            | ExpressionKind::Unicode(_) => true,
+            ExpressionKind::Optional(_) => self.format != ExportFormat::W3cEbnf,
+            ExpressionKind::Repeat(_) => self.format == ExportFormat::IsoEbnf,
+            ExpressionKind::NegExpression(e) => {
+                self.format != ExportFormat::Abnf || self.grammar.char_set(e).is_some()
+            }
+            ExpressionKind::Prose(s) => match self.format {
+                ExportFormat::W3cEbnf => false,
+                ExportFormat::Abnf => is_abnf_prose(s),
+                ExportFormat::IsoEbnf => true,
+            },
+            ExpressionKind::Alt(_)
+            | ExpressionKind::Sequence(_)
+            | ExpressionKind::NegativeLookahead(_)
+            | ExpressionKind::RepeatPlus(_)
+            | ExpressionKind::RepeatRange { .. }
+            | ExpressionKind::RepeatRangeNamed(..)
+            | ExpressionKind::Break(_)
+            | ExpressionKind::Comment(_)
+            | ExpressionKind::Cut(_) => false,
+        }
+    }
+
+    /// Writes zero or more repetitions of the expression.
+    fn repeat(&mut self, e: &Expression) {
+        match self.format {
+            ExportFormat::W3cEbnf => {
+                self.operand(e);
+                self.out.push('*');
+            }
+            ExportFormat::Abnf => {
+                self.out.push('*');
+                self.operand(e);
+            }
+            ExportFormat::IsoEbnf => self.bracketed('{', e, '}'),
+        }
+    }
+
+    /// Writes the expression between brackets, without any redundant
+    /// parentheses.
+    fn bracketed(&mut self, open: char, e: &Expression, close: char) {
+        self.out.push(open);
+        match &e.kind {
+            ExpressionKind::Grouped(inner) if e.suffix.is_none() => self.expr_or_empty(inner),
+            _ => self.expr_or_empty(e),
+        }
+        self.out.push(close);
+    }
+
+    fn repeat_range(
+        &mut self,
+        e: &Expression,
+        min: Option<u32>,
+        max: Option<u32>,
+        limit: RangeLimit,
+    ) {
+        let lo = min.unwrap_or(0);
+        let hi = max.map(|max| match limit {
+            RangeLimit::HalfOpen => max.saturating_sub(1),
+            RangeLimit::Closed => max,
+        });
+        match self.format {
+            ExportFormat::W3cEbnf => {
+                self.operand(e);
+                match (lo, hi) {
+                    (0, None) => self.out.push('*'),
+                    (1, None) => self.out.push('+'),
+                    (0, Some(1)) => self.out.push('?'),
+                    (1, Some(1)) => {}
+                    _ => {
+                        self.out.push(if lo == 0 { '*' } else { '+' });
+                        let range = format!(
+                            "{}{limit}{}",
+                            min.map(|v| v.to_string()).unwrap_or_default(),
+                            max.map(|v| v.to_string()).unwrap_or_default(),
+                        );
+                        self.annotate(&format!("repeated {range} times"));
+                    }
+                }
+            }
+            ExportFormat::Abnf => {
+                match (lo, hi) {
+                    (lo, Some(hi)) if lo == hi => self.out.push_str(&lo.to_string()),
+                    _ => {
+                        if lo > 0 {
+                            self.out.push_str(&lo.to_string());
+                        }
+                        self.out.push('*');
+                        if let Some(hi) = hi {
+                            self.out.push_str(&hi.to_string());
+                        }
+                    }
+                }
+                self.operand(e);
+            }
+            ExportFormat::IsoEbnf => {
+                let mut parts = 0;
+                if lo > 0 {
+                    self.out.push_str(&format!("{lo} * "));
+                    self.operand(e);
+                    parts += 1;
+                }
+                let optional = hi.map(|hi| hi.saturating_sub(lo));
+                if optional != Some(0) {
+                    if parts > 0 {
+                        self.out.push_str(", ");
+                    }
+                    match optional {
+                        None => self.repeat(e),
+                        Some(n) => {
+                            self.out.push_str(&format!("{n} * "));
+                            self.bracketed('[', e, ']');
+                        }
+                    }
+                    parts += 1;
+                }
+                if parts == 0 {
+                    self.annotate("repeated zero times");
+                }
+            }
+        }
+    }
+
+    fn terminal(&mut self, s: &str) {
+        match self.format {
+            ExportFormat::W3cEbnf | ExportFormat::IsoEbnf => {
+                let sep = if self.format == ExportFormat::W3cEbnf {
+                    " "
+                } else {
+                    ", "
+                };
+                // Neither notation has escapes, so a string with both kinds
+                // of quotes needs to be split up.
+                let mut pieces = Vec::new();
+                let mut rest = s;
+                while !rest.is_empty() {
+                    let quote = if rest.starts_with('"') { '\'' } else { '"' };
+                    let end = rest.find(quote).unwrap_or(rest.len());
+                    pieces.push(format!("{quote}{}{quote}", &rest[..end]));
+                    rest = &rest[end..];
+                }
+                if pieces.len() == 1 {
+                    self.out.push_str(&pieces[0]);
+                } else {
+                    self.out.push('(');
+                    self.out.push_str(&pieces.join(sep));
+                    self.out.push(')');
+                }
+            }
+            ExportFormat::Abnf => {
+                // Quoted strings in ABNF are case-insensitive, and cannot
+                // contain quotes or non-ASCII characters.
+                if s.chars()
+                    .all(|ch| matches!(ch, ' '..='~') && ch != '"' && !ch.is_ascii_alphabetic())
+                {
+                    self.out.push_str(&format!("\"{s}\""));
+                } else {
+                    let hex: Vec<_> = s.chars().map(|ch| format!("{:02X}", ch as u32)).collect();
+                    self.out.push_str(&format!("%x{}", hex.join(".")));
+                }
+            }
+        }
+    }
+
+    /// Writes a prose description, and returns whether it was written as
+    /// something other than a comment.
+    fn prose(&mut self, s: &str) -> bool {
+        match self.format {
+            ExportFormat::W3cEbnf => {
+                self.annotate(&format!("<{s}>"));
+                false
+            }
+            ExportFormat::Abnf => {
+                if is_abnf_prose(s) {
+                    self.out.push_str(&format!("<{s}>"));
+                    true
+                } else {
+                    self.annotate(&format!("<{s}>"));
+                    false
+                }
+            }
+            ExportFormat::IsoEbnf => {
+                self.special(s);
+                true
+            }
+        }
+    }
+
+    fn charset(&mut self, e: &Expression, es: &[Expression]) {
+        if self.format == ExportFormat::W3cEbnf
+            && let Some(chars) = self.grammar.char_set(e)
+        {
+            self.char_class(chars.ranges(), false);
+            return;
+        }
+        if es.len() > 1 {
+            self.out.push('(');
+        }
+        for (i, e) in es.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(match self.format {
+                    ExportFormat::W3cEbnf | ExportFormat::IsoEbnf => " | ",
+                    ExportFormat::Abnf => " / ",
+                });
+            }
+            self.expr(e);
+        }
+        if es.len() > 1 {
+            self.out.push(')');
+        }
+    }
+
+    fn character_range(&mut self, start: &Character, end: &Character) {
+        let (start, end) = (start.get_ch() as u32, end.get_ch() as u32);
+        match self.format {
+            ExportFormat::W3cEbnf => self.char_class(&[(start, end)], false),
+            ExportFormat::Abnf => self.out.push_str(&format!("%x{start:02X}-{end:02X}")),
+            ExportFormat::IsoEbnf => {
+                self.special(&format!("characters U+{start:04X} to U+{end:04X}"))
+            }
+        }
+    }
+
+    /// Writes a single character that is not in the given expression.
+    fn neg_expression(&mut self, e: &Expression) {
+        let chars = self.grammar.char_set(e);
+        match (self.format, chars) {
+            (ExportFormat::W3cEbnf, Some(chars)) => self.char_class(chars.ranges(), true),
+            (ExportFormat::W3cEbnf, None) => {
+                self.out.push('(');
+                self.char_class(CharSet::all().ranges(), false);
+                self.out.push_str(" - ");
+                self.operand(e);
+                self.out.push(')');
+            }
+            (ExportFormat::Abnf, Some(chars)) => {
+                let complement = chars.complement();
+                let complement = complement.ranges();
+                if complement.len() > 1 {
+                    self.out.push('(');
+                }
+                for (i, (start, end)) in complement.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(" / ");
+                    }
+                    if start == end {
+                        self.code_point(*start);
+                    } else {
+                        self.out.push_str(&format!("%x{start:02X}-{end:02X}"));
+                    }
+                }
+                if complement.len() > 1 {
+                    self.out.push(')');
+                }
+            }
+            (ExportFormat::Abnf, None) => {
+                self.annotate(&format!("any character except {e}"));
+                self.out.push_str(&format!("%x00-{MAX_CHAR:X}"));
+            }
+            (ExportFormat::IsoEbnf, _) => {
+                self.out.push('(');
+                self.special("any character");
+                self.out.push_str(" - ");
+                self.operand(e);
+                self.out.push(')');
+            }
+        }
+    }
+
+    /// Writes a W3C character class.
+    fn char_class(&mut self, ranges: &[(u32, u32)], negated: bool) {
+        fn class_char(ch: u32) -> String {
+            match char::from_u32(ch) {
+                Some(c) if c.is_ascii_alphanumeric() => c.to_string(),
+                _ => format!("#x{ch:X}"),
+            }
+        }
+        self.out.push('[');
+        if negated {
+            self.out.push('^');
+        }
+        for (start, end) in ranges {
+            self.out.push_str(&class_char(*start));
+            if start != end {
+                self.out.push('-');
+                self.out.push_str(&class_char(*end));
+            }
+        }
+        self.out.push(']');
+    }
+
+    fn code_point(&mut self, ch: u32) {
+        match self.format {
+            ExportFormat::W3cEbnf => self.out.push_str(&format!("#x{ch:04X}")),
+            ExportFormat::Abnf => self.out.push_str(&format!("%x{ch:02X}")),
+            ExportFormat::IsoEbnf => self.special(&format!("U+{ch:04X}")),
+        }
+    }
+
+    fn name(&self, name: &str) -> String {
+        let name = identifier(name);
+        match self.format {
+            ExportFormat::W3cEbnf | ExportFormat::IsoEbnf => name,
+            ExportFormat::Abnf => name.replace('_', "-"),
+        }
+    }
+
+    /// Writes an ISO special sequence.
+    fn special(&mut self, text: &str) {
+        self.out
+            .push_str(&format!("? {} ?", text.replace('?', "U+003F")));
+    }
+
+    /// Inserts a separator before the sequence element that starts at the
+    /// given index of the output.
+    ///
+    /// `prev_end` is where the previous element ended, ignoring comments
+    /// and whitespace.
+    fn seq_separator(&mut self, prev_end: Option<usize>, start: usize) {
+        if !self.out[start..].starts_with([' ', '\n'])
+            && !self.out[..start].ends_with([' ', '\n', '(', '[', '{'])
+        {
+            self.out.insert(start, ' ');
+        }
+        if let Some(prev_end) = prev_end
+            && self.format == ExportFormat::IsoEbnf
+        {
+            self.out.insert(prev_end, ',');
+        }
+    }
+
+    /// Writes a comment with the given text.
+    ///
+    /// In ABNF, this runs to the end of the line.
+    fn comment(&mut self, text: &str) {
+        match self.format {
+            ExportFormat::W3cEbnf => {
+                self.out
+                    .push_str(&format!("/* {} */", text.replace("*/", "* /")));
+            }
+            ExportFormat::Abnf => self.out.push_str(&format!("; {text}")),
+            ExportFormat::IsoEbnf => {
+                let text = text.replace("(*", "( *").replace("*)", "* )");
+                self.out.push_str(&format!("(* {text} *)"));
+            }
+        }
+    }
+
+    /// Writes a comment for something that cannot be expressed in the
+    /// target notation.
+    fn annotate(&mut self, text: &str) {
+        self.space();
+        self.comment(text);
+        if self.format == ExportFormat::Abnf {
+            // Continue the rule on the next line.
+            self.out.push_str("\n    ");
+        }
+    }
+
+    /// Adds a space unless the output already ends with whitespace or an
+    /// opening bracket.
+    fn space(&mut self) {
+        if !self.out.ends_with([' ', '\n', '(', '[', '{']) {
+            self.out.push(' ');
+        }
+    }
+
+    fn trim_end(&mut self) {
+        let len = self.out.trim_end().len();
+        self.out.truncate(len);
+    }
+}
+
+/// Whether the prose can be written as an ABNF `prose-val`.
+fn is_abnf_prose(s: &str) -> bool {
+    s.chars().all(|ch| matches!(ch, ' '..='~') && ch != '>')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExportFormat;
+    use crate::Grammar;
+
+    const GRAMMAR: &str = "\
+// A comment.
+@root Rule ->
+      A{n:1..=3} B{n}
+    | ^ [`a`-`z` `_` C]
+    | !`x` ~[`y` C] (A | B)? A* B+ A{2..} B{..4}
+
+A -> <prose> _suffix_
+
+B -> `struct`
+
+C -> U+000A";
+
+    fn export(format: ExportFormat) -> String {
+        Grammar::grammar_from_str(GRAMMAR, "test")
+            .unwrap()
+            .export(format)
+    }
+
+    #[test]
+    fn w3c_ebnf() {
+        assert_eq!(
+            export(ExportFormat::W3cEbnf),
+            r#"/* A comment. */
+Rule ::=
+      A+ /* repeated 1..=3 times */ /* repetition count bound to `n` */ B* /* repeated `n` times */
+    | /* cut: no backtracking past this point */ [#xA#x5Fa-z]
+    | /* not followed by `x` */ [^#xAy] (A | B)? A* B+ A+ /* repeated 2.. times */ B* /* repeated ..4 times */
+
+A ::= /* <prose> */ /* suffix */
+
+B ::= "struct"
+
+C ::= #x000A
+
+"#
+        );
+    }
+
+    #[test]
+    fn abnf() {
+        assert_eq!(
+            export(ExportFormat::Abnf),
+            r#"; A comment.
+Rule =
+      1*3A ; repetition count bound to `n`
+    *B ; repeated `n` times
+    / ; cut: no backtracking past this point
+    (%x61-7A / "_" / C)
+    / ; not followed by `x`
+    (%x00-09 / %x0B-78 / %x7A-10FFFF) [A / B] *A 1*B 2*A *3B
+
+A = <prose> ; suffix
+
+B = %x73.74.72.75.63.74
+
+C = %x0A
+
+"#
+        );
+    }
+
+    #[test]
+    fn iso_ebnf() {
+        assert_eq!(
+            export(ExportFormat::IsoEbnf),
+            r#"(* A comment. *)
+Rule =
+      1 * A, 2 * [A] (* repetition count bound to `n` *), {B} (* repeated `n` times *)
+    | (* cut: no backtracking past this point *) (? characters U+0061 to U+007A ? | "_" | C)
+    | (* not followed by `x` *) (? any character ? - ("y" | C)), [A | B], {A}, B, {B}, 2 * A, {A}, 3 * [B] ;
+
+A = ? prose ? (* suffix *) ;
+
+B = "struct" ;
+
+C = ? U+000A ? ;
+
+"#
+        );
+    }
+}
@@ -0,0 +1,449 @@
+//! Formatting of grammar productions with a canonical layout.
+//!
+//! The formatter keeps the line structure that the author chose (the
+//! `Break` expressions), along with comments, suffixes, footnotes, and
+//! edition annotations, and normalizes everything else:
+//!
+//! - Elements on a line are separated by a single space, with no space
+//!   inside parentheses.
+//! - The alternatives of a production that spans multiple lines (or that
+//!   doesn't fit on a single line) are each placed on their own line, with
+//!   the `|` aligned:
+//!
+//!   ```text
+//!   Name ->
+//!         A
+//!       | B
+//!   ```
+//!
+//! - A group whose alternatives are on separate lines is laid out the same
+//!   way, indented from the line where the group starts, with the closing
+//!   parenthesis on its own line.
+//! - Other line breaks are indented in steps of four spaces, relative to the
+//!   first line of the production or alternative.
+
+use crate::parser::{self, ParseError};
+use crate::{EditionRange, Expression, ExpressionKind, GRAMMAR_RE, Grammar, Production};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Productions with alternatives that would be longer than this on one line
+/// are split so that each alternative is on a separate line.
+const MAX_WIDTH: usize = 100;
+
+/// The indentation of a multi-line part of a production, such as an
+/// alternative.
+///
+/// Line breaks within it keep their indentation relative to the start of
+/// the part, rounded to a multiple of four.
+#[derive(Clone, Copy)]
+struct Block {
+    /// Where the part starts in the source.
+    src: usize,
+    /// Where the part starts in the output.
+    out: usize,
+}
+
+impl Block {
+    /// The output indentation of a `Break` with the given width.
+    fn indent(&self, width: usize) -> usize {
+        match width.saturating_sub(self.src) {
+            0 => self.out,
+            relative => self.out + ((relative + 2) / 4).max(1) * 4,
+        }
+    }
+}
+
+struct Formatter {
+    output: String,
+    /// Indentation of the line currently being written.
+    line_indent: usize,
+}
+
+impl Production {
+    /// Returns the production formatted with the canonical layout.
+    ///
+    /// See [`format_markdown`] for reformatting the grammar blocks in a
+    /// chapter.
+    pub fn format(&self) -> String {
+        let mut f = Formatter::new();
+        for comment in &self.comments {
+            if let ExpressionKind::Comment(s) = &comment.kind {
+                writeln!(f.output, "//{s}").unwrap();
+            }
+        }
+        if self.is_root {
+            f.output.push_str("@root ");
+        }
+        if let Some(edition) = &self.edition {
+            write!(f.output, "@edition({edition}) ").unwrap();
+        }
+        write!(f.output, "{} ->", self.name_with_parameters()).unwrap();
+        let head = f.output.clone();
+
+        // Continuation lines are indented relative to the first line after
+        // the name, or by four spaces if it starts on the same line.
+        let block = match first_break(&self.expression) {
+            Some(width) => Block { src: width, out: 4 },
+            None => {
+                f.output.push(' ');
+                Block { src: 0, out: 0 }
+            }
+        };
+        f.expr(&self.expression, block);
+        let ExpressionKind::Alt(es) = &self.expression.kind else {
+            return f.output;
+        };
+        let last_line = f.output.lines().last().unwrap_or_default();
+        if !f.output[head.len()..].contains('\n') && last_line.chars().count() <= MAX_WIDTH {
+            return f.output;
+        }
+        let mut f = Formatter::new();
+        f.output = head;
+        f.alternatives(es, 4);
+        f.output
+    }
+}
+
+/// Reformats every grammar block in the given markdown.
+///
+/// `path` is used for the location of any parse errors. If any of the
+/// blocks fail to parse, all of the errors are returned.
+pub fn format_markdown(content: &str, path: &Path) -> Result<String, Vec<ParseError>> {
+    let mut output = String::new();
+    let mut errors = Vec::new();
+    let mut last = 0;
+    for cap in GRAMMAR_RE.captures_iter(content) {
+        let block = cap.get(2).unwrap().range();
+        let mut grammar = Grammar::default();
+        if let Err(e) = parser::parse_grammar(content, block.clone(), &mut grammar, &cap[1], path) {
+            errors.extend(e);
+            continue;
+        }
+        if grammar.name_order.is_empty() {
+            continue;
+        }
+        let productions: Vec<_> = grammar
+            .name_order
+            .iter()
+            .map(|name| grammar.productions[name].format())
+            .collect();
+        output.push_str(&content[last..block.start]);
+        output.push_str(&productions.join("\n\n"));
+        output.push('\n');
+        last = block.end;
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    output.push_str(&content[last..]);
+    Ok(output)
+}
+
+impl Formatter {
+    fn new() -> Formatter {
+        Formatter {
+            output: String::new(),
+            // Continuation lines of a production are indented by at least
+            // this much.
+            line_indent: 4,
+        }
+    }
+
+    fn newline(&mut self, indent: usize) {
+        self.output.push('\n');
+        self.output.push_str(&" ".repeat(indent));
+        self.line_indent = indent;
+    }
+
+    /// Writes each alternative on a separate line, with the `|` at the
+    /// given column.
+    fn alternatives(&mut self, es: &[Expression], pipe: usize) {
+        for (i, e) in es.iter().enumerate() {
+            let es = trim_breaks(e);
+            self.output.push('\n');
+            if i == 0 {
+                self.output.push_str(&" ".repeat(pipe + 2));
+            } else {
+                self.output.push_str(&" ".repeat(pipe));
+                self.output.push_str("| ");
+            }
+            self.line_indent = pipe + 2;
+            let block = Block {
+                src: src_indent(es),
+                out: pipe + 2,
+            };
+            let edition = match e.kind {
+                ExpressionKind::Sequence(_) => e.edition.as_ref(),
+                _ => None,
+            };
+            self.seq(es, edition, block);
+            // Remove a line break at the end that `trim_breaks` can't see,
+            // such as one at the end of a cut.
+            self.output.truncate(self.output.trim_end().len());
+        }
+    }
+
+    /// Writes the elements of a sequence, with the sequence's edition
+    /// annotation before the first element that isn't a line break.
+    fn seq(&mut self, es: &[Expression], mut edition: Option<&EditionRange>, block: Block) {
+        for (i, e) in es.iter().enumerate() {
+            if i > 0 && !e.is_break() && !es[i - 1].is_break() {
+                self.output.push(' ');
+            }
+            if !e.is_break()
+                && let Some(edition) = edition.take()
+            {
+                write!(self.output, "@edition({edition}) ").unwrap();
+            }
+            self.expr(e, block);
+        }
+    }
+
+    fn expr(&mut self, e: &Expression, block: Block) {
+        if !matches!(e.kind, ExpressionKind::Sequence(_))
+            && let Some(edition) = &e.edition
+        {
+            write!(self.output, "@edition({edition}) ").unwrap();
+        }
+        match &e.kind {
+            ExpressionKind::Grouped(inner) => match &inner.kind {
+                ExpressionKind::Alt(es) if es.iter().any(has_edge_break) => {
+                    let indent = self.line_indent;
+                    self.output.push('(');
+                    self.alternatives(es, indent + 2);
+                    self.newline(indent);
+                    self.output.push(')');
+                }
+                _ => {
+                    self.output.push('(');
+                    self.expr(inner, block);
+                    self.output.push(')');
+                }
+            },
+            ExpressionKind::Alt(es) => {
+                for (i, e) in es.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(" | ");
+                    }
+                    self.expr(e, block);
+                }
+            }
+            ExpressionKind::Sequence(es) => self.seq(es, e.edition.as_ref(), block),
+            ExpressionKind::Optional(e) => {
+                self.expr(e, block);
+                self.output.push('?');
+            }
+            ExpressionKind::NegativeLookahead(e) => {
+                self.output.push('!');
+                self.expr(e, block);
+            }
+            ExpressionKind::Repeat(e) => {
+                self.expr(e, block);
+                self.output.push('*');
+            }
+            ExpressionKind::RepeatPlus(e) => {
+                self.expr(e, block);
+                self.output.push('+');
+            }
+            ExpressionKind::RepeatRange {
+                expr,
+                name,
+                min,
+                max,
+                limit,
+            } => {
+                self.expr(expr, block);
+                write!(
+                    self.output,
+                    "{{{}{}{limit}{}}}",
+                    name.as_ref().map(|n| format!("{n}:")).unwrap_or_default(),
+                    min.map(|v| v.to_string()).unwrap_or_default(),
+                    max.map(|v| v.to_string()).unwrap_or_default(),
+                )
+                .unwrap();
+            }
+            ExpressionKind::RepeatRangeNamed(e, name) => {
+                self.expr(e, block);
+                write!(self.output, "{{{name}}}").unwrap();
+            }
+            ExpressionKind::NegExpression(e) => {
+                self.output.push('~');
+                self.expr(e, block);
+            }
+            ExpressionKind::Cut(e) => {
+                self.output.push('^');
+                if !starts_with_break(e) {
+                    self.output.push(' ');
+                }
+                self.expr(e, block);
+            }
+            ExpressionKind::Break(width) => self.newline(block.indent(*width)),
+            ExpressionKind::Comment(s) => write!(self.output, "//{s}").unwrap(),
+            ExpressionKind::Nt(_)
+            | ExpressionKind::Terminal(_)
+            | ExpressionKind::Prose(_)
+            | ExpressionKind::Charset(_)
+            | ExpressionKind::CharacterRange(..)
+            | ExpressionKind::Unicode(_) => write!(self.output, "{}", e.kind).unwrap(),
+        }
+        if let Some(suffix) = &e.suffix {
+            write!(self.output, " _{suffix}_").unwrap();
+        }
+        if let Some(footnote) = &e.footnote {
+            write!(self.output, "[^{footnote}]").unwrap();
+        }
+    }
+}
+
+fn starts_with_break(e: &Expression) -> bool {
+    first_break(e).is_some()
+}
+
+/// Returns the width of the line break at the start of the expression.
+fn first_break(e: &Expression) -> Option<usize> {
+    match &e.kind {
+        ExpressionKind::Sequence(es) => es.first().and_then(first_break),
+        ExpressionKind::Break(width) => Some(*width),
+        _ => None,
+    }
+}
+
+/// Whether an alternative starts or ends with a line break, meaning that
+/// the alternatives are written on separate lines.
+fn has_edge_break(e: &Expression) -> bool {
+    starts_with_break(e) || ends_with_break(e)
+}
+
+fn ends_with_break(e: &Expression) -> bool {
+    match &e.kind {
+        ExpressionKind::Sequence(es) => es.last().is_some_and(ends_with_break),
+        ExpressionKind::Cut(e) => ends_with_break(e),
+        kind => kind.is_break(),
+    }
+}
+
+/// Returns the elements of an alternative without the line breaks before
+/// and after it.
+fn trim_breaks(e: &Expression) -> &[Expression] {
+    let mut es = match &e.kind {
+        ExpressionKind::Sequence(es) => &es[..],
+        _ => std::slice::from_ref(e),
+    };
+    while let [first, rest @ ..] = es
+        && first.is_break()
+    {
+        es = rest;
+    }
+    while let [rest @ .., last] = es
+        && last.is_break()
+    {
+        es = rest;
+    }
+    es
+}
+
+/// The column in the source where the first line of the elements starts.
+fn src_indent(es: &[Expression]) -> usize {
+    es.iter()
+        .find(|e| !matches!(e.kind, ExpressionKind::Comment(_)))
+        .or(es.first())
+        .map(|e| e.span.start.column.saturating_sub(1))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_markdown;
+    use crate::Grammar;
+    use std::path::Path;
+
+    fn format(input: &str) -> String {
+        let g = Grammar::grammar_from_str(input, "test").unwrap();
+        g.name_order
+            .iter()
+            .map(|name| &g.productions[name])
+            .filter(|p| !p.is_instance())
+            .map(|p| p.format())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    #[test]
+    fn spacing() {
+        assert_eq!(
+            format("A ->  B   ( C|D )*  `x`[^note]  C _suffix_   E{n:1..=2}"),
+            "A -> B (C | D)* `x`[^note] C _suffix_ E{n:1..=2}"
+        );
+        assert_eq!(
+            format("// Comment.\n@root A -> [ `a`-`z`  `_` ] ~[`x`] !B ^ C"),
+            "// Comment.\n@root A -> [`a`-`z` `_`] ~[`x`] !B ^ C"
+        );
+    }
+
+    #[test]
+    fn alternatives() {
+        assert_eq!(format("A -> B | C"), "A -> B | C");
+        assert_eq!(
+            format("A -> B\n   | C // comment\n   | D"),
+            "A ->\n      B\n    | C // comment\n    | D"
+        );
+        let long = format!("A -> {} | C", "B ".repeat(50));
+        assert_eq!(
+            format(&long),
+            format!("A ->\n      {}\n    | C", "B ".repeat(50).trim_end())
+        );
+    }
+
+    #[test]
+    fn breaks() {
+        assert_eq!(
+            format("A ->\n  `{`\n       B\n  `}` ^\n      C"),
+            "A ->\n    `{`\n        B\n    `}` ^\n        C"
+        );
+        assert_eq!(format("A ->\n      B"), "A ->\n    B");
+        assert_eq!(
+            format("A ->\n    B (\n          C\n        | D\n             E\n    )"),
+            "A ->\n    B (\n        C\n      | D\n            E\n    )"
+        );
+        assert_eq!(
+            format("A ->\n     B\n   | C `{`\n         D\n     `}`"),
+            "A ->\n      B\n    | C `{`\n          D\n      `}`"
+        );
+    }
+
+    #[test]
+    fn editions() {
+        assert_eq!(
+            format("@edition(2021..)   @root A -> @edition(2018..)  B C | D"),
+            "@root @edition(2021..) A -> @edition(2018..) B C | D"
+        );
+        assert_eq!(
+            format("A ->\n   @edition(..=2018) `x`\n  | @edition(2021..)   `y` // note"),
+            "A ->\n      @edition(..=2018) `x`\n    | @edition(2021..) `y` // note"
+        );
+    }
+
+    #[test]
+    fn parameters() {
+        assert_eq!(
+            format("A -> L<B,C<D>>\n\nL<T,U> -> T (`,` U)*\n\nC<T> -> T"),
+            "A -> L<B, C<D>>\n\nL<T, U> -> T (`,` U)*\n\nC<T> -> T"
+        );
+    }
+
+    #[test]
+    fn markdown() {
+        let input = "Text.\n\n```grammar,test\nA ->  B\n\n\nC -> D\n```\n\nMore text.\n";
+        let output = format_markdown(input, Path::new("test.md")).unwrap();
+        assert_eq!(
+            output,
+            "Text.\n\n```grammar,test\nA -> B\n\nC -> D\n```\n\nMore text.\n"
+        );
+        assert_eq!(
+            format_markdown(&output, Path::new("test.md")).unwrap(),
+            output
+        );
+        assert!(format_markdown("```grammar,test\nA -> (\n```\n", Path::new("test.md")).is_err());
+    }
+}
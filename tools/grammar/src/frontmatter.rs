@@ -3,16 +3,13 @@
 //!
 //! This should be removed once FRONTMATTER is added to the Reference.
 
-use crate::{Grammar, parser};
+use crate::{Grammar, GrammarLoader, book_src_dir};
 use diagnostics::Diagnostics;
-use std::path::Path;
 
 pub fn load_grammar_with_frontmatter(diag: &mut Diagnostics) -> Grammar {
-    let mut grammar = super::load_grammar(diag);
-
-    parser::parse_grammar(FRONTMATTER, &mut grammar, "lexer", Path::new("")).unwrap();
-
-    grammar
+    GrammarLoader::new(book_src_dir())
+        .snippet("", "lexer", FRONTMATTER)
+        .load(diag)
 }
 
 static FRONTMATTER: &str = "⊥ -> CHAR* CHAR
@@ -0,0 +1,187 @@
+//! Stable identifiers for expressions.
+//!
+//! An [`ExpressionId`] is derived from the name of the production and the
+//! position of the expression within it, so it stays the same across builds
+//! and when other productions are edited. The expression of a production
+//! is identified by the name alone, such as `Expression`, and each
+//! sub-expression adds a step for how it is reached from its parent, such
+//! as `Expression:alt[2].seq[1]`:
+//!
+//! - `alt[i]`, `seq[i]`, and `set[i]` are the elements of an alternation,
+//!   sequence, and character set.
+//! - `break[i]` and `comment[i]` are the line breaks and comments of a
+//!   sequence. These are counted separately from the other elements, so
+//!   that reformatting a production doesn't change the other IDs.
+//! - `group`, `opt`, `not`, `rep`, `neg`, and `cut` are the operand of a
+//!   group, `?`, `!`, a repetition, `~`, and `^`.
+//!
+//! The comments before a production name are `Name:comment[i]` and
+//! `Name:break[i]`.
+
+use crate::{Expression, ExpressionKind, Grammar, Production};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+/// The stable identifier of an expression, such as
+/// `Expression:alt[2].seq[1]`.
+///
+/// This is empty for expressions that were not loaded from a grammar.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ExpressionId(String);
+
+impl ExpressionId {
+    /// Returns the ID of the expression of the given production.
+    pub fn production_root(name: &str) -> ExpressionId {
+        ExpressionId(name.to_string())
+    }
+
+    /// The name of the production that contains the expression.
+    pub fn production(&self) -> &str {
+        self.0.split_once(':').map_or(&self.0, |(name, _)| name)
+    }
+
+    /// The steps from the production's expression to this one, such as
+    /// `alt[2].seq[1]`, or an empty string for the production's expression.
+    pub fn path(&self) -> &str {
+        self.0.split_once(':').map_or("", |(_, path)| path)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the ID of a child reached with the given step.
+    fn child(&self, step: &str) -> ExpressionId {
+        let sep = if self.0.contains(':') { '.' } else { ':' };
+        ExpressionId(format!("{}{sep}{step}", self.0))
+    }
+}
+
+impl Display for ExpressionId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for ExpressionId {
+    fn from(s: &str) -> ExpressionId {
+        ExpressionId(s.to_string())
+    }
+}
+
+impl Production {
+    /// Sets the ID of every expression in the production based on its
+    /// position.
+    pub(crate) fn assign_ids(&mut self) {
+        let root = ExpressionId::production_root(&self.name);
+        assign_elements(&root, "", &mut self.comments);
+        assign(&mut self.expression, root);
+    }
+}
+
+fn assign(e: &mut Expression, id: ExpressionId) {
+    match &mut e.kind {
+        ExpressionKind::Grouped(inner) => assign(inner, id.child("group")),
+        ExpressionKind::Optional(inner) => assign(inner, id.child("opt")),
+        ExpressionKind::NegativeLookahead(inner) => assign(inner, id.child("not")),
+        ExpressionKind::Repeat(inner)
+        | ExpressionKind::RepeatPlus(inner)
+        | ExpressionKind::RepeatRange { expr: inner, .. }
+        | ExpressionKind::RepeatRangeNamed(inner, _) => assign(inner, id.child("rep")),
+        ExpressionKind::NegExpression(inner) => assign(inner, id.child("neg")),
+        ExpressionKind::Cut(inner) => assign(inner, id.child("cut")),
+        ExpressionKind::Alt(es) => assign_elements(&id, "alt", es),
+        ExpressionKind::Sequence(es) => assign_elements(&id, "seq", es),
+        ExpressionKind::Charset(es) => assign_elements(&id, "set", es),
+        ExpressionKind::Nt(_)
+        | ExpressionKind::Terminal(_)
+        | ExpressionKind::Prose(_)
+        | ExpressionKind::Break(_)
+        | ExpressionKind::Comment(_)
+        | ExpressionKind::Unicode(_)
+        | ExpressionKind::CharacterRange(..) => {}
+    }
+    e.id = id;
+}
+
+/// Assigns the IDs of a list of elements, counting line breaks and comments
+/// separately from the others.
+fn assign_elements(parent: &ExpressionId, step: &str, es: &mut [Expression]) {
+    let (mut breaks, mut comments, mut others) = (0, 0, 0);
+    for e in es {
+        let (step, counter) = match e.kind {
+            ExpressionKind::Break(_) => ("break", &mut breaks),
+            ExpressionKind::Comment(_) => ("comment", &mut comments),
+            _ => (step, &mut others),
+        };
+        assign(e, parent.child(&format!("{step}[{counter}]")));
+        *counter += 1;
+    }
+}
+
+impl Grammar {
+    /// Returns the expression with the given ID.
+    pub fn expression(&self, id: &ExpressionId) -> Option<&Expression> {
+        let prod = self.productions.get(id.production())?;
+        let mut found = None;
+        for e in prod.comments.iter().chain([&prod.expression]) {
+            e.visit(&mut |e| {
+                if e.id == *id {
+                    found = Some(e);
+                }
+            });
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExpressionId;
+    use crate::Grammar;
+
+    #[test]
+    fn ids() {
+        let g = Grammar::grammar_from_str(
+            "// Comment.\nA ->\n      B (`x` | C)?\n    | // note\n      ~[`a` `b`]*",
+            "test",
+        )
+        .unwrap();
+        let mut ids = Vec::new();
+        let a = &g.productions["A"];
+        for e in a.comments.iter().chain([&a.expression]) {
+            e.visit(&mut |e| ids.push(e.id.to_string()));
+        }
+        assert_eq!(
+            ids,
+            [
+                "A:comment[0]",
+                "A:break[0]",
+                "A",
+                "A:alt[0]",
+                "A:alt[0].break[0]",
+                "A:alt[0].seq[0]",
+                "A:alt[0].seq[1]",
+                "A:alt[0].seq[1].opt",
+                "A:alt[0].seq[1].opt.group",
+                "A:alt[0].seq[1].opt.group.alt[0]",
+                "A:alt[0].seq[1].opt.group.alt[1]",
+                "A:alt[0].break[1]",
+                "A:alt[1]",
+                "A:alt[1].comment[0]",
+                "A:alt[1].break[0]",
+                "A:alt[1].seq[0]",
+                "A:alt[1].seq[0].rep",
+                "A:alt[1].seq[0].rep.neg",
+                "A:alt[1].seq[0].rep.neg.set[0]",
+                "A:alt[1].seq[0].rep.neg.set[1]",
+            ]
+        );
+        let id = ExpressionId::from("A:alt[0].seq[1]");
+        assert_eq!(id.production(), "A");
+        assert_eq!(id.path(), "alt[0].seq[1]");
+        assert_eq!(g.expression(&id).unwrap().to_string(), "(`x` | C)?");
+        assert!(g.expression(&"A:alt[5]".into()).is_none());
+    }
+}
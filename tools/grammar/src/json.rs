@@ -0,0 +1,193 @@
+//! Conversion of the grammar to and from JSON.
+//!
+//! The JSON format is intended for tools outside of this workspace that
+//! want to consume the Reference grammar. It is a direct mapping of the
+//! [`Production`] and [`Expression`] types:
+//!
+//! ```json
+//! {
+//!   "version": 2,
+//!   "productions": [
+//!     {
+//!       "name": "LF",
+//!       "comments": [],
+//!       "category": "lexer",
+//!       "expression": {
+//!         "kind": { "Unicode": ["\n", "000A"] },
+//!         "suffix": null,
+//!         "footnote": null,
+//!         "id": "LF",
+//!         "span": { "path": "whitespace.md", ... }
+//!       },
+//!       "path": "whitespace.md",
+//!       "is_root": false,
+//!       "span": { ... }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Productions are listed in the order they were discovered
+//! ([`Grammar::name_order`]). The instances of productions with parameters
+//! are not included, and are expanded again by [`Grammar::from_json`].
+//! Enums such as [`ExpressionKind`] are encoded as an object with a single
+//! key that is the name of the variant. The full format is described by the
+//! JSON Schema in [`JSON_SCHEMA`].
+//!
+//! [`JSON_FORMAT_VERSION`] is incremented whenever the format changes in a
+//! way that is not backwards compatible. The `parameters` and `edition`
+//! fields are omitted when they are empty, and default to empty when they
+//! are missing, so JSON written before they were added still loads.
+
+use crate::constraint::parse_constraints;
+use crate::parameters::expand_parameters;
+#[cfg(doc)]
+use crate::{Expression, ExpressionKind};
+use crate::{Grammar, Production};
+use serde::de::Error as _;
+use serde::{Deserialize, Serialize};
+
+/// The version of the JSON format generated by [`Grammar::to_json`].
+pub const JSON_FORMAT_VERSION: u32 = 2;
+
+/// A JSON Schema describing the format generated by [`Grammar::to_json`].
+pub const JSON_SCHEMA: &str = include_str!("../grammar.schema.json");
+
+#[derive(Serialize)]
+struct GrammarJsonRef<'a> {
+    version: u32,
+    productions: Vec<&'a Production>,
+}
+
+#[derive(Deserialize)]
+struct GrammarJson {
+    version: u32,
+    productions: Vec<Production>,
+}
+
+impl Grammar {
+    /// Converts the grammar to JSON.
+    pub fn to_json(&self) -> String {
+        let json = GrammarJsonRef {
+            version: JSON_FORMAT_VERSION,
+            productions: self
+                .name_order
+                .iter()
+                .map(|name| &self.productions[name])
+                .filter(|p| !p.is_instance())
+                .collect(),
+        };
+        serde_json::to_string_pretty(&json).unwrap()
+    }
+
+    /// Loads a grammar from JSON previously generated by [`Grammar::to_json`].
+    pub fn from_json(json: &str) -> Result<Grammar, serde_json::Error> {
+        let json: GrammarJson = serde_json::from_str(json)?;
+        if json.version != JSON_FORMAT_VERSION {
+            return Err(serde_json::Error::custom(format!(
+                "unsupported grammar format version {} (expected {JSON_FORMAT_VERSION})",
+                json.version
+            )));
+        }
+        let mut grammar = Grammar::default();
+        for mut production in json.productions {
+            parse_constraints(&mut production.expression);
+            let name = production.name.clone();
+            if grammar
+                .productions
+                .insert(name.clone(), production)
+                .is_some()
+            {
+                return Err(serde_json::Error::custom(format!(
+                    "duplicate production {name} in grammar"
+                )));
+            }
+            grammar.name_order.push(name);
+        }
+        expand_parameters(&mut grammar);
+        Ok(grammar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Conjunction, Constraint, Exclusion, Expression, Grammar, JSON_SCHEMA};
+
+    const GRAMMAR: &str = "\
+@root Rule ->
+      A{n:1..=3} B{n} // comment
+    | ^ [`a`-`z` U+0000-U+007F `_` C]
+    | !`x` ~[`y`] (D | E)? F* G+ H{2..}
+
+A -> <prose> _suffix_ `a`[^note] L<C>
+
+B -> U+000A
+
+C -> `c`
+
+D -> `d`
+
+E -> `e`
+
+F -> `f`
+
+G -> `g`
+
+H -> `h` _except `x`_
+
+L<T> -> T+";
+
+    #[test]
+    fn round_trip() {
+        let grammar = Grammar::grammar_from_str(GRAMMAR, "test").unwrap();
+        let json = grammar.to_json();
+        assert!(!json.contains("\"edition\""));
+        let loaded = Grammar::from_json(&json).unwrap();
+        assert_eq!(loaded.name_order, grammar.name_order);
+        assert!(loaded.productions.contains_key("L<C>"));
+        assert_eq!(loaded.productions["L"].parameters, ["T"]);
+        assert_eq!(json.matches("\"parameters\"").count(), 1);
+        for (name, prod) in &grammar.productions {
+            let loaded_prod = &loaded.productions[name];
+            assert_eq!(
+                loaded_prod.expression.to_string(),
+                prod.expression.to_string()
+            );
+            assert_eq!(loaded_prod.span, prod.span);
+            assert_eq!(loaded_prod.is_root, prod.is_root);
+            assert_eq!(loaded_prod.expression.id, prod.expression.id);
+        }
+        let constraints = |e: &Expression| {
+            let mut constraints = Vec::new();
+            e.visit(&mut |e| constraints.extend(e.constraint.clone()));
+            constraints
+        };
+        assert_eq!(
+            constraints(&loaded.productions["H"].expression),
+            [Constraint::Except(
+                vec![Exclusion::Terminal("x".to_string())],
+                Conjunction::Or
+            )]
+        );
+        assert_eq!(loaded.to_json(), json);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let err = Grammar::from_json(r#"{"version": 999, "productions": []}"#).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("unsupported grammar format version 999"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn schema_is_json() {
+        let schema: serde_json::Value = serde_json::from_str(JSON_SCHEMA).unwrap();
+        assert_eq!(
+            schema["properties"]["version"]["const"],
+            crate::JSON_FORMAT_VERSION
+        );
+    }
+}
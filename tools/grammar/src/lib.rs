@@ -2,30 +2,75 @@
 
 use diagnostics::{Diagnostics, warn_or_err};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
-use walkdir::WalkDir;
 
+mod analysis;
+mod charset;
+mod constraint;
+mod diff;
 mod display;
+mod edition;
+mod examples;
+mod export;
+mod format;
 mod frontmatter;
+mod id;
+mod json;
+mod lint;
+mod loader;
+mod parameters;
 mod parser;
+mod query;
+mod shadow;
+mod tokens;
+mod tree_sitter;
+mod used_by;
 
+pub use analysis::{Analysis, Symbol};
+pub use charset::CharSet;
+pub use constraint::{
+    Conjunction, Constraint, DELIMITERS, Exclusion, Lookahead, UnicodeProperty, keyword_production,
+};
+pub use diff::{GrammarDiff, ProductionDiff};
+pub use edition::{Edition, EditionRange};
+pub use examples::{Example, Expect, GRAMMAR_TEST_RE, GrammarTest};
+pub use export::ExportFormat;
+pub use format::format_markdown;
 pub use frontmatter::load_grammar_with_frontmatter;
+pub use id::ExpressionId;
+pub use json::{JSON_FORMAT_VERSION, JSON_SCHEMA};
+pub use lint::{LINTS, LOAD_CHECKS, Lint, lint_allow};
+pub use loader::GrammarLoader;
+pub use parameters::split_arguments;
+pub use parser::{ParseError, ParseErrorKind};
+pub use query::{Query, QueryMatch, QueryResults};
+pub use tokens::{Keyword, KeywordClass, TokenTables};
+pub use tree_sitter::{TreeSitterGrammar, TreeSitterIssue};
+pub use used_by::{Use, UsedBy};
 
 #[derive(Debug, Default)]
 pub struct Grammar {
     pub productions: HashMap<String, Production>,
     /// The order that the production names were discovered.
     pub name_order: Vec<String>,
-    /// Counter for generating unique expression IDs.
-    pub next_id: u32,
+    /// The examples from `grammar-test` blocks, in the order they were
+    /// loaded.
+    pub tests: Vec<GrammarTest>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Production {
     pub name: String,
+    /// The names of the parameters, such as `T` in `CommaList<T>`.
+    ///
+    /// This is empty for productions without parameters.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<String>,
     /// Comments and breaks that precede the production name.
     pub comments: Vec<Expression>,
     /// Category is from the markdown lang string, and defines how it is
@@ -36,20 +81,82 @@ pub struct Production {
     /// `src` directory.
     pub path: PathBuf,
     pub is_root: bool,
+    /// The editions the production applies to, from an `@edition(..)`
+    /// annotation before the name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edition: Option<EditionRange>,
+    /// Location of the production in the markdown source, from the
+    /// production name (or its annotations) to the end of the expression.
+    pub span: Span,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Expression {
     pub kind: ExpressionKind,
     /// Suffix is the `_foo_` part that is shown as a subscript.
+    ///
+    /// Use [`Expression::set_suffix`] to change it, so that the
+    /// [`Expression::constraint`] stays in sync.
     pub suffix: Option<String>,
+    /// The constraint parsed from the suffix.
+    ///
+    /// This is `None` if there is no suffix, or if it isn't one of the
+    /// recognized forms. Grammars loaded with [`GrammarLoader`] are checked
+    /// to only have recognized forms.
+    #[serde(skip)]
+    pub constraint: Option<Constraint>,
     /// A footnote is a markdown footnote link.
     pub footnote: Option<String>,
-    /// Unique ID of the expression.
-    pub id: u32,
+    /// The editions this alternative applies to, from an `@edition(..)`
+    /// annotation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edition: Option<EditionRange>,
+    /// Stable ID of the expression, derived from its position in the
+    /// production.
+    pub id: ExpressionId,
+    /// Location of the expression in the markdown source.
+    ///
+    /// This is empty for synthetic expressions that were not parsed from
+    /// the book.
+    pub span: Span,
 }
 
-#[derive(Clone, Debug)]
+/// A region of a markdown file that a grammar element was parsed from.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    /// The path to the markdown file, relative to the book's `src`
+    /// directory.
+    pub path: PathBuf,
+    /// Range in bytes of the markdown file.
+    pub range: Range<usize>,
+    /// Where the span starts.
+    pub start: LineColumn,
+    /// Where the span ends (exclusive).
+    pub end: LineColumn,
+}
+
+/// A 1-based line and column position in a markdown file.
+///
+/// The column is counted in characters, not bytes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LineColumn {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.path.display(),
+            self.start.line,
+            self.start.column
+        )
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ExpressionKind {
     /// `( A B C )`
     Grouped(Box<Expression>),
@@ -106,7 +213,7 @@ pub enum ExpressionKind {
     Unicode((char, String)),
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RangeLimit {
     /// `..`
     HalfOpen,
@@ -124,7 +231,7 @@ impl Display for RangeLimit {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Character {
     Char(char),
     /// `U+0060`
@@ -152,37 +259,34 @@ impl Display for Character {
 }
 
 impl Grammar {
-    pub fn grammar_from_str(input: &str, category: &str) -> Result<Grammar, parser::Error> {
+    pub fn grammar_from_str(input: &str, category: &str) -> Result<Grammar, Vec<ParseError>> {
         let mut grammar = Grammar::default();
-        parser::parse_grammar(input, &mut grammar, category, Path::new(""))?;
+        parser::parse_grammar(input, 0..input.len(), &mut grammar, category, Path::new(""))?;
+        parameters::expand_parameters(&mut grammar);
         Ok(grammar)
     }
-
-    /// Generates a new unique expression ID.
-    pub fn next_id(&mut self) -> u32 {
-        let id = self.next_id;
-        self.next_id += 1;
-        id
-    }
-
-    fn visit_nt(&self, callback: &mut dyn FnMut(&str)) {
-        for p in self.productions.values() {
-            p.expression.visit_nt(callback);
-        }
-    }
 }
 
 impl Expression {
-    pub fn new_kind(kind: ExpressionKind, id: u32) -> Self {
+    /// Returns an expression of the given kind.
+    ///
+    /// The ID is empty until the expression is part of a production that
+    /// has its IDs assigned.
+    pub fn new_kind(kind: ExpressionKind) -> Self {
         Self {
             kind,
             suffix: None,
+            constraint: None,
             footnote: None,
-            id,
+            edition: None,
+            id: ExpressionId::default(),
+            span: Span::default(),
         }
     }
 
-    fn visit_nt(&self, callback: &mut dyn FnMut(&str)) {
+    /// Calls the callback for every nonterminal in this expression, along
+    /// with the span of the nonterminal.
+    fn visit_nt(&self, callback: &mut dyn FnMut(&str, &Span)) {
         match &self.kind {
             ExpressionKind::Grouped(e)
             | ExpressionKind::Optional(e)
@@ -204,7 +308,7 @@ impl Expression {
             }
 
             ExpressionKind::Nt(nt) => {
-                callback(nt);
+                callback(nt, &self.span);
             }
             ExpressionKind::Terminal(_)
             | ExpressionKind::Prose(_)
@@ -215,6 +319,39 @@ impl Expression {
         }
     }
 
+    /// Calls the callback for this expression and every expression nested
+    /// inside it, parents before children.
+    pub fn visit<'a>(&'a self, callback: &mut dyn FnMut(&'a Expression)) {
+        callback(self);
+        match &self.kind {
+            ExpressionKind::Grouped(e)
+            | ExpressionKind::Optional(e)
+            | ExpressionKind::NegativeLookahead(e)
+            | ExpressionKind::Repeat(e)
+            | ExpressionKind::RepeatPlus(e)
+            | ExpressionKind::RepeatRange { expr: e, .. }
+            | ExpressionKind::RepeatRangeNamed(e, _)
+            | ExpressionKind::NegExpression(e)
+            | ExpressionKind::Cut(e) => {
+                e.visit(callback);
+            }
+            ExpressionKind::Alt(es)
+            | ExpressionKind::Sequence(es)
+            | ExpressionKind::Charset(es) => {
+                for e in es {
+                    e.visit(callback);
+                }
+            }
+            ExpressionKind::Nt(_)
+            | ExpressionKind::Terminal(_)
+            | ExpressionKind::Prose(_)
+            | ExpressionKind::Break(_)
+            | ExpressionKind::Comment(_)
+            | ExpressionKind::Unicode(_)
+            | ExpressionKind::CharacterRange(..) => {}
+        }
+    }
+
     pub fn is_break(&self) -> bool {
         self.kind.is_break()
     }
@@ -255,39 +392,40 @@ impl ExpressionKind {
 pub static GRAMMAR_RE: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"(?ms)^```grammar,([^\n]+)\n(.*?)^```").unwrap());
 
+/// Returns the path to the `src` directory of the Reference.
+pub fn book_src_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../src")
+}
+
 /// Loads the [`Grammar`] from the book.
+///
+/// See [`GrammarLoader`] for loading from some other location.
 pub fn load_grammar(diag: &mut Diagnostics) -> Grammar {
-    let mut grammar = Grammar::default();
-    let base = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../src");
-    for entry in WalkDir::new(&base) {
-        let entry = entry.unwrap();
-        let path = entry.path();
-        if path.extension().and_then(|s| s.to_str()) != Some("md") {
-            continue;
-        }
-        let content = std::fs::read_to_string(path).unwrap();
-        let relative_path = pathdiff::diff_paths(path, &base).expect("one path must be absolute");
-        for cap in GRAMMAR_RE.captures_iter(&content) {
-            let category = &cap[1];
-            let input = &cap[2];
-            if let Err(e) = parser::parse_grammar(input, &mut grammar, category, &relative_path) {
-                warn_or_err!(diag, "failed to parse grammar in {path:?}: {e}");
-            }
-        }
-    }
-
-    check_undefined_nt(&grammar, diag);
-    check_unexpected_roots(&grammar, diag);
-    grammar
+    GrammarLoader::new(book_src_dir()).load(diag)
 }
 
 /// Checks for nonterminals that are used but not defined.
+///
+/// This includes the names in the arguments of nonterminals, and skips the
+/// parameters of the production they are used in.
 fn check_undefined_nt(grammar: &Grammar, diag: &mut Diagnostics) {
-    grammar.visit_nt(&mut |nt| {
-        if !grammar.productions.contains_key(nt) {
-            warn_or_err!(diag, "non-terminal `{nt}` is used but not defined");
+    for prod in grammar.productions.values() {
+        if prod.is_instance() {
+            continue;
         }
-    });
+        prod.expression.visit_nt(&mut |nt, span| {
+            for name in parameters::names_in(nt) {
+                if !grammar.productions.contains_key(name)
+                    && !prod.parameters.iter().any(|p| p == name)
+                {
+                    warn_or_err!(
+                        diag,
+                        "{span}: non-terminal `{name}` is used but not defined"
+                    );
+                }
+            }
+        });
+    }
 }
 
 /// This checks that all the grammar roots are what we expect.
@@ -298,16 +436,17 @@ fn check_unexpected_roots(grammar: &Grammar, diag: &mut Diagnostics) {
     // `set` starts with every production name.
     let mut set: HashSet<_> = grammar.name_order.iter().map(|s| s.as_str()).collect();
     fn remove(set: &mut HashSet<&str>, grammar: &Grammar, prod: &Production, root_name: &str) {
-        prod.expression.visit_nt(&mut |nt| {
-            // Leave the root name in the set if we find it recursively.
-            if nt == root_name {
-                return;
-            }
-            if !set.remove(nt) {
-                return;
-            }
-            if let Some(nt_prod) = grammar.productions.get(nt) {
-                remove(set, grammar, nt_prod, root_name);
+        prod.expression.visit_nt(&mut |nt, _| {
+            // A nonterminal with arguments uses the production with
+            // parameters and the arguments, as well as the instance.
+            for name in parameters::names_in(nt).into_iter().chain([nt]) {
+                // Leave the root name in the set if we find it recursively.
+                if name == root_name || !set.remove(name) {
+                    continue;
+                }
+                if let Some(nt_prod) = grammar.productions.get(name) {
+                    remove(set, grammar, nt_prod, root_name);
+                }
             }
         });
     }
@@ -320,6 +459,8 @@ fn check_unexpected_roots(grammar: &Grammar, diag: &mut Diagnostics) {
         .for_each(|root| {
             remove(&mut set, grammar, root, &root.name);
         });
+    // Instances are only reached from the productions that use them.
+    set.retain(|name| !name.contains('<'));
     let expected: HashSet<_> = grammar
         .productions
         .values()
@@ -327,8 +468,17 @@ fn check_unexpected_roots(grammar: &Grammar, diag: &mut Diagnostics) {
         .map(|p| p.name.as_str())
         .collect();
     if set != expected {
-        let new: Vec<_> = set.difference(&expected).collect();
-        let removed: Vec<_> = expected.difference(&set).collect();
+        // Include the location of each production to make them easier to find.
+        let located = |names: HashSet<&&str>| -> Vec<String> {
+            let mut names: Vec<_> = names
+                .into_iter()
+                .map(|name| format!("{name} ({})", grammar.productions[*name].span))
+                .collect();
+            names.sort();
+            names
+        };
+        let new = located(set.difference(&expected).collect());
+        let removed = located(expected.difference(&set).collect());
         if !new.is_empty() {
             warn_or_err!(
                 diag,
@@ -0,0 +1,385 @@
+//! Style checks for the grammar.
+//!
+//! Unlike the checks done when the grammar is loaded, these don't affect
+//! what the grammar means. They catch things that would otherwise need to be
+//! noticed in review, such as a production name that doesn't follow the
+//! naming convention.
+//!
+//! Each lint has a name, and can be allowed for a single production with a
+//! `// lint-allow(name)` comment before the production or inside its
+//! expression. Several lints can be listed, separated by commas. The checks
+//! in [`LOAD_CHECKS`] that are done when the grammar is loaded can be
+//! allowed the same way.
+
+use crate::parameters::names_in;
+use crate::{Expression, ExpressionKind, Grammar, Production, Span};
+use diagnostics::{Diagnostics, warn_or_err};
+use std::collections::HashMap;
+
+/// A named style check.
+pub struct Lint {
+    /// The name used to allow the lint.
+    pub name: &'static str,
+    /// A short description of what the lint checks.
+    pub description: &'static str,
+    check: fn(&LintCx<'_>, &Production, &mut Vec<(Span, String)>),
+}
+
+/// Every lint, in the order they are run.
+pub const LINTS: &[Lint] = &[
+    Lint {
+        name: "lexer-case",
+        description: "lexer production not named in SCREAMING_CASE",
+        check: lexer_case,
+    },
+    Lint {
+        name: "syntax-case",
+        description: "production other than a lexer production not named in PascalCase",
+        check: syntax_case,
+    },
+    Lint {
+        name: "single-use",
+        description: "production that is only a terminal or nonterminal, and is only used once",
+        check: single_use,
+    },
+    Lint {
+        name: "redundant-group",
+        description: "parentheses around a single element",
+        check: redundant_group,
+    },
+    Lint {
+        name: "duplicate-alternative",
+        description: "alternative listed more than once",
+        check: duplicate_alternative,
+    },
+    Lint {
+        name: "overlapping-ranges",
+        description: "overlapping elements in a character set",
+        check: overlapping_ranges,
+    },
+];
+
+/// Checks done when the grammar is loaded that can be allowed with a
+/// `// lint-allow(name)` comment, along with a short description of each.
+pub const LOAD_CHECKS: &[(&str, &str)] = &[
+    (
+        "left-recursion",
+        "production that can reach itself without consuming any input",
+    ),
+    (
+        "shadowed-alternative",
+        "alternative that is never used because an earlier alternative matches first",
+    ),
+];
+
+/// Information shared by the lints.
+struct LintCx<'a> {
+    grammar: &'a Grammar,
+    /// The productions that use each production, once for each use.
+    uses: HashMap<&'a str, Vec<&'a Production>>,
+}
+
+impl Grammar {
+    /// Runs every lint on the grammar, and reports the problems to `diag`.
+    pub fn lint(&self, diag: &mut Diagnostics) {
+        let mut uses: HashMap<&str, Vec<&Production>> = HashMap::new();
+        // Instances are checked through the productions with parameters
+        // they were expanded from.
+        let productions: Vec<_> = self
+            .name_order
+            .iter()
+            .map(|name| &self.productions[name])
+            .filter(|prod| !prod.is_instance())
+            .collect();
+        for &prod in &productions {
+            prod.expression.visit(&mut |e| {
+                if let ExpressionKind::Nt(nt) = &e.kind {
+                    for used in names_in(nt) {
+                        if used != prod.name {
+                            uses.entry(used).or_default().push(prod);
+                        }
+                    }
+                }
+            });
+        }
+        let cx = LintCx {
+            grammar: self,
+            uses,
+        };
+        for prod in productions {
+            let name = &prod.name;
+            let allowed = allowed_lints(prod);
+            for (span, allow) in &allowed {
+                if !LINTS.iter().any(|lint| lint.name == *allow)
+                    && !LOAD_CHECKS.iter().any(|(check, _)| check == allow)
+                {
+                    warn_or_err!(diag, "{span}: in `{name}`, unknown lint `{allow}`");
+                }
+            }
+            for lint in LINTS {
+                if allowed.iter().any(|(_, allow)| *allow == lint.name) {
+                    continue;
+                }
+                let mut problems = Vec::new();
+                (lint.check)(&cx, prod, &mut problems);
+                for (span, msg) in problems {
+                    warn_or_err!(diag, "{span}: {}: in `{name}`, {msg}", lint.name);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the lint names in a `lint-allow(...)` comment, given the text
+/// after the `//`.
+pub fn lint_allow(comment: &str) -> Option<Vec<&str>> {
+    let names = comment
+        .trim()
+        .strip_prefix("lint-allow(")?
+        .strip_suffix(')')?;
+    Some(names.split(',').map(str::trim).collect())
+}
+
+/// Whether the lint or load check with the given name is allowed in the
+/// production.
+pub(crate) fn is_allowed(prod: &Production, name: &str) -> bool {
+    allowed_lints(prod).iter().any(|(_, allow)| *allow == name)
+}
+
+/// Returns the lints allowed in a production, along with the location of
+/// the comment that allows each one.
+fn allowed_lints(prod: &Production) -> Vec<(Span, &str)> {
+    let mut es: Vec<&Expression> = prod.comments.iter().collect();
+    prod.expression.visit(&mut |e| es.push(e));
+    let mut allowed = Vec::new();
+    for e in es {
+        if let ExpressionKind::Comment(text) = &e.kind
+            && let Some(names) = lint_allow(text)
+        {
+            allowed.extend(names.into_iter().map(|name| (e.span.clone(), name)));
+        }
+    }
+    allowed
+}
+
+fn lexer_case(_cx: &LintCx<'_>, prod: &Production, problems: &mut Vec<(Span, String)>) {
+    let name = &prod.name;
+    if prod.category == "lexer"
+        && !(name.starts_with(|ch: char| ch.is_ascii_uppercase())
+            && name
+                .chars()
+                .all(|ch| ch.is_ascii_uppercase() || ch.is_ascii_digit() || ch == '_'))
+    {
+        problems.push((
+            prod.span.clone(),
+            "lexer productions should be named in SCREAMING_CASE".to_string(),
+        ));
+    }
+}
+
+fn syntax_case(_cx: &LintCx<'_>, prod: &Production, problems: &mut Vec<(Span, String)>) {
+    let name = &prod.name;
+    if prod.category != "lexer"
+        && !(name.starts_with(|ch: char| ch.is_ascii_uppercase())
+            && name.chars().all(|ch| ch.is_ascii_alphanumeric())
+            && (name.len() == 1 || name.chars().any(|ch| ch.is_ascii_lowercase())))
+    {
+        problems.push((
+            prod.span.clone(),
+            "productions should be named in PascalCase".to_string(),
+        ));
+    }
+}
+
+fn single_use(cx: &LintCx<'_>, prod: &Production, problems: &mut Vec<(Span, String)>) {
+    // A production defined in another chapter than where it is used is
+    // usually there to give a name to something described in that chapter.
+    let Some([user]) = cx.uses.get(prod.name.as_str()).map(Vec::as_slice) else {
+        return;
+    };
+    if prod.is_root || user.path != prod.path {
+        return;
+    }
+    let es = elements(&prod.expression);
+    if let [e] = es.as_slice()
+        && e.suffix.is_none()
+        && e.footnote.is_none()
+        && matches!(e.kind, ExpressionKind::Terminal(_) | ExpressionKind::Nt(_))
+    {
+        problems.push((
+            prod.span.clone(),
+            format!(
+                "production is only `{e}` and is only used once; \
+                 consider using `{e}` directly"
+            ),
+        ));
+    }
+}
+
+fn redundant_group(_cx: &LintCx<'_>, prod: &Production, problems: &mut Vec<(Span, String)>) {
+    prod.expression.visit(&mut |e| {
+        let ExpressionKind::Grouped(inner) = &e.kind else {
+            return;
+        };
+        if let [inner] = elements(inner).as_slice()
+            && inner.suffix.is_none()
+            && inner.footnote.is_none()
+            && matches!(
+                inner.kind,
+                ExpressionKind::Nt(_)
+                    | ExpressionKind::Terminal(_)
+                    | ExpressionKind::Prose(_)
+                    | ExpressionKind::Charset(_)
+                    | ExpressionKind::Unicode(_)
+                    | ExpressionKind::Grouped(_)
+            )
+        {
+            problems.push((
+                e.span.clone(),
+                format!("unnecessary parentheses around `{inner}`"),
+            ));
+        }
+    });
+}
+
+fn duplicate_alternative(_cx: &LintCx<'_>, prod: &Production, problems: &mut Vec<(Span, String)>) {
+    prod.expression.visit(&mut |e| {
+        let ExpressionKind::Alt(es) = &e.kind else {
+            return;
+        };
+        let texts: Vec<_> = es.iter().map(text).collect();
+        for (i, alt) in es.iter().enumerate() {
+            if texts[..i].contains(&texts[i]) {
+                problems.push((
+                    alt.span.clone(),
+                    format!("alternative `{}` is listed more than once", texts[i]),
+                ));
+            }
+        }
+    });
+}
+
+fn overlapping_ranges(cx: &LintCx<'_>, prod: &Production, problems: &mut Vec<(Span, String)>) {
+    prod.expression.visit(&mut |e| {
+        let ExpressionKind::Charset(es) = &e.kind else {
+            return;
+        };
+        let sets: Vec<_> = es.iter().map(|e| cx.grammar.char_set(e)).collect();
+        for (i, a) in sets.iter().enumerate() {
+            let Some(a) = a else { continue };
+            let overlap = sets[..i]
+                .iter()
+                .position(|b| b.as_ref().is_some_and(|b| a.intersects(b)));
+            if let Some(j) = overlap {
+                problems.push((
+                    es[i].span.clone(),
+                    format!(
+                        "`{}` overlaps with `{}` in the same character set",
+                        es[i], es[j]
+                    ),
+                ));
+            }
+        }
+    });
+}
+
+/// The elements of a sequence, without line breaks and comments.
+fn elements(e: &Expression) -> Vec<&Expression> {
+    match &e.kind {
+        ExpressionKind::Sequence(es) => es
+            .iter()
+            .filter(|e| {
+                !matches!(
+                    e.kind,
+                    ExpressionKind::Break(_) | ExpressionKind::Comment(_)
+                )
+            })
+            .collect(),
+        _ => vec![e],
+    }
+}
+
+/// The text of an expression, on a single line.
+fn text(e: &Expression) -> String {
+    e.to_string()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LINTS;
+    use crate::Grammar;
+
+    /// Returns the name of each lint that fails, in order.
+    fn lint(input: &str, category: &str) -> Vec<&'static str> {
+        let grammar = Grammar::grammar_from_str(input, category).unwrap();
+        let mut failed = Vec::new();
+        let cx = super::LintCx {
+            grammar: &grammar,
+            uses: Default::default(),
+        };
+        for name in &grammar.name_order {
+            let prod = &grammar.productions[name];
+            let allowed = super::allowed_lints(prod);
+            for lint in LINTS {
+                let mut problems = Vec::new();
+                (lint.check)(&cx, prod, &mut problems);
+                if !problems.is_empty() && !allowed.iter().any(|(_, a)| *a == lint.name) {
+                    failed.push(lint.name);
+                }
+            }
+        }
+        failed
+    }
+
+    #[test]
+    fn naming() {
+        assert_eq!(
+            lint("IDENT -> `a`\n\nIdent -> `a`", "lexer"),
+            ["lexer-case"]
+        );
+        assert_eq!(
+            lint("Item -> `a`\n\nITEM -> `a`\n\nMy_Item -> `a`", "items"),
+            ["syntax-case", "syntax-case"]
+        );
+        assert_eq!(
+            lint("// lint-allow(lexer-case)\nXID_Start -> `a`", "lexer"),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(
+            lint("A -> (B) (B C) (`a`)? (B | C)", "items"),
+            ["redundant-group"]
+        );
+        assert_eq!(
+            lint("A -> B | C | B // lint-allow(redundant-group)", "items"),
+            ["duplicate-alternative"]
+        );
+        assert_eq!(
+            lint("A -> [`a`-`f` `c` `x`-`z`]", "lexer"),
+            ["overlapping-ranges"]
+        );
+        assert_eq!(
+            lint("A -> [`a`-`f` `g` `x`-`z`]", "lexer"),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn single_use() {
+        let grammar = Grammar::grammar_from_str(
+            "@root A -> B C C\n\nB -> D\n\nC -> `c`\n\nD -> `d` `e`",
+            "items",
+        )
+        .unwrap();
+        let mut diag = diagnostics::Diagnostics::new();
+        grammar.lint(&mut diag);
+        // Only `B` is used once and only a nonterminal.
+        assert_eq!(diag.count, 1);
+    }
+}
@@ -0,0 +1,326 @@
+//! Support for loading the grammar from a directory of markdown files.
+
+use crate::analysis::check_well_formed;
+use crate::charset::check_char_sets;
+use crate::constraint::check_constraints;
+use crate::examples::{check_grammar_tests, parse_grammar_tests};
+use crate::parameters::{check_parameters, expand_parameters};
+use crate::shadow::check_shadowed_alternatives;
+use crate::{GRAMMAR_RE, Grammar, check_undefined_nt, check_unexpected_roots, parser};
+use diagnostics::{Diagnostics, warn_or_err};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use walkdir::WalkDir;
+
+/// Builder for loading a [`Grammar`] from the `grammar` code blocks of a
+/// directory of markdown files.
+///
+/// ```no_run
+/// # use diagnostics::Diagnostics;
+/// # use grammar::GrammarLoader;
+/// let mut diag = Diagnostics::new();
+/// let grammar = GrammarLoader::new("path/to/book/src")
+///     .filter(|path| path.starts_with("items"))
+///     .snippet("extra.md", "items", "@root Extra -> `extra`")
+///     .load(&mut diag);
+/// ```
+pub struct GrammarLoader {
+    root: PathBuf,
+    filter: Option<PathFilter>,
+    snippets: Vec<Snippet>,
+}
+
+/// Callback to decide which markdown files to load.
+type PathFilter = Box<dyn Fn(&Path) -> bool>;
+
+/// A grammar that is not in a markdown file.
+struct Snippet {
+    path: PathBuf,
+    category: String,
+    source: String,
+}
+
+impl GrammarLoader {
+    /// Creates a loader that reads every markdown file under the given
+    /// directory.
+    ///
+    /// The paths of the productions will be relative to this directory.
+    pub fn new(root: impl Into<PathBuf>) -> GrammarLoader {
+        GrammarLoader {
+            root: root.into(),
+            filter: None,
+            snippets: Vec::new(),
+        }
+    }
+
+    /// Only load markdown files for which the filter returns `true`.
+    ///
+    /// The path passed to the filter is relative to the root directory.
+    pub fn filter(mut self, filter: impl Fn(&Path) -> bool + 'static) -> GrammarLoader {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Adds grammar productions from a string, as if it were the contents
+    /// of a `grammar` code block with the given category.
+    ///
+    /// `path` is recorded as the path of the productions, and should be
+    /// relative to the root directory.
+    pub fn snippet(
+        mut self,
+        path: impl Into<PathBuf>,
+        category: &str,
+        source: impl Into<String>,
+    ) -> GrammarLoader {
+        self.snippets.push(Snippet {
+            path: path.into(),
+            category: category.to_string(),
+            source: source.into(),
+        });
+        self
+    }
+
+    /// Loads the grammar, and checks it for errors.
+    ///
+    /// Any problems are reported to `diag`.
+    pub fn load(&self, diag: &mut Diagnostics) -> Grammar {
+        let mut grammar = Grammar::default();
+        for (path, relative_path) in self.markdown_files(diag) {
+            let content = match std::fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    warn_or_err!(diag, "failed to read {path:?}: {e}");
+                    continue;
+                }
+            };
+            for cap in GRAMMAR_RE.captures_iter(&content) {
+                let category = &cap[1];
+                let block = cap.get(2).unwrap().range();
+                if let Err(errors) =
+                    parser::parse_grammar(&content, block, &mut grammar, category, &relative_path)
+                {
+                    for e in errors {
+                        warn_or_err!(diag, "failed to parse grammar in {path:?}: {e}");
+                    }
+                }
+            }
+            parse_grammar_tests(&content, &relative_path, &mut grammar, diag);
+        }
+
+        for snippet in &self.snippets {
+            let source = &snippet.source;
+            if let Err(errors) = parser::parse_grammar(
+                source,
+                0..source.len(),
+                &mut grammar,
+                &snippet.category,
+                &snippet.path,
+            ) {
+                for e in errors {
+                    warn_or_err!(
+                        diag,
+                        "failed to parse grammar snippet {:?}: {e}",
+                        snippet.path
+                    );
+                }
+            }
+        }
+
+        expand_parameters(&mut grammar);
+
+        check_undefined_nt(&grammar, diag);
+        check_parameters(&grammar, diag);
+        check_unexpected_roots(&grammar, diag);
+        check_constraints(&grammar, diag);
+        check_char_sets(&grammar, diag);
+        check_well_formed(&grammar, diag);
+        check_shadowed_alternatives(&grammar, diag);
+        check_grammar_tests(&grammar, diag);
+        grammar
+    }
+
+    /// Returns the markdown files to load, along with their paths relative
+    /// to the root directory.
+    ///
+    /// The files are in the order of the chapters in `SUMMARY.md`, followed
+    /// by any files not in the summary sorted by path, so that the order of
+    /// the productions doesn't depend on the filesystem.
+    fn markdown_files(&self, diag: &mut Diagnostics) -> Vec<(PathBuf, PathBuf)> {
+        let mut files = Vec::new();
+        for entry in WalkDir::new(&self.root) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn_or_err!(diag, "failed to read {:?}: {e}", self.root);
+                    continue;
+                }
+            };
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("md") {
+                continue;
+            }
+            let relative_path =
+                pathdiff::diff_paths(path, &self.root).expect("one path must be absolute");
+            if let Some(filter) = &self.filter
+                && !filter(&relative_path)
+            {
+                continue;
+            }
+            files.push((path.to_path_buf(), relative_path));
+        }
+        let chapters = summary_order(&self.root);
+        files.sort_by_cached_key(|(_, relative_path)| {
+            let index = chapters.iter().position(|c| c == relative_path);
+            (index.unwrap_or(usize::MAX), relative_path.clone())
+        });
+        files
+    }
+}
+
+/// Returns the paths of the chapters listed in the `SUMMARY.md` file in the
+/// given directory, in order.
+///
+/// Returns an empty list if there is no `SUMMARY.md`.
+fn summary_order(root: &Path) -> Vec<PathBuf> {
+    static LINK_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\]\(([^)#]+\.md)\)").unwrap());
+    let Ok(summary) = std::fs::read_to_string(root.join("SUMMARY.md")) else {
+        return Vec::new();
+    };
+    LINK_RE
+        .captures_iter(&summary)
+        .map(|cap| PathBuf::from(&cap[1]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GrammarLoader;
+    use diagnostics::Diagnostics;
+    use std::path::{Path, PathBuf};
+
+    /// Creates a temporary book directory with the given files.
+    fn make_book(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("grammar-loader-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (path, contents) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        root
+    }
+
+    const ITEMS: &str = "# Items\n\n```grammar,items\n@root Item -> Struct\n```\n";
+    const STRUCTS: &str = "# Structs\n\n```grammar,items\nStruct -> `struct`\n```\n";
+
+    #[test]
+    fn load_directory() {
+        let root = make_book(
+            "directory",
+            &[("items.md", ITEMS), ("items/structs.md", STRUCTS)],
+        );
+        let mut diag = Diagnostics::new();
+        let grammar = GrammarLoader::new(&root).load(&mut diag);
+        assert_eq!(diag.count, 0);
+        assert_eq!(grammar.productions.len(), 2);
+        let s = grammar.productions.get("Struct").unwrap();
+        assert_eq!(s.path, Path::new("items/structs.md"));
+        assert_eq!(s.span.start.line, 4);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn load_filtered_with_snippet() {
+        let root = make_book(
+            "filtered",
+            &[("items.md", ITEMS), ("items/structs.md", STRUCTS)],
+        );
+        let mut diag = Diagnostics::new();
+        let grammar = GrammarLoader::new(&root)
+            .filter(|path| path == Path::new("items.md"))
+            .snippet(
+                "extra.md",
+                "items",
+                "Struct -> `struct` Name\n\nName -> `S`",
+            )
+            .load(&mut diag);
+        assert_eq!(diag.count, 0);
+        assert_eq!(grammar.productions.len(), 3);
+        let s = grammar.productions.get("Struct").unwrap();
+        assert_eq!(s.path, Path::new("extra.md"));
+        assert_eq!(s.category, "items");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn load_in_summary_order() {
+        const EXPRS: &str = "# Expressions\n\n```grammar,expressions\n@root Expr -> `e`\n```\n";
+        const TYPES: &str = "# Types\n\n```grammar,types\n@root Type -> `t`\n```\n";
+        let files = [
+            ("a-types.md", TYPES),
+            ("expressions.md", EXPRS),
+            ("items.md", ITEMS),
+            ("items/structs.md", STRUCTS),
+        ];
+        let root = make_book("unordered", &files);
+        let mut diag = Diagnostics::new();
+        let grammar = GrammarLoader::new(&root).load(&mut diag);
+        assert_eq!(diag.count, 0);
+        assert_eq!(grammar.name_order, ["Type", "Expr", "Struct", "Item"]);
+        std::fs::remove_dir_all(root).unwrap();
+
+        let summary = "# Summary\n\n\
+            - [Items](items.md)\n    \
+            - [Structs](items/structs.md)\n\
+            - [Expressions](expressions.md)\n";
+        let root = make_book(
+            "summary",
+            &[&files[..], &[("SUMMARY.md", summary)]].concat(),
+        );
+        let mut diag = Diagnostics::new();
+        let grammar = GrammarLoader::new(&root).load(&mut diag);
+        assert_eq!(diag.count, 0);
+        assert_eq!(grammar.name_order, ["Item", "Struct", "Expr", "Type"]);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn load_grammar_tests() {
+        let tests = "```grammar-test,Struct\naccept: struct\n```\n\n\
+                     ```grammar-test,Union\nreject: union\n```\n";
+        let root = make_book(
+            "tests",
+            &[
+                ("items.md", ITEMS),
+                ("items/structs.md", &[STRUCTS, tests].concat()),
+            ],
+        );
+        let mut diag = Diagnostics::capture();
+        let grammar = GrammarLoader::new(&root).load(&mut diag);
+        // `Struct` is defined, and the block for `Union` is the only problem.
+        assert_eq!(
+            diag.messages(),
+            ["items/structs.md:11:1: grammar-test block for undefined production `Union`"]
+        );
+        assert_eq!(grammar.tests.len(), 2);
+        assert_eq!(grammar.tests[0].production, "Struct");
+        assert_eq!(grammar.tests[0].span.path, Path::new("items/structs.md"));
+        assert_eq!(grammar.tests[0].examples[0].span.start.line, 7);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn load_reports_undefined() {
+        let root = make_book("undefined", &[("items.md", ITEMS)]);
+        let mut diag = Diagnostics::capture();
+        let grammar = GrammarLoader::new(&root).load(&mut diag);
+        assert_eq!(
+            diag.messages(),
+            ["items.md:4:15: non-terminal `Struct` is used but not defined"]
+        );
+        assert!(grammar.productions.contains_key("Item"));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,340 @@
+//! Productions with parameters.
+//!
+//! A production such as `CommaList<T> -> T (`,` T)* `,`?` is a template for
+//! a family of productions. It is used with arguments such as
+//! `CommaList<Expression>`, which is a nonterminal whose name includes the
+//! arguments. When the grammar is loaded, each of these uses is expanded
+//! into an instance production with that name, where the parameters are
+//! replaced with the arguments, so that tools that interpret the grammar
+//! don't need to know about parameters.
+//!
+//! Instances are added to the end of [`Grammar::name_order`]. Tools that
+//! work with the grammar as it is written, such as the renderers, skip them
+//! with [`Production::is_instance`].
+
+use crate::{Expression, ExpressionKind, Grammar, Production, Span};
+use diagnostics::{Diagnostics, warn_or_err};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// How deeply arguments can be nested in an instance, such as
+/// `A<B<C<D>>>`.
+///
+/// This stops the expansion of productions like `A<T> -> A<B<T>>`, which
+/// would otherwise never end.
+const MAX_DEPTH: usize = 8;
+
+/// Splits a nonterminal into the name of the production and its arguments,
+/// such as `CommaList` and `["Expression"]` for `CommaList<Expression>`.
+///
+/// The arguments are empty for a nonterminal without arguments.
+pub fn split_arguments(nt: &str) -> (&str, Vec<&str>) {
+    let Some(open) = nt.find('<') else {
+        return (nt, Vec::new());
+    };
+    let inner = &nt[open + 1..nt.len() - 1];
+    let mut args = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, ch) in inner.char_indices() {
+        match ch {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(inner[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(inner[start..].trim());
+    (&nt[..open], args)
+}
+
+impl Production {
+    /// Whether this production has parameters, such as `CommaList<T>`.
+    pub fn is_generic(&self) -> bool {
+        !self.parameters.is_empty()
+    }
+
+    /// The name of the production followed by its parameters, such as
+    /// `CommaList<T>`.
+    pub fn name_with_parameters(&self) -> String {
+        if self.parameters.is_empty() {
+            return self.name.clone();
+        }
+        format!("{}<{}>", self.name, self.parameters.join(", "))
+    }
+
+    /// Whether this production was created by expanding a production with
+    /// parameters, such as `CommaList<Expression>`.
+    pub fn is_instance(&self) -> bool {
+        self.name.contains('<')
+    }
+}
+
+/// Returns a name for a nonterminal that is an identifier in notations
+/// without parameters, such as `CommaList_Expression` for
+/// `CommaList<Expression>`.
+pub(crate) fn identifier(nt: &str) -> String {
+    nt.replace(", ", "_").replace('<', "_").replace('>', "")
+}
+
+/// Returns how deeply the arguments of a nonterminal are nested.
+fn depth(nt: &str) -> usize {
+    let (_, args) = split_arguments(nt);
+    args.iter().map(|arg| depth(arg) + 1).max().unwrap_or(0)
+}
+
+/// Replaces the parameters in a nonterminal with their arguments.
+fn substitute(nt: &str, map: &HashMap<&str, &str>) -> String {
+    let (name, args) = split_arguments(nt);
+    if args.is_empty() {
+        return map.get(name).unwrap_or(&name).to_string();
+    }
+    let args: Vec<_> = args.iter().map(|arg| substitute(arg, map)).collect();
+    format!("{name}<{}>", args.join(", "))
+}
+
+fn substitute_expr(e: &mut Expression, map: &HashMap<&str, &str>) {
+    match &mut e.kind {
+        ExpressionKind::Grouped(e)
+        | ExpressionKind::Optional(e)
+        | ExpressionKind::NegativeLookahead(e)
+        | ExpressionKind::Repeat(e)
+        | ExpressionKind::RepeatPlus(e)
+        | ExpressionKind::RepeatRange { expr: e, .. }
+        | ExpressionKind::RepeatRangeNamed(e, _)
+        | ExpressionKind::NegExpression(e)
+        | ExpressionKind::Cut(e) => substitute_expr(e, map),
+        ExpressionKind::Alt(es) | ExpressionKind::Sequence(es) | ExpressionKind::Charset(es) => {
+            for e in es {
+                substitute_expr(e, map);
+            }
+        }
+        ExpressionKind::Nt(nt) => *nt = substitute(nt, map),
+        ExpressionKind::Terminal(_)
+        | ExpressionKind::Prose(_)
+        | ExpressionKind::Break(_)
+        | ExpressionKind::Comment(_)
+        | ExpressionKind::Unicode(_)
+        | ExpressionKind::CharacterRange(..) => {}
+    }
+}
+
+/// Adds the nonterminals with arguments in the expression to the queue.
+fn queue_applications(e: &Expression, queue: &mut VecDeque<String>) {
+    e.visit_nt(&mut |nt, _| {
+        if nt.contains('<') {
+            queue.push_back(nt.to_string());
+        }
+    });
+}
+
+/// Adds an instance production for every use of a production with
+/// arguments.
+///
+/// Uses that are not valid, such as those with the wrong number of
+/// arguments, are skipped. These are reported by [`check_parameters`].
+pub(crate) fn expand_parameters(grammar: &mut Grammar) {
+    let mut queue = VecDeque::new();
+    for name in &grammar.name_order {
+        let prod = &grammar.productions[name];
+        if !prod.is_generic() {
+            queue_applications(&prod.expression, &mut queue);
+        }
+    }
+    while let Some(nt) = queue.pop_front() {
+        if grammar.productions.contains_key(&nt) || depth(&nt) > MAX_DEPTH {
+            continue;
+        }
+        let (name, args) = split_arguments(&nt);
+        let Some(generic) = grammar.productions.get(name) else {
+            continue;
+        };
+        if generic.parameters.len() != args.len() {
+            continue;
+        }
+        let map: HashMap<_, _> = generic
+            .parameters
+            .iter()
+            .map(String::as_str)
+            .zip(args)
+            .collect();
+        let mut expression = generic.expression.clone();
+        substitute_expr(&mut expression, &map);
+        let mut instance = Production {
+            name: nt.clone(),
+            comments: Vec::new(),
+            category: generic.category.clone(),
+            expression,
+            path: generic.path.clone(),
+            is_root: false,
+            edition: generic.edition,
+            parameters: Vec::new(),
+            span: generic.span.clone(),
+        };
+        instance.assign_ids();
+        queue_applications(&instance.expression, &mut queue);
+        grammar.name_order.push(nt.clone());
+        grammar.productions.insert(nt, instance);
+    }
+}
+
+/// Checks the parameters of productions and the arguments they are used
+/// with.
+pub(crate) fn check_parameters(grammar: &Grammar, diag: &mut Diagnostics) {
+    let mut too_deep = HashSet::new();
+    for name in &grammar.name_order {
+        let prod = &grammar.productions[name];
+        if prod.is_instance() {
+            // Everything else was checked in the production with
+            // parameters.
+            prod.expression.visit_nt(&mut |nt, span| {
+                if depth(nt) > MAX_DEPTH
+                    && !grammar.productions.contains_key(nt)
+                    && too_deep.insert(nt.to_string())
+                {
+                    warn_or_err!(
+                        diag,
+                        "{span}: arguments of `{nt}` are nested too deeply, \
+                         in the expansion of `{name}`"
+                    );
+                }
+            });
+            continue;
+        }
+        for param in &prod.parameters {
+            if grammar.productions.contains_key(param) {
+                warn_or_err!(
+                    diag,
+                    "{}: parameter `{param}` of `{name}` has the same name as a production",
+                    prod.span
+                );
+            }
+        }
+        prod.expression.visit_nt(&mut |nt, span| {
+            check_arguments(grammar, prod, nt, span, diag);
+        });
+    }
+}
+
+/// Checks that a nonterminal is used with the arguments its production
+/// expects.
+///
+/// Undefined nonterminals are reported by `check_undefined_nt`.
+fn check_arguments(
+    grammar: &Grammar,
+    prod: &Production,
+    nt: &str,
+    span: &Span,
+    diag: &mut Diagnostics,
+) {
+    let (name, args) = split_arguments(nt);
+    let expected = if prod.parameters.iter().any(|p| p == name) {
+        0
+    } else if let Some(target) = grammar.productions.get(name) {
+        target.parameters.len()
+    } else {
+        return;
+    };
+    if args.len() != expected {
+        warn_or_err!(
+            diag,
+            "{span}: `{name}` expects {expected} argument(s), but `{nt}` has {}",
+            args.len()
+        );
+        return;
+    }
+    for arg in args {
+        check_arguments(grammar, prod, arg, span, diag);
+    }
+}
+
+/// Returns the name of the production of a nonterminal, followed by the
+/// names in its arguments.
+pub(crate) fn names_in(nt: &str) -> Vec<&str> {
+    let (name, args) = split_arguments(nt);
+    let mut names = vec![name];
+    for arg in args {
+        names.extend(names_in(arg));
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_arguments;
+    use crate::{Grammar, GrammarLoader};
+    use diagnostics::Diagnostics;
+
+    #[test]
+    fn split() {
+        assert_eq!(split_arguments("A"), ("A", vec![]));
+        assert_eq!(split_arguments("A<B>"), ("A", vec!["B"]));
+        assert_eq!(
+            split_arguments("A<B<C, D>, E>"),
+            ("A", vec!["B<C, D>", "E"])
+        );
+    }
+
+    #[test]
+    fn expand() {
+        let g = Grammar::grammar_from_str(
+            "@root List -> CommaList<Item> Pair<Item, CommaList<Item>>\n\n\
+             CommaList<T> -> T (`,` T)* `,`?\n\n\
+             Pair<A, B> -> A `=` B\n\n\
+             Item -> `i`",
+            "test",
+        )
+        .unwrap();
+        assert_eq!(
+            g.name_order,
+            [
+                "List",
+                "CommaList",
+                "Pair",
+                "Item",
+                "CommaList<Item>",
+                "Pair<Item, CommaList<Item>>",
+            ]
+        );
+        let list = &g.productions["CommaList"];
+        assert!(list.is_generic() && !list.is_instance());
+        let instance = &g.productions["CommaList<Item>"];
+        assert!(instance.is_instance() && !instance.is_generic());
+        assert_eq!(instance.expression.to_string(), "Item (`,` Item)* `,`?");
+        assert_eq!(instance.span, list.span);
+        assert_eq!(instance.expression.id.as_str(), "CommaList<Item>");
+        assert_eq!(
+            g.productions["Pair<Item, CommaList<Item>>"]
+                .expression
+                .to_string(),
+            "Item `=` CommaList<Item>"
+        );
+    }
+
+    fn check(source: &str) -> u32 {
+        let mut diag = Diagnostics::new();
+        GrammarLoader::new(env!("CARGO_MANIFEST_DIR"))
+            .filter(|_| false)
+            .snippet("test.md", "test", source)
+            .load(&mut diag);
+        diag.count
+    }
+
+    #[test]
+    fn errors() {
+        let list = |body: &str| format!("@root A -> B {body}\n\nB -> `b`\n\nL<T> -> T (`,` T)*");
+        assert_eq!(check(&list("L<B>")), 0);
+        assert_eq!(check(&list("L")), 1);
+        assert_eq!(check(&list("L<B, B>")), 1);
+        assert_eq!(check(&list("B<L<B>>")), 1);
+        assert_eq!(check(&list("L<X>")), 1);
+        assert_eq!(check(&list("L<L<B>>")), 0);
+        assert_eq!(check("@root A -> B<A>\n\nB<A> -> `b` A"), 1);
+        assert_eq!(
+            check("@root A -> `a` B<A>\n\nB<T> -> `b` T | `c` B<B<T>>"),
+            1
+        );
+    }
+}
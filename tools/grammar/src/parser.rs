@@ -1,44 +1,131 @@
 //! A parser of the ENBF-like grammar.
 
-use super::{Character, Expression, ExpressionKind, Grammar, Production, RangeLimit};
+use super::{
+    Character, EditionRange, Expression, ExpressionKind, Grammar, LineColumn, Production,
+    RangeLimit, Span,
+};
 use std::fmt;
 use std::fmt::Display;
+use std::ops::Range;
 use std::path::Path;
 
 struct Parser<'a> {
+    /// The text of the grammar block being parsed.
     input: &'a str,
     index: usize,
     grammar: &'a mut Grammar,
+    /// The path of the markdown file, used for [`Span`]s.
+    path: &'a Path,
+    /// Byte offset of `input` within the markdown file.
+    offset: usize,
+    /// Line number (1-based) in the markdown file of the start of `input`.
+    first_line: usize,
+    /// Byte offsets within `input` of the start of each line.
+    line_starts: Vec<usize>,
 }
 
-#[derive(Debug)]
-pub struct Error {
-    message: String,
+/// An error found while parsing a grammar block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// Where the error is in the markdown file, including the path.
+    pub span: Span,
+    /// The text of the line where the error is, used for display.
     line: String,
-    lineno: usize,
-    col: usize,
 }
 
-impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        let lineno = format!("{}", self.lineno);
+/// The kind of a [`ParseError`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseErrorKind {
+    /// A specific piece of syntax was expected, such as a closing `}`.
+    Expected(&'static str),
+    ExpectedProductionName,
+    ExpectedArrow,
+    /// A production has no expression after `->`.
+    ExpectedExpression,
+    /// A continuation line of a production is not indented.
+    ExpectedIndentation,
+    /// The production is already defined.
+    DuplicateProduction(String),
+    /// There is nothing after `^`, `~`, or `!`, or inside `( )`.
+    MissingOperand(&'static str),
+    UnterminatedTerminal,
+    EmptyTerminal,
+    EmptyCharset,
+    UnterminatedCharset,
+    /// Something in a `[ ]` character set that isn't allowed there.
+    BadCharsetElement(&'static str),
+    EmptyProse,
+    UnterminatedProse,
+    UnterminatedGroup,
+    /// `U+` is not followed by 4 to 6 uppercase hexadecimal digits.
+    BadUnicode,
+    /// A repetition range like `{2..1}` is invalid.
+    BadRange(String),
+    /// The range in an `@edition(..)` annotation is invalid.
+    BadEdition(String),
+    UnterminatedSuffix,
+    EmptyFootnote,
+    UnterminatedFootnote,
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::result::Result<(), fmt::Error> {
+        match self {
+            ParseErrorKind::Expected(what) => write!(f, "expected {what}"),
+            ParseErrorKind::ExpectedProductionName => write!(f, "expected production name"),
+            ParseErrorKind::ExpectedArrow => write!(f, "expected -> arrow"),
+            ParseErrorKind::ExpectedExpression => write!(f, "expected an expression"),
+            ParseErrorKind::ExpectedIndentation => write!(f, "expected indentation on next line"),
+            ParseErrorKind::DuplicateProduction(name) => {
+                write!(f, "duplicate production {name} in grammar")
+            }
+            ParseErrorKind::MissingOperand(after) => write!(f, "expected expression {after}"),
+            ParseErrorKind::UnterminatedTerminal => write!(f, "expected closing backtick"),
+            ParseErrorKind::EmptyTerminal => write!(f, "expected terminal"),
+            ParseErrorKind::EmptyCharset => {
+                write!(f, "expected at least one character in character group")
+            }
+            ParseErrorKind::UnterminatedCharset => write!(f, "expected closing ]"),
+            ParseErrorKind::BadCharsetElement(reason) => write!(f, "{reason}"),
+            ParseErrorKind::EmptyProse => write!(f, "expected prose text"),
+            ParseErrorKind::UnterminatedProse => write!(f, "expected closing `>`"),
+            ParseErrorKind::UnterminatedGroup => write!(f, "expected closing `)`"),
+            ParseErrorKind::BadUnicode => {
+                write!(f, "expected 4 to 6 uppercase hexadecimal digits after `U+`")
+            }
+            ParseErrorKind::BadRange(reason) => write!(f, "{reason}"),
+            ParseErrorKind::BadEdition(reason) => write!(f, "{reason}"),
+            ParseErrorKind::UnterminatedSuffix => {
+                write!(f, "failed to find end of _ suffixed text")
+            }
+            ParseErrorKind::EmptyFootnote => write!(f, "expected footnote id"),
+            ParseErrorKind::UnterminatedFootnote => write!(f, "expected closing `]`"),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::result::Result<(), fmt::Error> {
+        let lineno = format!("{}", self.span.start.line);
         let space = " ".repeat(lineno.len() + 1);
-        let col = " ".repeat(self.col);
+        let col = " ".repeat(self.span.start.column);
         let line = &self.line;
-        let message = &self.message;
-        write!(f, "\n{space}|\n{lineno} | {line}\n{space}|{col}^ {message}")
+        let kind = &self.kind;
+        write!(f, "\n{space}|\n{lineno} | {line}\n{space}|{col}^ {kind}")
     }
 }
 
+impl std::error::Error for ParseError {}
+
 macro_rules! bail {
-    ($parser:expr, $($arg:tt)*) => {{
-        let mut msg = String::new();
-        fmt::write(&mut msg, format_args!($($arg)*)).unwrap();
-        return Err($parser.error(msg));
+    ($parser:expr, $kind:expr) => {{
+        return Err($parser.error($kind));
     }};
 }
 
-type Result<T> = std::result::Result<T, Error>;
+/// The error is boxed since it is large, and only created on failure.
+type Result<T> = std::result::Result<T, Box<ParseError>>;
 
 /// Whether a character can start a grammar rule name.
 ///
@@ -58,36 +145,123 @@ fn is_name_continue(ch: char) -> bool {
     ch.is_alphanumeric() || ch == '_' || !ch.is_ascii()
 }
 
+/// Parses the grammar block located at the given byte range of a markdown
+/// file, and adds its productions to `grammar`.
+///
+/// `content` is the entire markdown file so that the [`Span`]s of the
+/// productions and expressions are relative to the file, not the block.
+///
+/// If a production fails to parse, parsing continues with the next
+/// production (after the next blank line), and all of the errors are
+/// returned. Productions that parsed successfully are still added.
 pub fn parse_grammar(
-    input: &str,
+    content: &str,
+    block: Range<usize>,
     grammar: &mut Grammar,
     category: &str,
     path: &Path,
-) -> Result<()> {
+) -> std::result::Result<(), Vec<ParseError>> {
+    let input = &content[block.clone()];
+    let first_line = content[..block.start].matches('\n').count() + 1;
+    let line_starts = std::iter::once(0)
+        .chain(input.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
     let mut parser = Parser {
         input,
         index: 0,
         grammar,
+        path,
+        offset: block.start,
+        first_line,
+        line_starts,
     };
+    let mut errors = Vec::new();
     loop {
-        let p = parser.parse_production(category, path)?;
-        parser.grammar.name_order.push(p.name.clone());
-        if let Some(dupe) = parser.grammar.productions.insert(p.name.clone(), p) {
-            bail!(parser, "duplicate production {} in grammar", dupe.name);
+        let start = parser.index;
+        match parser.parse_production(category, path) {
+            Ok(p) if parser.grammar.productions.contains_key(&p.name) => {
+                errors.push(ParseError {
+                    line: parser.input[p.span.range.start - parser.offset..]
+                        .lines()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    kind: ParseErrorKind::DuplicateProduction(p.name),
+                    span: p.span,
+                });
+            }
+            Ok(p) => {
+                parser.grammar.name_order.push(p.name.clone());
+                parser.grammar.productions.insert(p.name.clone(), p);
+            }
+            Err(e) => {
+                errors.push(*e);
+                // Skip to the next production, which starts after a blank
+                // line.
+                parser.index = match parser.input[start..].find("\n\n") {
+                    Some(i) => start + i,
+                    None => parser.input.len(),
+                };
+            }
         }
         parser.take_while(&|ch| ch == '\n');
         if parser.eof() {
             break;
         }
     }
-    Ok(())
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
 }
 
 impl Parser<'_> {
     /// Helper to create a new expression with a unique ID.
-    fn new_expr(&mut self, kind: ExpressionKind) -> Expression {
-        let id = self.grammar.next_id();
-        Expression::new_kind(kind, id)
+    ///
+    /// The span of the expression starts at `start` and ends at the current
+    /// position.
+    fn new_expr(&mut self, kind: ExpressionKind, start: usize) -> Expression {
+        self.new_expr_range(kind, start..self.index)
+    }
+
+    /// Helper to create a new expression covering the given byte range of
+    /// the input.
+    ///
+    /// The ID is assigned once the whole production has been parsed.
+    fn new_expr_range(&mut self, kind: ExpressionKind, range: Range<usize>) -> Expression {
+        let mut expr = Expression::new_kind(kind);
+        expr.span = self.span(range);
+        expr
+    }
+
+    /// Returns the byte range of the input that covers all of the given
+    /// expressions.
+    fn covering_range(&self, es: &[Expression]) -> Range<usize> {
+        let start = es.first().unwrap().span.range.start;
+        let end = es.last().unwrap().span.range.end;
+        start - self.offset..end - self.offset
+    }
+
+    /// Converts a byte range of the input to a [`Span`] in the markdown file.
+    fn span(&self, range: Range<usize>) -> Span {
+        Span {
+            path: self.path.to_owned(),
+            range: self.offset + range.start..self.offset + range.end,
+            start: self.line_column(range.start),
+            end: self.line_column(range.end),
+        }
+    }
+
+    /// Converts a byte index of the input to a line and column in the
+    /// markdown file.
+    fn line_column(&self, index: usize) -> LineColumn {
+        let line = self.line_starts.partition_point(|&start| start <= index) - 1;
+        let line_start = self.line_starts[line];
+        LineColumn {
+            line: self.first_line + line,
+            column: self.input[line_start..index].chars().count() + 1,
+        }
     }
 
     fn take_while(&mut self, f: &dyn Fn(char) -> bool) -> &str {
@@ -127,22 +301,27 @@ impl Parser<'_> {
     }
 
     /// Expects the next input to be the given string, and advances the head.
-    fn expect(&mut self, s: &str, err: &str) -> Result<()> {
+    fn expect(&mut self, s: &str, err: ParseErrorKind) -> Result<()> {
         if !self.input[self.index..].starts_with(s) {
-            bail!(self, "{err}");
+            bail!(self, err);
         };
         self.index += s.len();
         Ok(())
     }
 
-    fn error(&mut self, message: String) -> Error {
-        let (line, lineno, col) = translate_position(self.input, self.index);
-        Error {
-            message,
+    /// Returns an error at the current position, covering the next
+    /// character.
+    fn error(&mut self, kind: ParseErrorKind) -> Box<ParseError> {
+        let (line, _, _) = translate_position(self.input, self.index);
+        let end = self.input[self.index..]
+            .chars()
+            .next()
+            .map_or(self.index, |ch| self.index + ch.len_utf8());
+        Box::new(ParseError {
+            kind,
+            span: self.span(self.index..end),
             line: line.to_string(),
-            lineno,
-            col,
-        }
+        })
     }
 
     /// Advances zero or more spaces.
@@ -152,34 +331,70 @@ impl Parser<'_> {
 
     fn parse_production(&mut self, category: &str, path: &Path) -> Result<Production> {
         let mut comments = Vec::new();
-        while let Ok(comment) = self.parse_comment() {
-            self.expect("\n", "expected newline")?;
-            comments.push(self.new_expr(comment));
-            comments.push(self.new_expr(ExpressionKind::Break(0)));
+        loop {
+            let start = self.index;
+            let Ok(comment) = self.parse_comment() else {
+                break;
+            };
+            let comment = self.new_expr(comment, start);
+            self.expect("\n", ParseErrorKind::Expected("newline"))?;
+            comments.push(comment);
+            comments.push(self.new_expr(ExpressionKind::Break(0), self.index));
+        }
+        let start = self.index;
+        let mut is_root = false;
+        let mut edition = None;
+        loop {
+            if self.parse_is_root() {
+                is_root = true;
+            } else if let Some(range) = self.parse_edition()? {
+                edition = Some(range);
+            } else {
+                break;
+            }
+            self.space0();
         }
-        let is_root = self.parse_is_root();
-        self.space0();
         let name = self
             .parse_name()
-            .ok_or_else(|| self.error("expected production name".to_string()))?;
-        self.expect(" ->", "expected -> arrow")?;
+            .ok_or_else(|| self.error(ParseErrorKind::ExpectedProductionName))?;
+        let parameters = self.parse_parameters()?;
+        self.expect(" ->", ParseErrorKind::ExpectedArrow)?;
         let Some(expression) = self.parse_expression()? else {
-            bail!(self, "expected an expression");
+            bail!(self, ParseErrorKind::ExpectedExpression);
         };
-        Ok(Production {
+        let end = self.covering_range(std::slice::from_ref(&expression)).end;
+        let mut production = Production {
             name,
+            parameters,
             comments,
             category: category.to_string(),
             expression,
             path: path.to_owned(),
             is_root,
-        })
+            edition,
+            span: self.span(start..end),
+        };
+        production.assign_ids();
+        Ok(production)
     }
 
     fn parse_is_root(&mut self) -> bool {
         self.take_str("@root")
     }
 
+    /// Parses an `@edition(..)` annotation.
+    fn parse_edition(&mut self) -> Result<Option<EditionRange>> {
+        if !self.take_str("@edition(") {
+            return Ok(None);
+        }
+        let text = self.take_while(&|ch| ch != ')' && ch != '\n').to_string();
+        let range = text
+            .parse()
+            .map_err(|reason| self.error(ParseErrorKind::BadEdition(reason)))?;
+        self.expect(")", ParseErrorKind::Expected("closing `)`"))?;
+        Ok(Some(range))
+    }
+
     fn parse_name(&mut self) -> Option<String> {
         let first = self.input[self.index..].chars().next()?;
         if !is_name_start(first) {
@@ -188,6 +403,53 @@ impl Parser<'_> {
         Some(self.take_while(&|c| is_name_continue(c)).to_string())
     }
 
+    /// Parses the parameters after a production name, such as `<T, U>`.
+    fn parse_parameters(&mut self) -> Result<Vec<String>> {
+        let mut parameters = Vec::new();
+        if !self.take_str("<") {
+            return Ok(parameters);
+        }
+        loop {
+            let Some(name) = self.parse_name() else {
+                bail!(self, ParseErrorKind::Expected("parameter name"));
+            };
+            parameters.push(name);
+            if self.take_str(">") {
+                return Ok(parameters);
+            }
+            self.expect(",", ParseErrorKind::Expected("`,` or `>`"))?;
+            self.space0();
+        }
+    }
+
+    /// Parses a nonterminal name along with any arguments, such as
+    /// `CommaList<Expression>`.
+    ///
+    /// The arguments are separated with `, ` in the returned name, no matter
+    /// how they were spaced in the source.
+    fn parse_nonterminal_name(&mut self) -> Result<Option<String>> {
+        let Some(mut name) = self.parse_name() else {
+            return Ok(None);
+        };
+        if !self.take_str("<") {
+            return Ok(Some(name));
+        }
+        name.push('<');
+        loop {
+            let Some(arg) = self.parse_nonterminal_name()? else {
+                bail!(self, ParseErrorKind::Expected("argument"));
+            };
+            name.push_str(&arg);
+            if self.take_str(">") {
+                name.push('>');
+                return Ok(Some(name));
+            }
+            self.expect(",", ParseErrorKind::Expected("`,` or `>`"))?;
+            self.space0();
+            name.push_str(", ");
+        }
+    }
+
     fn parse_expression(&mut self) -> Result<Option<Expression>> {
         let mut es = Vec::new();
         while let Some(e) = self.parse_seq()? {
@@ -200,14 +462,30 @@ impl Parser<'_> {
         match es.len() {
             0 => Ok(None),
             1 => Ok(Some(es.pop().unwrap())),
-            _ => Ok(Some(self.new_expr(ExpressionKind::Alt(es)))),
+            _ => {
+                let range = self.covering_range(&es);
+                Ok(Some(self.new_expr_range(ExpressionKind::Alt(es), range)))
+            }
         }
     }
 
     fn parse_seq(&mut self) -> Result<Option<Expression>> {
-        let mut es = Vec::new();
+        let mut es: Vec<Expression> = Vec::new();
+        let mut edition = None;
         loop {
             self.space0();
+            // An annotation can only be at the start of an alternative,
+            // possibly on a new line.
+            let edition_start = self.index;
+            if edition.is_none()
+                && es
+                    .iter()
+                    .all(|e| matches!(e.kind, ExpressionKind::Break(_)))
+                && let Some(range) = self.parse_edition()?
+            {
+                edition = Some((range, edition_start));
+                continue;
+            }
             if self.peek() == Some(b'^') {
                 let cut = self.parse_cut()?;
                 es.push(cut);
@@ -218,20 +496,32 @@ impl Parser<'_> {
             };
             es.push(e);
         }
-        match es.len() {
-            0 => Ok(None),
-            1 => Ok(Some(es.pop().unwrap())),
-            _ => Ok(Some(self.new_expr(ExpressionKind::Sequence(es)))),
+        let mut e = match es.len() {
+            0 if edition.is_some() => bail!(self, ParseErrorKind::ExpectedExpression),
+            0 => return Ok(None),
+            1 => es.pop().unwrap(),
+            _ => {
+                let range = self.covering_range(&es);
+                self.new_expr_range(ExpressionKind::Sequence(es), range)
+            }
+        };
+        if let Some((range, start)) = edition {
+            e.edition = Some(range);
+            e.span = self
+                .span(start.min(e.span.range.start - self.offset)..e.span.range.end - self.offset);
         }
+        Ok(Some(e))
     }
 
     /// Parse cut (`^`) operator.
     fn parse_cut(&mut self) -> Result<Expression> {
-        self.expect("^", "expected `^`")?;
+        let start = self.index;
+        self.expect("^", ParseErrorKind::Expected("`^`"))?;
         let Some(rhs) = self.parse_seq()? else {
-            bail!(self, "expected expression after cut operator");
+            bail!(self, ParseErrorKind::MissingOperand("after cut operator"));
         };
-        Ok(self.new_expr(ExpressionKind::Cut(Box::new(rhs))))
+        let end = self.covering_range(std::slice::from_ref(&rhs)).end;
+        Ok(self.new_expr_range(ExpressionKind::Cut(Box::new(rhs)), start..end))
     }
 
     fn parse_expr1(&mut self) -> Result<Option<Expression>> {
@@ -239,6 +529,7 @@ impl Parser<'_> {
             return Ok(None);
         };
 
+        let start = self.index;
         let kind = if self.take_str("U+") {
             ExpressionKind::Unicode(self.parse_unicode()?)
         } else if self.input[self.index..]
@@ -247,7 +538,7 @@ impl Parser<'_> {
             .map(|ch| is_name_start(ch))
             .unwrap_or(false)
         {
-            self.parse_nonterminal()
+            self.parse_nonterminal()?
                 .expect("first char already checked")
         } else if self.take_str("\n") {
             if self.eof() || self.take_str("\n") {
@@ -255,7 +546,7 @@ impl Parser<'_> {
             }
             let space = self.take_while(&|ch| ch == ' ');
             if space.len() == 0 {
-                bail!(self, "expected indentation on next line");
+                bail!(self, ParseErrorKind::ExpectedIndentation);
             }
             ExpressionKind::Break(space.len())
         } else if next == b'/' {
@@ -276,24 +567,23 @@ impl Parser<'_> {
             return Ok(None);
         };
         let kind = match self.peek() {
-            Some(b'?') => self.parse_optional(kind)?,
-            Some(b'*') => self.parse_repeat(kind)?,
-            Some(b'+') => self.parse_repeat_plus(kind)?,
-            Some(b'{') => self.parse_repeat_range(kind)?,
+            Some(b'?') => self.parse_optional(kind, start)?,
+            Some(b'*') => self.parse_repeat(kind, start)?,
+            Some(b'+') => self.parse_repeat_plus(kind, start)?,
+            Some(b'{') => self.parse_repeat_range(kind, start)?,
             _ => kind,
         };
         let suffix = self.parse_suffix()?;
         let footnote = self.parse_footnote()?;
 
-        let mut expr = self.new_expr(kind);
-        expr.suffix = suffix;
+        let mut expr = self.new_expr(kind, start);
+        expr.set_suffix(suffix);
         expr.footnote = footnote;
         Ok(Some(expr))
     }
 
-    fn parse_nonterminal(&mut self) -> Option<ExpressionKind> {
-        let nt = self.parse_name()?;
-        Some(ExpressionKind::Nt(nt))
+    fn parse_nonterminal(&mut self) -> Result<Option<ExpressionKind>> {
+        Ok(self.parse_nonterminal_name()?.map(ExpressionKind::Nt))
     }
 
     /// Parse terminal within backticks.
@@ -303,37 +593,38 @@ impl Parser<'_> {
 
     /// Parse string within backticks.
     fn parse_terminal_str(&mut self) -> Result<String> {
-        self.expect("`", "expected opening backtick")?;
+        self.expect("`", ParseErrorKind::Expected("opening backtick"))?;
         let term = self.take_while(&|x| !['\n', '`'].contains(&x)).to_string();
         if term.is_empty() {
-            bail!(self, "expected terminal");
+            bail!(self, ParseErrorKind::EmptyTerminal);
         }
-        self.expect("`", "expected closing backtick")?;
+        self.expect("`", ParseErrorKind::UnterminatedTerminal)?;
         Ok(term)
     }
 
     /// Parse e.g. `// Single line comment.`.
     fn parse_comment(&mut self) -> Result<ExpressionKind> {
-        self.expect("//", "expected `//`")?;
+        self.expect("//", ParseErrorKind::Expected("`//`"))?;
         let text = self.take_while(&|x| x != '\n').to_string();
         Ok(ExpressionKind::Comment(text))
     }
 
     fn parse_charset(&mut self) -> Result<ExpressionKind> {
-        self.expect("[", "expected opening [")?;
+        self.expect("[", ParseErrorKind::Expected("opening ["))?;
         let mut characters = Vec::new();
         loop {
             self.space0();
+            let start = self.index;
             let Some(ch) = self.parse_characters()? else {
                 break;
             };
-            characters.push(self.new_expr(ch));
+            characters.push(self.new_expr(ch, start));
         }
         if characters.is_empty() {
-            bail!(self, "expected at least one character in character group");
+            bail!(self, ParseErrorKind::EmptyCharset);
         }
         self.space0();
-        self.expect("]", "expected closing ]")?;
+        self.expect("]", ParseErrorKind::UnterminatedCharset)?;
         Ok(ExpressionKind::Charset(characters))
     }
 
@@ -343,14 +634,20 @@ impl Parser<'_> {
         if let Some(a) = self.parse_character()? {
             if self.take_str("-") {
                 let Some(b) = self.parse_character()? else {
-                    bail!(self, "expected character in range");
+                    bail!(
+                        self,
+                        ParseErrorKind::BadCharsetElement("expected character in range")
+                    );
                 };
                 Ok(Some(ExpressionKind::CharacterRange(a, b)))
             } else {
                 //~^ Parse terminal in backticks.
                 let t = match a {
                     Character::Char(ch) => ch.to_string(),
-                    Character::Unicode(_) => bail!(self, "unicode not supported"),
+                    Character::Unicode(_) => bail!(
+                        self,
+                        ParseErrorKind::BadCharsetElement("unicode not supported")
+                    ),
                 };
                 Ok(Some(ExpressionKind::Terminal(t)))
             }
@@ -368,7 +665,10 @@ impl Parser<'_> {
             let term = self.parse_terminal_str()?;
             if term.len() > 1 {
                 self.index = recov + 1;
-                bail!(self, "invalid start terminal in range");
+                bail!(
+                    self,
+                    ParseErrorKind::BadCharsetElement("invalid start terminal in range")
+                );
             }
             let ch = term.chars().next().unwrap();
             Ok(Some(Character::Char(ch)))
@@ -381,47 +681,53 @@ impl Parser<'_> {
 
     /// Parse e.g. `<prose text>`.
     fn parse_prose(&mut self) -> Result<ExpressionKind> {
-        self.expect("<", "expected opening `<`")?;
+        self.expect("<", ParseErrorKind::Expected("opening `<`"))?;
         let text = self.take_while(&|x| !['\n', '>'].contains(&x)).to_string();
         if text.is_empty() {
-            bail!(self, "expected prose text");
+            bail!(self, ParseErrorKind::EmptyProse);
         }
-        self.expect(">", "expected closing `>`")?;
+        self.expect(">", ParseErrorKind::UnterminatedProse)?;
         Ok(ExpressionKind::Prose(text))
     }
 
     fn parse_grouped(&mut self) -> Result<ExpressionKind> {
-        self.expect("(", "expected opening `(`")?;
+        self.expect("(", ParseErrorKind::Expected("opening `(`"))?;
         self.space0();
         let Some(e) = self.parse_expression()? else {
-            bail!(self, "expected expression in parenthesized group");
+            bail!(
+                self,
+                ParseErrorKind::MissingOperand("in parenthesized group")
+            );
         };
         self.space0();
-        self.expect(")", "expected closing `)`")?;
+        self.expect(")", ParseErrorKind::UnterminatedGroup)?;
         Ok(ExpressionKind::Grouped(Box::new(e)))
     }
 
     fn parse_neg_expression(&mut self) -> Result<ExpressionKind> {
-        self.expect("~", "expected ~")?;
+        self.expect("~", ParseErrorKind::Expected("~"))?;
+        let start = self.index;
         let Some(next) = self.peek() else {
-            bail!(self, "expected expression after ~");
+            bail!(self, ParseErrorKind::MissingOperand("after ~"));
         };
         let kind = match next {
             b'[' => self.parse_charset()?,
             b'`' => self.parse_terminal()?,
-            _ => self.parse_nonterminal().ok_or_else(|| {
-                self.error("expected a charset, terminal, or name after ~ negation".to_string())
+            _ => self.parse_name().map(ExpressionKind::Nt).ok_or_else(|| {
+                self.error(ParseErrorKind::Expected(
+                    "a charset, terminal, or name after ~ negation",
+                ))
             })?,
         };
-        let inner_expr = self.new_expr(kind);
+        let inner_expr = self.new_expr(kind, start);
         Ok(ExpressionKind::NegExpression(Box::new(inner_expr)))
     }
 
     fn parse_negative_lookahead(&mut self) -> Result<ExpressionKind> {
-        self.expect("!", "expected !")?;
+        self.expect("!", ParseErrorKind::Expected("!"))?;
         self.space0();
         let Some(e) = self.parse_expr1()? else {
-            bail!(self, "expected expression after !");
+            bail!(self, ParseErrorKind::MissingOperand("after !"));
         };
         Ok(ExpressionKind::NegativeLookahead(Box::new(e)))
     }
@@ -435,7 +741,7 @@ impl Parser<'_> {
                     xs.push(x);
                     self.index += 1;
                 }
-                _ => bail!(self, "expected 4 uppercase hexadecimal digits after `U+`"),
+                _ => bail!(self, ParseErrorKind::BadUnicode),
             }
             Ok(())
         };
@@ -453,31 +759,34 @@ impl Parser<'_> {
     }
 
     /// Parse `?` after expression.
-    fn parse_optional(&mut self, kind: ExpressionKind) -> Result<ExpressionKind> {
-        self.expect("?", "expected `?`")?;
-        let inner_expr = self.new_expr(kind);
+    ///
+    /// `start` is where the expression being quantified starts.
+    fn parse_optional(&mut self, kind: ExpressionKind, start: usize) -> Result<ExpressionKind> {
+        let inner_expr = self.new_expr(kind, start);
+        self.expect("?", ParseErrorKind::Expected("`?`"))?;
         Ok(ExpressionKind::Optional(Box::new(inner_expr)))
     }
 
     /// Parse `*` after expression.
-    fn parse_repeat(&mut self, kind: ExpressionKind) -> Result<ExpressionKind> {
-        self.expect("*", "expected `*`")?;
-        let inner_expr = self.new_expr(kind);
+    fn parse_repeat(&mut self, kind: ExpressionKind, start: usize) -> Result<ExpressionKind> {
+        let inner_expr = self.new_expr(kind, start);
+        self.expect("*", ParseErrorKind::Expected("`*`"))?;
         Ok(ExpressionKind::Repeat(Box::new(inner_expr)))
     }
 
     /// Parse `+` after expression.
-    fn parse_repeat_plus(&mut self, kind: ExpressionKind) -> Result<ExpressionKind> {
-        self.expect("+", "expected `+`")?;
-        let inner_expr = self.new_expr(kind);
+    fn parse_repeat_plus(&mut self, kind: ExpressionKind, start: usize) -> Result<ExpressionKind> {
+        let inner_expr = self.new_expr(kind, start);
+        self.expect("+", ParseErrorKind::Expected("`+`"))?;
         Ok(ExpressionKind::RepeatPlus(Box::new(inner_expr)))
     }
 
     /// Parse `{a..b}` | `{a..=b}` | `{name:a..=b}` | `{name}` after expression.
     //
     // `name:` before the range is a named binding. `{name}` refers to that binding.
-    fn parse_repeat_range(&mut self, kind: ExpressionKind) -> Result<ExpressionKind> {
-        self.expect("{", "expected `{`")?;
+    fn parse_repeat_range(&mut self, kind: ExpressionKind, start: usize) -> Result<ExpressionKind> {
+        let inner_expr = self.new_expr(kind, start);
+        self.expect("{", ParseErrorKind::Expected("`{`"))?;
         let start = self.index;
         let name = match (self.parse_name(), self.peek()) {
             (Some(name), Some(b':')) => {
@@ -486,7 +795,6 @@ impl Parser<'_> {
             }
             (Some(name), Some(b'}')) => {
                 self.index += 1;
-                let inner_expr = self.new_expr(kind);
                 return Ok(ExpressionKind::RepeatRangeNamed(Box::new(inner_expr), name));
             }
             _ => {
@@ -496,9 +804,12 @@ impl Parser<'_> {
         };
         let min = self.take_while(&|x| x.is_ascii_digit());
         let Ok(min) = (!min.is_empty()).then(|| min.parse::<u32>()).transpose() else {
-            bail!(self, "malformed range start");
+            bail!(
+                self,
+                ParseErrorKind::BadRange("malformed range start".to_string())
+            );
         };
-        self.expect("..", "expected `..` or `..=`")?;
+        self.expect("..", ParseErrorKind::Expected("`..` or `..=`"))?;
         let limit = if self.take_str("=") {
             RangeLimit::Closed
         } else {
@@ -506,23 +817,39 @@ impl Parser<'_> {
         };
         let max = self.take_while(&|x| x.is_ascii_digit());
         let Ok(max) = (!max.is_empty()).then(|| max.parse::<u32>()).transpose() else {
-            bail!(self, "malformed range end");
+            bail!(
+                self,
+                ParseErrorKind::BadRange("malformed range end".to_string())
+            );
         };
         match (min, max, limit) {
             (Some(min), Some(max), _) if max < min => {
-                bail!(self, "range {min}{limit}{max} is malformed")
+                bail!(
+                    self,
+                    ParseErrorKind::BadRange(format!("range {min}{limit}{max} is malformed"))
+                )
             }
             (Some(min), Some(max), RangeLimit::HalfOpen) if max <= min => {
-                bail!(self, "half-open range maximum must be greater than minimum")
+                bail!(
+                    self,
+                    ParseErrorKind::BadRange(
+                        "half-open range maximum must be greater than minimum".to_string()
+                    )
+                )
             }
             (None, Some(0), RangeLimit::HalfOpen) => {
-                bail!(self, "half-open range `..0` is empty")
+                bail!(
+                    self,
+                    ParseErrorKind::BadRange("half-open range `..0` is empty".to_string())
+                )
             }
-            (_, None, RangeLimit::Closed) => bail!(self, "closed range must have an upper bound"),
+            (_, None, RangeLimit::Closed) => bail!(
+                self,
+                ParseErrorKind::BadRange("closed range must have an upper bound".to_string())
+            ),
             _ => {}
         }
-        self.expect("}", "expected `}`")?;
-        let inner_expr = self.new_expr(kind);
+        self.expect("}", ParseErrorKind::Expected("`}`"))?;
         Ok(ExpressionKind::RepeatRange {
             expr: Box::new(inner_expr),
             name,
@@ -540,11 +867,11 @@ impl Parser<'_> {
         let start = self.index;
         loop {
             let Some(next) = self.peek() else {
-                bail!(self, "failed to find end of _ suffixed text");
+                bail!(self, ParseErrorKind::UnterminatedSuffix);
             };
             self.index += 1;
             match next {
-                b'\n' => bail!(self, "failed to find end of _ suffixed text"),
+                b'\n' => bail!(self, ParseErrorKind::UnterminatedSuffix),
                 b'`' => in_backtick = !in_backtick,
                 b'_' if !in_backtick => {
                     if self
@@ -568,9 +895,9 @@ impl Parser<'_> {
         }
         let id = self.take_while(&|x| !['\n', ']'].contains(&x)).to_string();
         if id.is_empty() {
-            bail!(self, "expected footnote id");
+            bail!(self, ParseErrorKind::EmptyFootnote);
         }
-        self.expect("]", "expected closing `]`")?;
+        self.expect("]", ParseErrorKind::UnterminatedFootnote)?;
         Ok(Some(id))
     }
 }
@@ -598,8 +925,8 @@ fn translate_position(input: &str, index: usize) -> (&str, usize, usize) {
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::{parse_grammar, translate_position};
-    use crate::{Character, ExpressionKind, Grammar, RangeLimit};
+    use crate::parser::{ParseErrorKind, parse_grammar, translate_position};
+    use crate::{Character, Expression, ExpressionKind, Grammar, LineColumn, RangeLimit};
     use std::path::Path;
 
     #[test]
@@ -615,11 +942,130 @@ mod tests {
 
     fn parse(input: &str) -> Result<Grammar, String> {
         let mut grammar = Grammar::default();
-        parse_grammar(input, &mut grammar, "test", Path::new("test.md"))
-            .map_err(|e| e.to_string())?;
+        parse_grammar(
+            input,
+            0..input.len(),
+            &mut grammar,
+            "test",
+            Path::new("test.md"),
+        )
+        .map_err(|errors| {
+            errors
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })?;
         Ok(grammar)
     }
 
+    // --- Span tests ---
+
+    /// Parses a grammar block embedded in a markdown document.
+    fn parse_markdown(content: &str) -> Grammar {
+        let start = content.find("```grammar,test\n").unwrap() + "```grammar,test\n".len();
+        let end = start + content[start..].find("```").unwrap();
+        let mut grammar = Grammar::default();
+        parse_grammar(
+            content,
+            start..end,
+            &mut grammar,
+            "test",
+            Path::new("a/b.md"),
+        )
+        .unwrap();
+        grammar
+    }
+
+    #[test]
+    fn span_production() {
+        let grammar = parse_markdown("# Title\n\n```grammar,test\n@root Rule -> A B\n```\n");
+        let rule = grammar.productions.get("Rule").unwrap();
+        assert_eq!(rule.span.path, Path::new("a/b.md"));
+        assert_eq!(rule.span.start, LineColumn { line: 4, column: 1 });
+        assert_eq!(
+            rule.span.end,
+            LineColumn {
+                line: 4,
+                column: 18
+            }
+        );
+        assert_eq!(rule.span.to_string(), "a/b.md:4:1");
+    }
+
+    #[test]
+    fn span_expressions() {
+        let content = "```grammar,test\nRule ->\n    A `x`*\n  | [`a`-`z`]\n```\n";
+        let grammar = parse_markdown(content);
+        let rule = grammar.productions.get("Rule").unwrap();
+        let text = |e: &Expression| &content[e.span.range.clone()];
+        let ExpressionKind::Alt(alts) = &rule.expression.kind else {
+            panic!("expected Alt, got {:?}", rule.expression.kind);
+        };
+        let ExpressionKind::Sequence(seq) = &alts[0].kind else {
+            panic!("expected Sequence, got {:?}", alts[0].kind);
+        };
+        // The first element is the break before `A`.
+        assert!(seq[0].is_break());
+        assert_eq!(text(&seq[1]), "A");
+        assert_eq!(seq[1].span.start, LineColumn { line: 3, column: 5 });
+        assert_eq!(text(&seq[2]), "`x`*");
+        let ExpressionKind::Repeat(inner) = &seq[2].kind else {
+            panic!("expected Repeat, got {:?}", seq[2].kind);
+        };
+        assert_eq!(text(inner), "`x`");
+        assert_eq!(
+            inner.span.end,
+            LineColumn {
+                line: 3,
+                column: 10
+            }
+        );
+        assert_eq!(text(&alts[1]), "[`a`-`z`]");
+        assert_eq!(alts[1].span.start, LineColumn { line: 4, column: 5 });
+        let ExpressionKind::Charset(chars) = &alts[1].kind else {
+            panic!("expected Charset, got {:?}", alts[1].kind);
+        };
+        assert_eq!(text(&chars[0]), "`a`-`z`");
+    }
+
+    #[test]
+    fn span_error_line_number() {
+        let content = "text\n\n```grammar,test\nRule -> A\n\nBad\n```\n";
+        let start = content.find("Rule").unwrap();
+        let end = content.rfind("```").unwrap();
+        let mut grammar = Grammar::default();
+        let err = parse_grammar(content, start..end, &mut grammar, "test", Path::new("x.md"))
+            .unwrap_err()[0]
+            .to_string();
+        assert!(err.contains("6 | Bad"), "expected line 6, got: {err}");
+    }
+
+    #[test]
+    fn collects_all_errors() {
+        let content = "```grammar,test\nA -> `x\n\nB -> C\n\nD ->\n\nB -> E\n```\n";
+        let start = "```grammar,test\n".len();
+        let end = content.rfind("```").unwrap();
+        let mut grammar = Grammar::default();
+        let errors = parse_grammar(content, start..end, &mut grammar, "test", Path::new("x.md"))
+            .unwrap_err();
+        let kinds: Vec<_> = errors.iter().map(|e| e.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                ParseErrorKind::UnterminatedTerminal,
+                ParseErrorKind::ExpectedExpression,
+                ParseErrorKind::DuplicateProduction("B".to_string()),
+            ]
+        );
+        assert_eq!(errors[0].span.path, Path::new("x.md"));
+        assert_eq!(&content[errors[0].span.range.clone()], "\n");
+        assert_eq!(errors[0].span.start, LineColumn { line: 2, column: 8 });
+        assert_eq!(errors[2].span.start, LineColumn { line: 8, column: 1 });
+        // The productions without errors are still loaded.
+        assert_eq!(grammar.name_order, ["B"]);
+    }
+
     #[test]
     fn test_cut() {
         let input = "Rule -> A ^ B | C";
@@ -973,6 +1419,15 @@ mod tests {
         assert_eq!(s, "10FFFF");
     }
 
+    #[test]
+    fn unicode_too_short() {
+        let err = parse("Rule -> U+00").unwrap_err();
+        assert!(
+            err.contains("expected 4 to 6 uppercase hexadecimal digits after `U+`"),
+            "expected unicode error, got: {err}"
+        );
+    }
+
     #[test]
     fn unicode_in_alternation() {
         let input = "Rule -> U+0009 | U+000A";
@@ -1330,4 +1785,59 @@ mod tests {
             "expected `..` error for {{}}, got: {err}"
         );
     }
+
+    #[test]
+    fn edition_annotations() {
+        let grammar =
+            parse("@root @edition(2021..) A ->\n      B\n    | @edition(..2021) C D").unwrap();
+        let a = &grammar.productions["A"];
+        assert!(a.is_root);
+        assert_eq!(a.edition.unwrap().to_string(), "2021..");
+        let ExpressionKind::Alt(alts) = &a.expression.kind else {
+            panic!("expected Alt, got {:?}", a.expression.kind);
+        };
+        assert_eq!(alts[0].edition, None);
+        assert_eq!(alts[1].edition.unwrap().to_string(), "..2021");
+        assert!(matches!(alts[1].kind, ExpressionKind::Sequence(_)));
+    }
+
+    #[test]
+    fn edition_err() {
+        let err = parse("A -> @edition(2019..) B").unwrap_err();
+        assert!(
+            err.contains("unknown edition `2019`"),
+            "expected unknown edition error, got: {err}"
+        );
+        let err = parse("A -> B @edition(2018..) C").unwrap_err();
+        assert!(
+            err.contains("expected"),
+            "annotation in the middle of a sequence should fail, got: {err}"
+        );
+        let err = parse("A -> B | @edition(2018..)").unwrap_err();
+        assert!(
+            err.contains("expected an expression"),
+            "expected missing expression error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn parameters() {
+        let g = parse("A -> L<B,  C<D>> E\n\nL<T,U> -> T U").unwrap();
+        assert_eq!(g.productions["L"].parameters, ["T", "U"]);
+        assert!(g.productions["A"].parameters.is_empty());
+        let ExpressionKind::Sequence(es) = &g.productions["A"].expression.kind else {
+            panic!("expected sequence");
+        };
+        assert!(matches!(&es[0].kind, ExpressionKind::Nt(n) if n == "L<B, C<D>>"));
+        assert_eq!(es[0].span.range, 5..16);
+
+        let err = parse("L<> -> `x`").unwrap_err();
+        assert!(err.contains("expected parameter name"), "{err}");
+        let err = parse("L<T -> `x`").unwrap_err();
+        assert!(err.contains("expected `,` or `>`"), "{err}");
+        let err = parse("A -> L<>").unwrap_err();
+        assert!(err.contains("expected argument"), "{err}");
+        let err = parse("A -> L<`x`>").unwrap_err();
+        assert!(err.contains("expected argument"), "{err}");
+    }
 }
@@ -0,0 +1,551 @@
+//! Queries for finding productions in the grammar.
+//!
+//! A query is a list of predicates combined with `and`, `or`, `not`, and
+//! parentheses, such as `category:items and uses:Visibility`. `and` binds
+//! more tightly than `or`.
+//!
+//! | Predicate | Matches productions that |
+//! |-----------|--------------------------|
+//! | `kind:Cut` | contain an expression of the given [`ExpressionKind`] |
+//! | `terminal:fn` | contain the given terminal |
+//! | `uses:Visibility` | refer to the given production |
+//! | `reaches:Visibility` | refer to the given production, directly or through other productions |
+//! | `reachable-from:Item` | the given production can reach |
+//! | `first:<` | can start with the given terminal, token, or character |
+//! | `category:items` | are in the given category |
+//! | `path:items` | are in a chapter under the given path |
+//! | `root` | are marked with `@root` |
+//! | `nullable` | can match the empty string |
+//!
+//! A value can be written in backticks if it contains spaces or
+//! parentheses, such as ``first:`(` ``.
+//!
+//! Instances of productions with parameters are used to follow references,
+//! but are never part of the results.
+
+use crate::analysis::Symbol;
+use crate::parameters::names_in;
+use crate::{Analysis, ExpressionKind, Grammar, Production, Span};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A parsed query, created with [`str::parse`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Query {
+    /// `kind:Name`
+    Kind(String),
+    /// `terminal:text`
+    Terminal(String),
+    /// `uses:Name`
+    Uses(String),
+    /// `reaches:Name`
+    Reaches(String),
+    /// `reachable-from:Name`
+    ReachableFrom(String),
+    /// `first:text`
+    First(String),
+    /// `category:name`
+    Category(String),
+    /// `path:path`
+    Path(PathBuf),
+    /// `root`
+    Root,
+    /// `nullable`
+    Nullable,
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+/// The names of the variants of [`ExpressionKind`], as used by `kind:`.
+const KINDS: &[&str] = &[
+    "Grouped",
+    "Alt",
+    "Sequence",
+    "Optional",
+    "NegativeLookahead",
+    "Repeat",
+    "RepeatPlus",
+    "RepeatRange",
+    "RepeatRangeNamed",
+    "Nt",
+    "Terminal",
+    "Prose",
+    "Break",
+    "Comment",
+    "Charset",
+    "NegExpression",
+    "Cut",
+    "Unicode",
+    "CharacterRange",
+];
+
+fn kind_name(kind: &ExpressionKind) -> &'static str {
+    match kind {
+        ExpressionKind::Grouped(_) => "Grouped",
+        ExpressionKind::Alt(_) => "Alt",
+        ExpressionKind::Sequence(_) => "Sequence",
+        ExpressionKind::Optional(_) => "Optional",
+        ExpressionKind::NegativeLookahead(_) => "NegativeLookahead",
+        ExpressionKind::Repeat(_) => "Repeat",
+        ExpressionKind::RepeatPlus(_) => "RepeatPlus",
+        ExpressionKind::RepeatRange { .. } => "RepeatRange",
+        ExpressionKind::RepeatRangeNamed(..) => "RepeatRangeNamed",
+        ExpressionKind::Nt(_) => "Nt",
+        ExpressionKind::Terminal(_) => "Terminal",
+        ExpressionKind::Prose(_) => "Prose",
+        ExpressionKind::Break(_) => "Break",
+        ExpressionKind::Comment(_) => "Comment",
+        ExpressionKind::Charset(_) => "Charset",
+        ExpressionKind::NegExpression(_) => "NegExpression",
+        ExpressionKind::Cut(_) => "Cut",
+        ExpressionKind::Unicode(_) => "Unicode",
+        ExpressionKind::CharacterRange(..) => "CharacterRange",
+    }
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Query, String> {
+        let tokens = tokenize(s)?;
+        let mut parser = QueryParser { tokens, index: 0 };
+        let query = parser.parse_or()?;
+        match parser.tokens.get(parser.index) {
+            Some(token) => Err(format!("expected `and` or `or`, found `{token}`")),
+            None => Ok(query),
+        }
+    }
+}
+
+/// Splits a query into words and parentheses.
+fn tokenize(s: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut in_backtick = false;
+    for ch in s.chars() {
+        match ch {
+            '`' => in_backtick = !in_backtick,
+            _ if in_backtick => word.push(ch),
+            '(' | ')' => {
+                if !word.is_empty() {
+                    tokens.push(std::mem::take(&mut word));
+                }
+                tokens.push(ch.to_string());
+            }
+            _ if ch.is_whitespace() => {
+                if !word.is_empty() {
+                    tokens.push(std::mem::take(&mut word));
+                }
+            }
+            _ => word.push(ch),
+        }
+    }
+    if in_backtick {
+        return Err("unterminated backtick".to_string());
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    Ok(tokens)
+}
+
+struct QueryParser {
+    tokens: Vec<String>,
+    index: usize,
+}
+
+impl QueryParser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.index).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<&str> {
+        let token = self.tokens.get(self.index)?;
+        self.index += 1;
+        Some(token)
+    }
+
+    fn parse_or(&mut self) -> Result<Query, String> {
+        let mut query = self.parse_and()?;
+        while self.peek() == Some("or") {
+            self.index += 1;
+            query = Query::Or(Box::new(query), Box::new(self.parse_and()?));
+        }
+        Ok(query)
+    }
+
+    fn parse_and(&mut self) -> Result<Query, String> {
+        let mut query = self.parse_unary()?;
+        while self.peek() == Some("and") {
+            self.index += 1;
+            query = Query::And(Box::new(query), Box::new(self.parse_unary()?));
+        }
+        Ok(query)
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, String> {
+        let Some(token) = self.next() else {
+            return Err("expected a predicate, found the end of the query".to_string());
+        };
+        match token {
+            "not" => Ok(Query::Not(Box::new(self.parse_unary()?))),
+            "(" => {
+                let query = self.parse_or()?;
+                match self.next() {
+                    Some(")") => Ok(query),
+                    Some(token) => Err(format!("expected `)`, found `{token}`")),
+                    None => Err("expected `)`, found the end of the query".to_string()),
+                }
+            }
+            "root" => Ok(Query::Root),
+            "nullable" => Ok(Query::Nullable),
+            _ => parse_predicate(token),
+        }
+    }
+}
+
+fn parse_predicate(token: &str) -> Result<Query, String> {
+    let Some((key, value)) = token.split_once(':') else {
+        return Err(format!("expected a predicate, found `{token}`"));
+    };
+    if value.is_empty() {
+        return Err(format!("expected a value after `{key}:`"));
+    }
+    let value = value.to_string();
+    let query = match key {
+        "kind" => {
+            if !KINDS.contains(&value.as_str()) {
+                return Err(format!(
+                    "unknown expression kind `{value}`, expected one of: {}",
+                    KINDS.join(", ")
+                ));
+            }
+            Query::Kind(value)
+        }
+        "terminal" => Query::Terminal(value),
+        "uses" => Query::Uses(value),
+        "reaches" => Query::Reaches(value),
+        "reachable-from" => Query::ReachableFrom(value),
+        "first" => Query::First(value),
+        "category" => Query::Category(value),
+        "path" => Query::Path(value.into()),
+        _ => return Err(format!("unknown predicate `{key}:`")),
+    };
+    Ok(query)
+}
+
+impl Query {
+    /// The names of the productions that the query refers to.
+    fn production_names(&self, names: &mut Vec<String>) {
+        match self {
+            Query::Uses(name) | Query::Reaches(name) | Query::ReachableFrom(name) => {
+                names.push(name.clone())
+            }
+            Query::Not(q) => q.production_names(names),
+            Query::And(a, b) | Query::Or(a, b) => {
+                a.production_names(names);
+                b.production_names(names);
+            }
+            _ => {}
+        }
+    }
+
+    fn needs_analysis(&self) -> bool {
+        match self {
+            Query::First(_) | Query::Nullable => true,
+            Query::Not(q) => q.needs_analysis(),
+            Query::And(a, b) | Query::Or(a, b) => a.needs_analysis() || b.needs_analysis(),
+            _ => false,
+        }
+    }
+}
+
+/// The productions that matched a query, created with [`Grammar::query`].
+#[derive(Debug, Default, Serialize)]
+pub struct QueryResults {
+    /// The matching productions, in the order of [`Grammar::name_order`].
+    pub matches: Vec<QueryMatch>,
+}
+
+/// A production that matched a query.
+#[derive(Debug, Serialize)]
+pub struct QueryMatch {
+    pub name: String,
+    pub category: String,
+    pub path: PathBuf,
+    pub span: Span,
+}
+
+impl QueryResults {
+    /// Returns the results in JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl Display for QueryResults {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let width = self.matches.iter().map(|m| m.name.len()).max().unwrap_or(0);
+        for m in &self.matches {
+            writeln!(f, "{:width$}  {}", m.name, m.span)?;
+        }
+        Ok(())
+    }
+}
+
+impl Grammar {
+    /// Returns the productions that match the query.
+    ///
+    /// Returns an error if the query refers to a production that doesn't
+    /// exist.
+    pub fn query(&self, query: &Query) -> Result<QueryResults, String> {
+        let mut names = Vec::new();
+        query.production_names(&mut names);
+        for name in names {
+            if !self.productions.contains_key(&name) {
+                return Err(format!("production `{name}` does not exist"));
+            }
+        }
+        let cx = QueryContext {
+            grammar: self,
+            analysis: query.needs_analysis().then(|| self.analyze()),
+            references: references(self),
+        };
+        let matched = cx.eval(query);
+        let matches = self
+            .name_order
+            .iter()
+            .filter(|name| matched.contains(name.as_str()))
+            .map(|name| {
+                let prod = &self.productions[name];
+                QueryMatch {
+                    name: prod.name_with_parameters(),
+                    category: prod.category.clone(),
+                    path: prod.path.clone(),
+                    span: prod.span.clone(),
+                }
+            })
+            .collect();
+        Ok(QueryResults { matches })
+    }
+}
+
+/// The names of the productions that each production refers to.
+///
+/// A reference to `CommaList<Expression>` refers to that instance, and to
+/// `CommaList` and `Expression`.
+fn references(grammar: &Grammar) -> HashMap<&str, HashSet<&str>> {
+    grammar
+        .productions
+        .values()
+        .map(|prod| {
+            let mut refs = HashSet::new();
+            prod.expression.visit(&mut |e| {
+                if let ExpressionKind::Nt(nt) = &e.kind {
+                    refs.extend(names_in(nt).into_iter().chain([nt.as_str()]));
+                }
+            });
+            for param in &prod.parameters {
+                refs.remove(param.as_str());
+            }
+            (prod.name.as_str(), refs)
+        })
+        .collect()
+}
+
+struct QueryContext<'a> {
+    grammar: &'a Grammar,
+    analysis: Option<Analysis>,
+    references: HashMap<&'a str, HashSet<&'a str>>,
+}
+
+impl<'a> QueryContext<'a> {
+    /// Returns the names of the productions that match the query.
+    fn eval(&self, query: &Query) -> HashSet<&'a str> {
+        match query {
+            Query::Not(q) => {
+                let matched = self.eval(q);
+                self.filter(|prod| !matched.contains(prod.name.as_str()))
+            }
+            Query::And(a, b) => {
+                let b = self.eval(b);
+                self.eval(a).intersection(&b).copied().collect()
+            }
+            Query::Or(a, b) => {
+                let b = self.eval(b);
+                self.eval(a).union(&b).copied().collect()
+            }
+            Query::Reaches(name) => {
+                // Search backwards from the production.
+                let mut reached = HashSet::new();
+                let mut stack = vec![name.as_str()];
+                while let Some(target) = stack.pop() {
+                    for (user, refs) in &self.references {
+                        if refs.contains(target) && reached.insert(*user) {
+                            stack.push(user);
+                        }
+                    }
+                }
+                self.filter(|prod| reached.contains(prod.name.as_str()))
+            }
+            Query::ReachableFrom(name) => {
+                let mut reached = HashSet::new();
+                let mut stack = vec![name.as_str()];
+                while let Some(user) = stack.pop() {
+                    for target in self.references.get(user).into_iter().flatten() {
+                        if reached.insert(*target) {
+                            stack.push(target);
+                        }
+                    }
+                }
+                self.filter(|prod| reached.contains(prod.name.as_str()))
+            }
+            _ => self.filter(|prod| self.matches(query, prod)),
+        }
+    }
+
+    /// Returns the names of the productions, other than instances, for
+    /// which `f` returns true.
+    fn filter(&self, f: impl Fn(&Production) -> bool) -> HashSet<&'a str> {
+        self.grammar
+            .productions
+            .values()
+            .filter(|prod| !prod.is_instance() && f(prod))
+            .map(|prod| prod.name.as_str())
+            .collect()
+    }
+
+    /// Whether a production matches a predicate that only depends on the
+    /// production itself.
+    fn matches(&self, query: &Query, prod: &Production) -> bool {
+        match query {
+            Query::Kind(kind) => {
+                let mut found = false;
+                prod.expression.visit(&mut |e| {
+                    found |= kind_name(&e.kind) == kind;
+                });
+                found
+            }
+            Query::Terminal(terminal) => {
+                let mut found = false;
+                prod.expression.visit(&mut |e| {
+                    found |= matches!(&e.kind, ExpressionKind::Terminal(t) if t == terminal);
+                });
+                found
+            }
+            Query::Uses(name) => self.references[prod.name.as_str()].contains(name.as_str()),
+            Query::First(text) => {
+                let analysis = self.analysis.as_ref().unwrap();
+                analysis
+                    .first(&prod.name)
+                    .is_some_and(|first| first.iter().any(|symbol| starts_with(symbol, text)))
+            }
+            Query::Category(category) => prod.category == *category,
+            Query::Path(path) => prod.path.starts_with(Path::new(path)),
+            Query::Root => prod.is_root,
+            Query::Nullable => self.analysis.as_ref().unwrap().is_nullable(&prod.name),
+            Query::Not(_)
+            | Query::And(..)
+            | Query::Or(..)
+            | Query::Reaches(_)
+            | Query::ReachableFrom(_) => unreachable!("handled by eval"),
+        }
+    }
+}
+
+/// Whether a symbol in a FIRST set matches the text of a `first:` query.
+fn starts_with(symbol: &Symbol, text: &str) -> bool {
+    match symbol {
+        Symbol::Terminal(s) | Symbol::Token(s) => s == text,
+        Symbol::Chars(set) => {
+            let mut chars = text.chars();
+            matches!((chars.next(), chars.next()), (Some(ch), None) if set.contains(ch))
+        }
+        Symbol::Prose(_) | Symbol::End => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Query;
+    use crate::{Grammar, GrammarLoader};
+    use diagnostics::Diagnostics;
+
+    fn query(g: &Grammar, q: &str) -> Vec<String> {
+        let q: Query = q.parse().unwrap();
+        g.query(&q)
+            .unwrap()
+            .matches
+            .into_iter()
+            .map(|m| m.name)
+            .collect()
+    }
+
+    #[test]
+    fn parse() {
+        let q: Query = "category:items and (uses:A or not kind:Cut)"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            q,
+            Query::And(
+                Box::new(Query::Category("items".to_string())),
+                Box::new(Query::Or(
+                    Box::new(Query::Uses("A".to_string())),
+                    Box::new(Query::Not(Box::new(Query::Kind("Cut".to_string())))),
+                )),
+            )
+        );
+        let q: Query = "first:`(` or root and nullable".parse().unwrap();
+        assert_eq!(
+            q,
+            Query::Or(
+                Box::new(Query::First("(".to_string())),
+                Box::new(Query::And(Box::new(Query::Root), Box::new(Query::Nullable))),
+            )
+        );
+        assert!("kind:Foo".parse::<Query>().is_err());
+        assert!("size:1".parse::<Query>().is_err());
+        assert!("(root".parse::<Query>().is_err());
+        assert!("root nullable".parse::<Query>().is_err());
+        assert!("uses:".parse::<Query>().is_err());
+    }
+
+    #[test]
+    fn predicates() {
+        let g = GrammarLoader::new(env!("CARGO_MANIFEST_DIR"))
+            .filter(|_| false)
+            .snippet(
+                "items.md",
+                "items",
+                "@root Item -> Vis? (Fn | Struct)\n\n\
+                 Fn -> `fn` ^ NAME List<Param>\n\n\
+                 Param -> NAME\n\n\
+                 List<T> -> `(` T* `)`\n\n\
+                 Vis -> `pub`?\n\n\
+                 Struct -> `struct` NAME",
+            )
+            .snippet("lexer.md", "lexer", "NAME -> [`a`-`z`]+")
+            .load(&mut Diagnostics::new());
+        assert_eq!(query(&g, "kind:Cut"), ["Fn"]);
+        assert_eq!(
+            query(&g, "terminal:fn or terminal:struct"),
+            ["Fn", "Struct"]
+        );
+        assert_eq!(query(&g, "uses:NAME"), ["Fn", "Param", "Struct"]);
+        assert_eq!(query(&g, "uses:Param"), ["Fn"]);
+        assert_eq!(query(&g, "reaches:Param"), ["Item", "Fn"]);
+        assert_eq!(query(&g, "reachable-from:Fn"), ["Param", "List<T>", "NAME"]);
+        assert_eq!(query(&g, "first:pub"), ["Item", "Vis"]);
+        assert_eq!(query(&g, "first:NAME"), ["Param"]);
+        assert_eq!(query(&g, "first:x"), ["NAME"]);
+        assert_eq!(query(&g, "path:lexer.md"), ["NAME"]);
+        assert_eq!(query(&g, "category:lexer and not uses:NAME"), ["NAME"]);
+        assert_eq!(query(&g, "root or nullable"), ["Item", "Vis"]);
+        assert!(g.query(&"uses:Missing".parse().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,500 @@
+//! Detection of alternatives that are shadowed by an earlier alternative.
+//!
+//! The parser interpreter treats `A | B` as an ordered choice: `B` is only
+//! tried if `A` fails to match. If everything that `B` can match starts with
+//! something that `A` matches, then `B` is never used. For example, in
+//! ``(`..` | `..=`) Expression`` the `..=` alternative is never used, since
+//! `..` always matches first.
+//!
+//! This only handles alternatives that match a small, finite set of inputs,
+//! such as terminals and short sequences of them. In lexer productions, the
+//! inputs are compared character by character. In other productions, each
+//! terminal and token is a single unit, since the input has already been
+//! split into tokens.
+//!
+//! A negative lookahead just before an alternation, such as
+//! ``![`*`] (~[`/`] | `*` `x`)``, rules out the inputs it matches for every
+//! alternative, so those inputs are ignored when comparing them.
+//!
+//! An alternative with an `@edition(..)` annotation only shadows alternatives
+//! that apply in a subset of its editions. The keyword productions (such as
+//! `STRICT_KEYWORDS`) are not checked, since they are lists of words that are
+//! only compared with a whole token. A production that is intentionally
+//! written with a shadowed alternative can allow the check with
+//! `// lint-allow(shadowed-alternative)`.
+
+use crate::lint::is_allowed;
+use crate::{Edition, Expression, ExpressionKind, Grammar, KeywordClass, Production};
+use diagnostics::{Diagnostics, warn_or_err};
+
+/// Limit on the number of inputs an expression can match before giving up.
+const MAX_INPUTS: usize = 64;
+
+/// Limit on how deep to follow nonterminals.
+const MAX_DEPTH: u32 = 8;
+
+/// One element of the input.
+#[derive(Clone, Debug, PartialEq)]
+enum Unit {
+    /// A character (in lexer productions) or a terminal token.
+    Text(String),
+    /// A lexer token used by a syntax production.
+    Token(String),
+}
+
+/// A sequence of units that an expression can match.
+type Input = Vec<Unit>;
+
+/// The inputs that an expression matches.
+struct Inputs {
+    inputs: Vec<Input>,
+    /// Whether the inputs are only the start of what the expression
+    /// matches, because the rest couldn't be determined.
+    truncated: bool,
+    /// Whether these are exactly the inputs that the expression matches
+    /// with ordered choice.
+    ///
+    /// This is false for something like ``(`a` | `ab`) `c` `` which can't
+    /// match `abc`, since `a` is chosen without trying `ab`.
+    exact: bool,
+}
+
+/// Checks every alternation for alternatives that can never be used.
+pub(crate) fn check_shadowed_alternatives(grammar: &Grammar, diag: &mut Diagnostics) {
+    for name in &grammar.name_order {
+        let prod = &grammar.productions[name];
+        if prod.is_instance()
+            || KeywordClass::ALL
+                .iter()
+                .any(|class| class.production() == *name)
+            || is_allowed(prod, "shadowed-alternative")
+        {
+            continue;
+        }
+        let mut lookaheads = Vec::new();
+        prod.expression
+            .visit(&mut |e| find_lookaheads(e, &mut lookaheads));
+        prod.expression.visit(&mut |e| {
+            let ExpressionKind::Alt(es) = &e.kind else {
+                return;
+            };
+            let lookahead = lookaheads
+                .iter()
+                .find(|(alt, _)| std::ptr::eq(*alt, e))
+                .map(|(_, lookahead)| *lookahead);
+            let inputs: Vec<_> = es
+                .iter()
+                .map(|e| {
+                    let mut inputs = inputs(grammar, prod, e, 0)?;
+                    if let Some(lookahead) = lookahead {
+                        inputs
+                            .inputs
+                            .retain(|input| !rejected_by(grammar, prod, lookahead, input));
+                    }
+                    Some(inputs)
+                })
+                .collect();
+            for (later_i, later) in es.iter().enumerate() {
+                let Some(earlier) =
+                    (0..later_i).find(|&i| shadows(grammar, prod, es, &inputs, i, later_i))
+                else {
+                    continue;
+                };
+                warn_or_err!(
+                    diag,
+                    "{}: in `{name}`, alternative `{}` is never used, \
+                     because the earlier alternative `{}` always matches first",
+                    later.span,
+                    text(later),
+                    text(&es[earlier]),
+                );
+            }
+        });
+    }
+}
+
+/// Whether alternative `earlier` matches a prefix of every input that
+/// alternative `later` matches.
+fn shadows(
+    grammar: &Grammar,
+    prod: &Production,
+    es: &[Expression],
+    inputs: &[Option<Inputs>],
+    earlier: usize,
+    later: usize,
+) -> bool {
+    if !Edition::ALL
+        .iter()
+        .all(|&edition| !es[later].in_edition(edition) || es[earlier].in_edition(edition))
+    {
+        return false;
+    }
+    if text(&es[earlier]) == text(&es[later]) {
+        return true;
+    }
+    let Some(later) = &inputs[later] else {
+        return false;
+    };
+    // Everything the later alternative matches is ruled out by a lookahead,
+    // so it isn't used whatever the order.
+    if later.inputs.is_empty() {
+        return false;
+    }
+    // A single character from a set shadows anything starting with one of
+    // those characters.
+    if prod.category == "lexer"
+        && let Some(chars) = grammar.char_set(&es[earlier])
+    {
+        return later.inputs.iter().all(|input| match input.first() {
+            Some(Unit::Text(s)) => chars.contains(s.chars().next().unwrap()),
+            _ => false,
+        });
+    }
+    match &inputs[earlier] {
+        // An alternative that can match nothing is reported by
+        // `check_well_formed` instead.
+        Some(earlier) if earlier.inputs.iter().any(|input| input.is_empty()) => false,
+        Some(earlier) if earlier.exact && !earlier.truncated => later.inputs.iter().all(|input| {
+            earlier
+                .inputs
+                .iter()
+                .any(|prefix| input.starts_with(prefix))
+        }),
+        _ => false,
+    }
+}
+
+/// Records each alternation in the sequence `e` that comes right after a
+/// negative lookahead, along with the expression of the lookahead.
+fn find_lookaheads<'a>(e: &'a Expression, lookaheads: &mut Vec<(&'a Expression, &'a Expression)>) {
+    let ExpressionKind::Sequence(es) = &e.kind else {
+        return;
+    };
+    let mut lookahead = None;
+    for e in es {
+        if matches!(
+            e.kind,
+            ExpressionKind::Break(_) | ExpressionKind::Comment(_)
+        ) {
+            continue;
+        }
+        if let Some(lookahead) = lookahead
+            && let Some(alt) = leading_alt(e)
+        {
+            lookaheads.push((alt, lookahead));
+        }
+        lookahead = match &e.kind {
+            ExpressionKind::NegativeLookahead(inner) => Some(&**inner),
+            _ => None,
+        };
+    }
+}
+
+/// Returns the alternation that `e` starts with, looking through groups,
+/// cuts, and sequences.
+fn leading_alt(e: &Expression) -> Option<&Expression> {
+    match &e.kind {
+        ExpressionKind::Alt(_) => Some(e),
+        ExpressionKind::Grouped(e) | ExpressionKind::Cut(e) => leading_alt(e),
+        ExpressionKind::Sequence(es) => es
+            .iter()
+            .find(|e| {
+                !matches!(
+                    e.kind,
+                    ExpressionKind::Break(_) | ExpressionKind::Comment(_)
+                )
+            })
+            .and_then(leading_alt),
+        _ => None,
+    }
+}
+
+/// Whether a negative lookahead of `lookahead` rules out the input, because
+/// `lookahead` matches the start of it.
+fn rejected_by(
+    grammar: &Grammar,
+    prod: &Production,
+    lookahead: &Expression,
+    input: &Input,
+) -> bool {
+    if prod.category == "lexer"
+        && let Some(chars) = grammar.char_set(lookahead)
+    {
+        return match input.first() {
+            Some(Unit::Text(s)) => chars.contains(s.chars().next().unwrap()),
+            _ => false,
+        };
+    }
+    match inputs(grammar, prod, lookahead, 0) {
+        Some(la) if la.exact && !la.truncated => la
+            .inputs
+            .iter()
+            .any(|prefix| !prefix.is_empty() && input.starts_with(prefix)),
+        _ => false,
+    }
+}
+
+/// Returns the inputs that the expression can match, or `None` if there are
+/// too many or they can't be determined.
+///
+/// If only the start of a sequence can be determined, the inputs are
+/// truncated after it.
+fn inputs(grammar: &Grammar, prod: &Production, e: &Expression, depth: u32) -> Option<Inputs> {
+    if e.suffix.is_some() {
+        return None;
+    }
+    let single = |unit| {
+        Some(Inputs {
+            inputs: vec![vec![unit]],
+            truncated: false,
+            exact: true,
+        })
+    };
+    match &e.kind {
+        ExpressionKind::Grouped(e) => inputs(grammar, prod, e, depth),
+        ExpressionKind::Alt(es) => {
+            let mut result = Inputs {
+                inputs: Vec::new(),
+                truncated: false,
+                exact: true,
+            };
+            for e in es {
+                let alt = inputs(grammar, prod, e, depth)?;
+                result.truncated |= alt.truncated;
+                result.exact &= alt.exact;
+                result.inputs.extend(alt.inputs);
+            }
+            (result.inputs.len() <= MAX_INPUTS).then_some(result)
+        }
+        ExpressionKind::Sequence(es) => {
+            let es: Vec<_> = es
+                .iter()
+                .filter(|e| {
+                    !matches!(
+                        e.kind,
+                        ExpressionKind::Break(_) | ExpressionKind::Comment(_)
+                    )
+                })
+                .collect();
+            let mut result = Inputs {
+                inputs: vec![Vec::new()],
+                truncated: false,
+                exact: true,
+            };
+            for (i, e) in es.iter().enumerate() {
+                let Some(next) = inputs(grammar, prod, e, depth) else {
+                    if i == 0 {
+                        return None;
+                    }
+                    result.truncated = true;
+                    break;
+                };
+                // Ordered choice commits to the first match of each element,
+                // so the combination is only exact if no element (other than
+                // the last) matches a prefix of another of its inputs.
+                let is_last = i == es.len() - 1;
+                result.exact &= next.exact && (is_last || prefix_free(&next.inputs));
+                let mut combined = Vec::new();
+                for prefix in &result.inputs {
+                    for suffix in &next.inputs {
+                        combined.push(prefix.iter().chain(suffix).cloned().collect());
+                    }
+                }
+                if combined.len() > MAX_INPUTS {
+                    return None;
+                }
+                result.inputs = combined;
+                if next.truncated {
+                    result.truncated = true;
+                    break;
+                }
+            }
+            Some(result)
+        }
+        ExpressionKind::Optional(e) => {
+            let mut result = inputs(grammar, prod, e, depth)?;
+            result.inputs.push(Vec::new());
+            Some(result)
+        }
+        ExpressionKind::Nt(nt) => {
+            let nt_prod = grammar.productions.get(nt)?;
+            if prod.category != "lexer" && nt_prod.category == "lexer" {
+                return single(Unit::Token(nt.clone()));
+            }
+            if depth >= MAX_DEPTH {
+                return None;
+            }
+            inputs(grammar, nt_prod, &nt_prod.expression, depth + 1)
+        }
+        ExpressionKind::Terminal(s) if prod.category == "lexer" => Some(Inputs {
+            inputs: vec![s.chars().map(|ch| Unit::Text(ch.to_string())).collect()],
+            truncated: false,
+            exact: true,
+        }),
+        ExpressionKind::Terminal(s) => single(Unit::Text(s.clone())),
+        ExpressionKind::Unicode((ch, _)) => single(Unit::Text(ch.to_string())),
+        _ => None,
+    }
+}
+
+/// Whether none of the inputs is a prefix of another.
+fn prefix_free(inputs: &[Input]) -> bool {
+    inputs.iter().enumerate().all(|(i, a)| {
+        inputs
+            .iter()
+            .enumerate()
+            .all(|(j, b)| i == j || !b.starts_with(a))
+    })
+}
+
+/// The text of an alternative, as written on a single line.
+fn text(e: &Expression) -> String {
+    e.to_string()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_shadowed_alternatives;
+    use crate::Grammar;
+    use diagnostics::Diagnostics;
+
+    /// Returns the shadowed alternatives that are reported, each with the
+    /// earlier alternative that shadows it.
+    fn shadowed(input: &str, category: &str) -> Vec<(String, String)> {
+        let g = Grammar::grammar_from_str(input, category).unwrap();
+        let mut diag = Diagnostics::capture();
+        check_shadowed_alternatives(&g, &mut diag);
+        diag.messages()
+            .iter()
+            .map(|msg| {
+                let (_, rest) = msg.split_once(", alternative `").unwrap();
+                let (later, rest) = rest.split_once("` is never used").unwrap();
+                let (_, earlier) = rest.split_once("earlier alternative `").unwrap();
+                let earlier = earlier.strip_suffix("` always matches first").unwrap();
+                (later.to_string(), earlier.to_string())
+            })
+            .collect()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(later, earlier)| (later.to_string(), earlier.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn message() {
+        let g = Grammar::grammar_from_str("A -> `..` | `..=`", "lexer").unwrap();
+        let mut diag = Diagnostics::capture();
+        check_shadowed_alternatives(&g, &mut diag);
+        assert_eq!(
+            diag.messages(),
+            [":1:13: in `A`, alternative ``..=`` is never used, \
+             because the earlier alternative ``..`` always matches first"]
+        );
+    }
+
+    #[test]
+    fn shadowed_alternatives() {
+        assert_eq!(
+            shadowed("A -> `..` | `..=`", "lexer"),
+            pairs(&[("`..=`", "`..`")])
+        );
+        assert_eq!(shadowed("A -> `..=` | `..`", "lexer"), pairs(&[]));
+        assert_eq!(
+            shadowed("A -> [`a`-`z`] | `b` `c`", "lexer"),
+            pairs(&[("`b` `c`", "[`a`-`z`]")])
+        );
+        assert_eq!(
+            shadowed("A -> B | `x` `y`\n\nB -> `x` | `z`", "lexer"),
+            pairs(&[("`x` `y`", "B")])
+        );
+        assert_eq!(shadowed("A -> B | B", "items"), pairs(&[("B", "B")]));
+        // `a` is always chosen inside the group, so the first alternative
+        // can't match `abc`. Only `ab` is reported.
+        assert_eq!(
+            shadowed("A -> (`a` | `ab`) `c` | `a` `b` `c`", "lexer"),
+            pairs(&[("`ab`", "`a`")])
+        );
+        // Only the start of the later alternative needs to be known.
+        assert_eq!(
+            shadowed("A -> `pub` | `pub` `(` B* `)`", "items"),
+            pairs(&[("`pub` `(` B* `)`", "`pub`")])
+        );
+        // Each alternative is reported once, with the first one that
+        // shadows it.
+        assert_eq!(
+            shadowed("A -> `a` | `a` | `a` `b`", "lexer"),
+            pairs(&[("`a`", "`a`"), ("`a` `b`", "`a`")])
+        );
+    }
+
+    #[test]
+    fn tokens() {
+        // In syntax productions, each terminal is a separate token.
+        assert_eq!(shadowed("A -> `..` | `..=`", "items"), pairs(&[]));
+        assert_eq!(
+            shadowed("A -> `..` | `..` `=`", "items"),
+            pairs(&[("`..` `=`", "`..`")])
+        );
+    }
+
+    #[test]
+    fn editions() {
+        // `a` doesn't apply in 2015, where `a` `b` can still be used.
+        assert_eq!(
+            shadowed("A -> @edition(2018..) `a` | `a` `b`", "lexer"),
+            pairs(&[])
+        );
+        assert_eq!(
+            shadowed("A -> `a` | @edition(2018..) `a` `b`", "lexer"),
+            pairs(&[("@edition(2018..) `a` `b`", "`a`")])
+        );
+        assert_eq!(
+            shadowed(
+                "A -> @edition(2018..) `a` | @edition(2021..) `a` `b`",
+                "lexer"
+            ),
+            pairs(&[("@edition(2021..) `a` `b`", "@edition(2018..) `a`")])
+        );
+    }
+
+    #[test]
+    fn lookahead() {
+        // The lookahead rules out `/`, so `~[`*`]` can't shadow `B`.
+        assert_eq!(
+            shadowed("A -> `a` ![`*` `/`] (~[`*`] | B)\n\nB -> `/` `*`", "lexer"),
+            pairs(&[])
+        );
+        assert_eq!(
+            shadowed("A -> `a` ![`*`] (~[`*`] | B)\n\nB -> `/` `*`", "lexer"),
+            pairs(&[("B", "~[`*`]")])
+        );
+        // Only the inputs that the lookahead allows are compared.
+        assert_eq!(
+            shadowed("A -> !`x` (`a` | `a` `b` | `x` `y`)", "items"),
+            pairs(&[("`a` `b`", "`a`")])
+        );
+    }
+
+    #[test]
+    fn keywords_and_allow() {
+        // Keywords are compared with a whole token, so `as` doesn't hide
+        // `async`.
+        assert_eq!(
+            shadowed("STRICT_KEYWORDS -> `as` | `async`", "lexer"),
+            pairs(&[])
+        );
+        assert_eq!(
+            shadowed(
+                "// lint-allow(shadowed-alternative)\nA -> `pub` | `pub` `(` `crate` `)`",
+                "items"
+            ),
+            pairs(&[])
+        );
+    }
+}
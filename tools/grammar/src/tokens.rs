@@ -0,0 +1,189 @@
+//! Tables of keywords and punctuation derived from the grammar.
+//!
+//! The keywords are listed in `STRICT_KEYWORDS`, `RESERVED_KEYWORDS`, and
+//! `WEAK_KEYWORDS`, and the punctuation in `PUNCTUATION`. An alternative
+//! that was added in a later edition has an annotation like
+//! `@edition(2018..)`.
+
+use crate::{Edition, EditionRange, Expression, ExpressionKind, Grammar, keyword_production};
+
+/// The kinds of keywords.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KeywordClass {
+    Strict,
+    Reserved,
+    Weak,
+}
+
+impl KeywordClass {
+    pub const ALL: &[KeywordClass] = &[
+        KeywordClass::Strict,
+        KeywordClass::Reserved,
+        KeywordClass::Weak,
+    ];
+
+    /// The name of the class, such as `strict`.
+    pub fn name(self) -> &'static str {
+        match self {
+            KeywordClass::Strict => "strict",
+            KeywordClass::Reserved => "reserved",
+            KeywordClass::Weak => "weak",
+        }
+    }
+
+    /// The name of the production listing the keywords of this class.
+    pub fn production(self) -> String {
+        keyword_production(self.name())
+    }
+}
+
+/// A keyword listed in the grammar.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keyword {
+    pub text: String,
+    pub class: KeywordClass,
+    /// The editions the keyword is in, if it isn't in every edition.
+    pub edition: Option<EditionRange>,
+}
+
+impl Keyword {
+    /// Whether this is a keyword in the given edition.
+    pub fn in_edition(&self, edition: Edition) -> bool {
+        self.edition.is_none_or(|range| range.contains(edition))
+    }
+}
+
+/// The keywords and punctuation tokens of the language.
+#[derive(Clone, Debug, Default)]
+pub struct TokenTables {
+    /// Every keyword, in the order they are listed in the grammar.
+    pub keywords: Vec<Keyword>,
+    /// Every punctuation token, in the order they are listed in the grammar.
+    pub punctuation: Vec<String>,
+}
+
+impl TokenTables {
+    /// Returns the keyword with the given text.
+    pub fn keyword(&self, text: &str) -> Option<&Keyword> {
+        self.keywords.iter().find(|keyword| keyword.text == text)
+    }
+
+    /// Returns the keywords of the given class that apply to an edition.
+    pub fn keywords(
+        &self,
+        class: KeywordClass,
+        edition: Edition,
+    ) -> impl Iterator<Item = &Keyword> {
+        self.keywords
+            .iter()
+            .filter(move |keyword| keyword.class == class && keyword.in_edition(edition))
+    }
+
+    pub fn is_punctuation(&self, s: &str) -> bool {
+        self.punctuation.iter().any(|p| p == s)
+    }
+
+    /// Returns the punctuation tokens with more than one character, which
+    /// may need to be split into smaller tokens.
+    pub fn multi_char_punctuation(&self) -> impl Iterator<Item = &str> {
+        self.punctuation
+            .iter()
+            .map(String::as_str)
+            .filter(|p| p.chars().count() > 1)
+    }
+}
+
+impl Grammar {
+    /// Extracts the keyword and punctuation tables from the productions that
+    /// list them.
+    ///
+    /// Productions that are missing from the grammar are treated as empty.
+    pub fn token_tables(&self) -> TokenTables {
+        let mut tables = TokenTables::default();
+        for &class in KeywordClass::ALL {
+            for (text, edition) in self.terminals(&class.production()) {
+                tables.keywords.push(Keyword {
+                    text,
+                    class,
+                    edition,
+                });
+            }
+        }
+        tables.punctuation = self
+            .terminals("PUNCTUATION")
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect();
+        tables
+    }
+
+    /// Returns the terminals listed in a production, with the editions of
+    /// the alternative each one is in.
+    fn terminals(&self, name: &str) -> Vec<(String, Option<EditionRange>)> {
+        let mut terminals = Vec::new();
+        if let Some(prod) = self.productions.get(name) {
+            collect_terminals(&prod.expression, None, &mut terminals);
+        }
+        terminals
+    }
+}
+
+fn collect_terminals(
+    e: &Expression,
+    edition: Option<EditionRange>,
+    terminals: &mut Vec<(String, Option<EditionRange>)>,
+) {
+    let edition = e.edition.or(edition);
+    match &e.kind {
+        ExpressionKind::Terminal(s) => terminals.push((s.clone(), edition)),
+        ExpressionKind::Sequence(es) => {
+            for e in es {
+                collect_terminals(e, edition, terminals);
+            }
+        }
+        ExpressionKind::Alt(es) => {
+            for e in es {
+                collect_terminals(e, edition, terminals);
+            }
+        }
+        ExpressionKind::Grouped(e) => collect_terminals(e, edition, terminals),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeywordClass;
+    use crate::{Edition, Grammar};
+
+    #[test]
+    fn tables() {
+        let g = Grammar::grammar_from_str(
+            "STRICT_KEYWORDS ->\n      `as`\n    | @edition(2018..) `dyn`\n\n\
+             WEAK_KEYWORDS -> `union`\n\n\
+             PUNCTUATION -> `::` | `:` | `..=`",
+            "lexer",
+        )
+        .unwrap();
+        let tables = g.token_tables();
+        let dyn_ = tables.keyword("dyn").unwrap();
+        assert_eq!(dyn_.class, KeywordClass::Strict);
+        assert!(!dyn_.in_edition(Edition::Edition2015));
+        assert!(dyn_.in_edition(Edition::Edition2024));
+        assert_eq!(tables.keyword("as").unwrap().edition, None);
+        assert_eq!(tables.keyword("union").unwrap().class, KeywordClass::Weak);
+        let strict = |edition| {
+            tables
+                .keywords(KeywordClass::Strict, edition)
+                .map(|k| k.text.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(strict(Edition::Edition2015), ["as"]);
+        assert_eq!(strict(Edition::Edition2021), ["as", "dyn"]);
+        assert!(tables.is_punctuation(":"));
+        assert_eq!(
+            tables.multi_char_punctuation().collect::<Vec<_>>(),
+            ["::", "..="]
+        );
+    }
+}
@@ -0,0 +1,750 @@
+//! Generation of a [tree-sitter] grammar from the Reference grammar.
+//!
+//! Productions in the `lexer` category are turned into tokens, and all
+//! other productions become regular rules. Lexer productions that are only
+//! used by other lexer productions are inlined into the tokens that use
+//! them, since tree-sitter tokens cannot refer to other rules.
+//!
+//! Some constructs cannot be expressed in tree-sitter. Productions that use
+//! named repetitions, unknown prose, or recursive tokens (such as nested
+//! block comments) are declared as `externals`, and need to be implemented
+//! by hand in an external scanner. Other constructs, such as negative
+//! lookahead and suffixes, are dropped and listed as approximations.
+//!
+//! Productions with parameters become a rule for each of their instances,
+//! with names such as `CommaList_Expression` for `CommaList<Expression>`.
+//!
+//! [tree-sitter]: https://tree-sitter.github.io/
+
+use crate::charset::CharSet;
+use crate::parameters::identifier;
+use crate::{EditionRange, Expression, ExpressionKind, Grammar, Production, RangeLimit, Span};
+use std::collections::{HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
+
+/// The production used as the start rule, if it exists.
+const START: &str = "Crate";
+
+/// The production used for keyword extraction, if it exists.
+const WORD: &str = "IDENTIFIER_OR_KEYWORD";
+
+/// Productions that may appear anywhere between tokens, if they exist.
+const EXTRAS: &[&str] = &["WHITESPACE", "COMMENT"];
+
+/// Bounded repetitions with more than this many repeats are approximated
+/// with an unbounded repetition instead of being expanded.
+const MAX_EXPANSION: u32 = 8;
+
+/// Line width that the generated JavaScript tries to stay within.
+const WIDTH: usize = 100;
+
+/// The result of converting the grammar with [`Grammar::to_tree_sitter`].
+pub struct TreeSitterGrammar {
+    /// The contents of `grammar.js`.
+    pub grammar_js: String,
+    /// Productions that could not be translated, and are declared as
+    /// `externals` that must be implemented in an external scanner.
+    pub externals: Vec<TreeSitterIssue>,
+    /// Places where the generated grammar is less precise than the
+    /// Reference.
+    pub approximations: Vec<TreeSitterIssue>,
+}
+
+/// A production that could not be translated exactly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeSitterIssue {
+    pub production: String,
+    /// The location of the construct that could not be translated.
+    pub span: Span,
+    pub reason: String,
+}
+
+impl Display for TreeSitterIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}: {}: {}", self.span, self.production, self.reason)
+    }
+}
+
+impl TreeSitterGrammar {
+    /// Returns a human-readable report of the productions that could not be
+    /// translated exactly.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        let sections = [
+            (
+                "Productions that must be implemented in an external scanner",
+                &self.externals,
+            ),
+            (
+                "Approximations in the generated grammar",
+                &self.approximations,
+            ),
+        ];
+        for (title, issues) in sections {
+            report.push_str(&format!("{title} ({}):\n", issues.len()));
+            for issue in issues {
+                report.push_str(&format!("  {issue}\n"));
+            }
+            report.push('\n');
+        }
+        report
+    }
+}
+
+impl Grammar {
+    /// Converts the grammar to a tree-sitter `grammar.js` with the given
+    /// language name.
+    ///
+    /// The start rule is `Crate`. Only productions reachable from it (or
+    /// from whitespace and comments) are included.
+    pub fn to_tree_sitter(&self, name: &str) -> TreeSitterGrammar {
+        let mut generator = Generator {
+            grammar: self,
+            queue: VecDeque::new(),
+            seen: HashSet::new(),
+            rules: Vec::new(),
+            externals: Vec::new(),
+            approximations: Vec::new(),
+            inline_stack: Vec::new(),
+            notes: Vec::new(),
+        };
+        let start = if self.productions.contains_key(START) {
+            Some(START)
+        } else {
+            self.name_order
+                .iter()
+                .map(|name| &self.productions[name])
+                .find(|p| p.is_root && p.category != "lexer")
+                .map(|p| p.name.as_str())
+        };
+        let extras: Vec<_> = EXTRAS
+            .iter()
+            .copied()
+            .filter(|name| self.productions.contains_key(*name))
+            .collect();
+        for name in start.iter().chain(&extras) {
+            generator.enqueue(name);
+        }
+        while let Some(name) = generator.queue.pop_front() {
+            generator.production(&self.productions[name]);
+        }
+        let word = generator
+            .rules
+            .iter()
+            .any(|(name, _)| *name == WORD)
+            .then_some(WORD);
+        for issues in [&mut generator.externals, &mut generator.approximations] {
+            issues.sort_by(|a, b| {
+                (&a.production, a.span.range.start).cmp(&(&b.production, b.span.range.start))
+            });
+        }
+        let grammar_js = generator.grammar_js(name, &extras, word);
+        TreeSitterGrammar {
+            grammar_js,
+            externals: generator.externals,
+            approximations: generator.approximations,
+        }
+    }
+}
+
+/// A JavaScript expression in a tree-sitter grammar.
+#[derive(Clone, Debug, PartialEq)]
+enum Js {
+    /// A call to a DSL function, such as `seq` or `choice`.
+    Call(&'static str, Vec<Js>),
+    String(String),
+    Regex(String),
+    /// `$.name`
+    Rule(String),
+}
+
+struct Generator<'a> {
+    grammar: &'a Grammar,
+    /// Productions that still need to be translated.
+    queue: VecDeque<&'a str>,
+    /// Productions that have been queued.
+    seen: HashSet<&'a str>,
+    /// The translated rules, in the order they were translated.
+    rules: Vec<(&'a str, Js)>,
+    externals: Vec<TreeSitterIssue>,
+    approximations: Vec<TreeSitterIssue>,
+    /// Lexer productions currently being inlined into a token.
+    inline_stack: Vec<&'a str>,
+    /// Approximations made while translating the current production.
+    notes: Vec<TreeSitterIssue>,
+}
+
+/// The result of translating an expression.
+///
+/// `Ok(None)` is an expression that matches the empty string, such as a
+/// negative lookahead that was dropped.
+type Translation = Result<Option<Js>, TreeSitterIssue>;
+
+impl<'a> Generator<'a> {
+    fn enqueue(&mut self, name: &'a str) {
+        if self.seen.insert(name) {
+            self.queue.push_back(name);
+        }
+    }
+
+    fn production(&mut self, prod: &'a Production) {
+        self.notes.clear();
+        self.edition(prod, &prod.expression, prod.edition);
+        let result = if prod.category == "lexer" {
+            self.token_rule(prod)
+        } else {
+            self.expr(prod, &prod.expression)
+        };
+        match result {
+            Ok(js) => {
+                let js = js.unwrap_or_else(|| Js::Call("blank", Vec::new()));
+                self.rules.push((&prod.name, js));
+                for note in self.notes.drain(..) {
+                    if !self.approximations.contains(&note) {
+                        self.approximations.push(note);
+                    }
+                }
+            }
+            Err(issue) => self.externals.push(issue),
+        }
+    }
+
+    /// Translates a lexer production that is used as a rule.
+    fn token_rule(&mut self, prod: &'a Production) -> Translation {
+        // A production that is only a choice of other lexer productions,
+        // such as `IDENTIFIER`, refers to the other tokens instead of
+        // inlining them.
+        let es: Vec<_> = match &prod.expression.kind {
+            ExpressionKind::Alt(es) => es.iter().collect(),
+            _ => vec![&prod.expression],
+        };
+        let nts: Option<Vec<_>> = es
+            .iter()
+            .map(|e| match &without_comments(e).kind {
+                ExpressionKind::Nt(nt) if self.is_lexer(nt) => Some((*e, nt)),
+                _ => None,
+            })
+            .collect();
+        if let Some(nts) = nts {
+            let mut choices = Vec::new();
+            for (e, nt) in nts {
+                self.suffix(prod, without_comments(e));
+                self.enqueue(nt);
+                choices.push(Js::Rule(nt.clone()));
+            }
+            return Ok(Some(choice(choices)));
+        }
+        self.inline_stack.push(&prod.name);
+        let result = self.expr(prod, &prod.expression);
+        self.inline_stack.pop();
+        Ok(result?.map(|js| Js::Call("token", vec![js])))
+    }
+
+    fn expr(&mut self, prod: &'a Production, e: &'a Expression) -> Translation {
+        self.suffix(prod, e);
+        let js = match &e.kind {
+            ExpressionKind::Grouped(e) => self.expr(prod, e)?,
+            ExpressionKind::Alt(es) => {
+                let mut choices = Vec::new();
+                let mut empty = false;
+                for e in es {
+                    match self.expr(prod, e)? {
+                        Some(js) => choices.push(js),
+                        None => empty = true,
+                    }
+                }
+                if empty {
+                    choices.push(Js::Call("blank", Vec::new()));
+                }
+                Some(choice(choices))
+            }
+            ExpressionKind::Sequence(es) => {
+                let mut items = Vec::new();
+                for e in es {
+                    items.extend(self.expr(prod, e)?);
+                }
+                seq(items)
+            }
+            ExpressionKind::Optional(e) => self.expr(prod, e)?.map(|js| call("optional", js)),
+            ExpressionKind::Repeat(e) => self.expr(prod, e)?.map(|js| call("repeat", js)),
+            ExpressionKind::RepeatPlus(e) => self.expr(prod, e)?.map(|js| call("repeat1", js)),
+            ExpressionKind::RepeatRange {
+                expr,
+                min,
+                max,
+                limit,
+                ..
+            } => {
+                let Some(js) = self.expr(prod, expr)? else {
+                    return Ok(None);
+                };
+                self.repeat_range(prod, e, js, *min, *max, *limit)
+            }
+            ExpressionKind::RepeatRangeNamed(_, name) => {
+                return Err(issue(
+                    prod,
+                    e,
+                    format!("repetition count `{name}` depends on an earlier repetition"),
+                ));
+            }
+            ExpressionKind::NegativeLookahead(inner) => {
+                self.note(
+                    prod,
+                    e,
+                    format!("negative lookahead `!{inner}` is not enforced"),
+                );
+                None
+            }
+            ExpressionKind::Nt(nt) => return self.nt(prod, e, nt),
+            ExpressionKind::Terminal(s) => Some(Js::String(s.clone())),
+            ExpressionKind::Prose(s) => match s.as_str() {
+                "`XID_Start` defined by Unicode" => Some(Js::Regex(r"\p{XID_Start}".to_string())),
+                "`XID_Continue` defined by Unicode" => {
+                    Some(Js::Regex(r"\p{XID_Continue}".to_string()))
+                }
+                _ => {
+                    return Err(issue(
+                        prod,
+                        e,
+                        format!("prose `<{s}>` cannot be translated"),
+                    ));
+                }
+            },
+            ExpressionKind::Break(_) | ExpressionKind::Comment(_) => None,
+            ExpressionKind::Charset(es) => match self.grammar.char_set(e) {
+                Some(chars) => Some(char_class(&chars, false)),
+                None => {
+                    let mut choices = Vec::new();
+                    for e in es {
+                        choices.extend(self.expr(prod, e)?);
+                    }
+                    Some(choice(choices))
+                }
+            },
+            ExpressionKind::CharacterRange(start, end) => Some(char_class(
+                &CharSet::from_range(start.get_ch() as u32, end.get_ch() as u32),
+                false,
+            )),
+            ExpressionKind::NegExpression(inner) => match self.grammar.char_set(inner) {
+                Some(chars) => Some(char_class(&chars, true)),
+                None => {
+                    return Err(issue(
+                        prod,
+                        e,
+                        format!("the characters matched by `~{inner}` cannot be determined"),
+                    ));
+                }
+            },
+            // tree-sitter does not backtrack like a PEG parser, so a cut
+            // has no effect.
+            ExpressionKind::Cut(e) => self.expr(prod, e)?,
+            ExpressionKind::Unicode((ch, _)) => Some(Js::String(ch.to_string())),
+        };
+        Ok(js)
+    }
+
+    fn nt(&mut self, prod: &'a Production, e: &'a Expression, nt: &'a str) -> Translation {
+        let Some(nt_prod) = self.grammar.productions.get(nt) else {
+            return Err(issue(prod, e, format!("`{nt}` is not defined")));
+        };
+        if self.inline_stack.is_empty() {
+            self.enqueue(nt);
+            return Ok(Some(Js::Rule(nt.to_string())));
+        }
+        // Inside a token, other productions must be inlined.
+        if nt_prod.category != "lexer" {
+            return Err(issue(
+                prod,
+                e,
+                format!("token refers to non-lexer production `{nt}`"),
+            ));
+        }
+        if self.inline_stack.contains(&nt) {
+            return Err(issue(
+                prod,
+                e,
+                format!("tokens cannot be recursive, but `{nt}` refers to itself"),
+            ));
+        }
+        self.edition(nt_prod, &nt_prod.expression, nt_prod.edition);
+        self.inline_stack.push(nt);
+        let result = self.expr(nt_prod, &nt_prod.expression);
+        self.inline_stack.pop();
+        result.map_err(|inner| {
+            if inner.production == prod.name {
+                return inner;
+            }
+            // Only mention the innermost problem.
+            let reason = match inner.reason.split_once("`: ") {
+                Some((_, reason)) if inner.reason.starts_with("uses `") => reason,
+                _ => &inner.reason,
+            };
+            issue(prod, e, format!("uses `{nt}`: {reason}"))
+        })
+    }
+
+    fn repeat_range(
+        &mut self,
+        prod: &'a Production,
+        e: &'a Expression,
+        js: Js,
+        min: Option<u32>,
+        max: Option<u32>,
+        limit: RangeLimit,
+    ) -> Option<Js> {
+        let lo = min.unwrap_or(0);
+        let hi = max.map(|max| match limit {
+            RangeLimit::HalfOpen => max.saturating_sub(1),
+            RangeLimit::Closed => max,
+        });
+        if lo > MAX_EXPANSION || hi.is_some_and(|hi| hi - lo.min(hi) > MAX_EXPANSION) {
+            self.note(prod, e, "repetition bounds are not enforced".to_string());
+            return Some(call(if lo == 0 { "repeat" } else { "repeat1" }, js));
+        }
+        let mut items = vec![js.clone(); lo as usize];
+        match hi {
+            None => items.push(call("repeat", js)),
+            Some(hi) => {
+                // `A{0..=2}` is `optional(seq(A, optional(A)))`.
+                let mut rest = None;
+                for _ in lo..hi {
+                    let inner = seq(std::iter::once(js.clone()).chain(rest).collect());
+                    rest = inner.map(|inner| call("optional", inner));
+                }
+                items.extend(rest);
+            }
+        }
+        seq(items)
+    }
+
+    fn suffix(&mut self, prod: &'a Production, e: &'a Expression) {
+        if let Some(suffix) = &e.suffix {
+            self.note(prod, e, format!("suffix `{suffix}` is not enforced"));
+        }
+        self.edition(prod, e, e.edition);
+    }
+
+    /// Notes that an `@edition(..)` annotation is ignored, since the
+    /// generated grammar is for every edition.
+    fn edition(&mut self, prod: &Production, e: &Expression, edition: Option<EditionRange>) {
+        if let Some(edition) = edition {
+            self.note(prod, e, format!("edition `{edition}` is not enforced"));
+        }
+    }
+
+    fn note(&mut self, prod: &Production, e: &Expression, reason: String) {
+        self.notes.push(issue(prod, e, reason));
+    }
+
+    fn is_lexer(&self, name: &str) -> bool {
+        self.grammar
+            .productions
+            .get(name)
+            .is_some_and(|p| p.category == "lexer")
+    }
+
+    fn grammar_js(&self, name: &str, extras: &[&str], word: Option<&str>) -> String {
+        let mut out = String::new();
+        out.push_str(
+            "// Generated from the Rust Reference grammar by `grammar-check tree-sitter`.\n\n",
+        );
+        out.push_str("module.exports = grammar({\n");
+        out.push_str(&format!("  name: {},\n\n", js_string(name)));
+        let list = |names: Vec<&str>| -> String {
+            names
+                .iter()
+                .map(|name| format!("    $.{},\n", identifier(name)))
+                .collect()
+        };
+        let extras: Vec<_> = extras
+            .iter()
+            .copied()
+            .filter(|name| self.is_defined(name))
+            .collect();
+        out.push_str(&format!("  extras: $ => [\n{}  ],\n\n", list(extras)));
+        if !self.externals.is_empty() {
+            let externals: Vec<_> = self
+                .externals
+                .iter()
+                .map(|issue| issue.production.as_str())
+                .collect();
+            out.push_str(&format!("  externals: $ => [\n{}  ],\n\n", list(externals)));
+        }
+        if let Some(word) = word {
+            out.push_str(&format!("  word: $ => $.{word},\n\n"));
+        }
+        out.push_str("  rules: {\n");
+        for (name, js) in &self.rules {
+            let prefix = format!("    {}: $ => ", identifier(name));
+            out.push_str(&prefix);
+            js.write(&mut out, 4, prefix.len());
+            out.push_str(",\n");
+        }
+        out.push_str("  },\n});\n");
+        out
+    }
+
+    /// Whether the production is either a rule or an external.
+    fn is_defined(&self, name: &str) -> bool {
+        self.rules.iter().any(|(n, _)| *n == name)
+            || self.externals.iter().any(|issue| issue.production == name)
+    }
+}
+
+impl Js {
+    /// Writes the expression, splitting it across lines if it doesn't fit.
+    ///
+    /// `indent` is the indentation of the current line, and `column` is
+    /// where the expression starts.
+    fn write(&self, out: &mut String, indent: usize, column: usize) {
+        let inline = self.to_string();
+        match self {
+            Js::Call(name, args) if column + inline.len() > WIDTH && !args.is_empty() => {
+                out.push_str(name);
+                out.push_str("(\n");
+                for arg in args {
+                    out.push_str(&" ".repeat(indent + 2));
+                    arg.write(out, indent + 2, indent + 2);
+                    out.push_str(",\n");
+                }
+                out.push_str(&" ".repeat(indent));
+                out.push(')');
+            }
+            _ => out.push_str(&inline),
+        }
+    }
+}
+
+impl Display for Js {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Js::Call(name, args) => {
+                write!(f, "{name}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                write!(f, ")")
+            }
+            Js::String(s) => write!(f, "{}", js_string(s)),
+            Js::Regex(r) => write!(f, "/{r}/"),
+            Js::Rule(name) => write!(f, "$.{}", identifier(name)),
+        }
+    }
+}
+
+fn call(name: &'static str, js: Js) -> Js {
+    Js::Call(name, vec![js])
+}
+
+/// Returns a `seq` of the items, or `None` if there are no items.
+fn seq(items: Vec<Js>) -> Option<Js> {
+    let mut flat = Vec::new();
+    for item in items {
+        match item {
+            Js::Call("seq", inner) => flat.extend(inner),
+            item => flat.push(item),
+        }
+    }
+    match flat.len() {
+        0 => None,
+        1 => flat.pop(),
+        _ => Some(Js::Call("seq", flat)),
+    }
+}
+
+fn choice(mut choices: Vec<Js>) -> Js {
+    if choices.len() == 1 {
+        choices.pop().unwrap()
+    } else {
+        Js::Call("choice", choices)
+    }
+}
+
+fn issue(prod: &Production, e: &Expression, reason: String) -> TreeSitterIssue {
+    // Synthetic expressions don't have a span.
+    let span = if e.span.path.as_os_str().is_empty() && e.span.range.is_empty() {
+        prod.span.clone()
+    } else {
+        e.span.clone()
+    };
+    TreeSitterIssue {
+        production: prod.name.clone(),
+        span,
+        reason,
+    }
+}
+
+/// Skips over comments and breaks around a single expression.
+fn without_comments(e: &Expression) -> &Expression {
+    if let ExpressionKind::Sequence(es) = &e.kind {
+        let mut es = es.iter().filter(|e| {
+            !matches!(
+                e.kind,
+                ExpressionKind::Break(_) | ExpressionKind::Comment(_)
+            )
+        });
+        if let (Some(e), None) = (es.next(), es.next()) {
+            return e;
+        }
+    }
+    e
+}
+
+/// Returns a regular expression matching a single character in (or not in)
+/// the given set.
+fn char_class(chars: &CharSet, negated: bool) -> Js {
+    fn class_char(ch: u32) -> String {
+        match char::from_u32(ch) {
+            Some(c) if c.is_ascii_alphanumeric() || c == '_' => c.to_string(),
+            _ if ch <= 0xFFFF => format!("\\u{ch:04X}"),
+            _ => format!("\\u{{{ch:X}}}"),
+        }
+    }
+    if negated && chars.is_empty() {
+        return char_class(&CharSet::all(), false);
+    }
+    if negated && chars.complement().is_empty() {
+        // Matches nothing, which tree-sitter can't express directly.
+        return Js::Regex("[^\\u0000-\\u{10FFFF}]".to_string());
+    }
+    let mut class = String::from("[");
+    if negated {
+        class.push('^');
+    }
+    for (start, end) in chars.ranges() {
+        class.push_str(&class_char(*start));
+        if start != end {
+            class.push('-');
+            class.push_str(&class_char(*end));
+        }
+    }
+    class.push(']');
+    Js::Regex(class)
+}
+
+/// Returns a JavaScript string literal.
+fn js_string(s: &str) -> String {
+    let mut out = String::from("'");
+    for ch in s.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '\'' => out.push_str("\\'"),
+            ' '..='~' => out.push(ch),
+            _ if (ch as u32) <= 0xFFFF => out.push_str(&format!("\\u{:04X}", ch as u32)),
+            _ => out.push_str(&format!("\\u{{{:X}}}", ch as u32)),
+        }
+    }
+    out.push('\'');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Grammar;
+
+    fn grammar(syntax: &str, lexer: &str) -> Grammar {
+        let mut grammar = Grammar::grammar_from_str(syntax, "items").unwrap();
+        crate::parser::parse_grammar(
+            lexer,
+            0..lexer.len(),
+            &mut grammar,
+            "lexer",
+            std::path::Path::new("lexer.md"),
+        )
+        .unwrap();
+        grammar
+    }
+
+    #[test]
+    fn translate() {
+        let g = grammar(
+            "@root Crate -> Item*\n\nItem -> `fn` IDENTIFIER `(` `)` ^ `{` `}` | `mod` IDENTIFIER? `;`",
+            "IDENTIFIER -> IDENTIFIER_OR_KEYWORD _except a keyword_ | RAW_IDENTIFIER\n\n\
+             IDENTIFIER_OR_KEYWORD -> (XID_Start | `_`) XID_Continue* !`'`\n\n\
+             RAW_IDENTIFIER -> `r#` IDENTIFIER_OR_KEYWORD\n\n\
+             XID_Start -> <`XID_Start` defined by Unicode>\n\n\
+             XID_Continue -> <`XID_Continue` defined by Unicode>\n\n\
+             WHITESPACE -> [U+0009-U+000A `_`]",
+        );
+        let ts = g.to_tree_sitter("rust");
+        assert_eq!(
+            ts.grammar_js,
+            r#"// Generated from the Rust Reference grammar by `grammar-check tree-sitter`.
+
+module.exports = grammar({
+  name: 'rust',
+
+  extras: $ => [
+    $.WHITESPACE,
+  ],
+
+  word: $ => $.IDENTIFIER_OR_KEYWORD,
+
+  rules: {
+    Crate: $ => repeat($.Item),
+    WHITESPACE: $ => token(/[\u0009-\u000A_]/),
+    Item: $ => choice(
+      seq('fn', $.IDENTIFIER, '(', ')', '{', '}'),
+      seq('mod', optional($.IDENTIFIER), ';'),
+    ),
+    IDENTIFIER: $ => choice($.IDENTIFIER_OR_KEYWORD, $.RAW_IDENTIFIER),
+    IDENTIFIER_OR_KEYWORD: $ => token(seq(choice(/\p{XID_Start}/, '_'), repeat(/\p{XID_Continue}/))),
+    RAW_IDENTIFIER: $ => token(seq('r#', choice(/\p{XID_Start}/, '_'), repeat(/\p{XID_Continue}/))),
+  },
+});
+"#
+        );
+        assert!(ts.externals.is_empty());
+        let reasons: Vec<_> = ts
+            .approximations
+            .iter()
+            .map(|i| format!("{}: {}", i.production, i.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                "IDENTIFIER: suffix `except a keyword` is not enforced",
+                "IDENTIFIER_OR_KEYWORD: negative lookahead `!`'`` is not enforced",
+            ]
+        );
+    }
+
+    #[test]
+    fn externals() {
+        let g = grammar(
+            "@root Crate -> (RAW_STRING | COMMENT | HEX)*",
+            "RAW_STRING -> `r` `#`{n:1..=255} `\"` ~CR* `\"` `#`{n}\n\n\
+             CR -> U+000D\n\n\
+             COMMENT -> `/*` (COMMENT | ~`*`)* `*/`\n\n\
+             HEX -> `\\u{` HEX_DIGIT{1..=3} `}`\n\n\
+             HEX_DIGIT -> [`0`-`9` `a`-`f`]",
+        );
+        let ts = g.to_tree_sitter("rust");
+        let reasons: Vec<_> = ts
+            .externals
+            .iter()
+            .map(|i| format!("{}: {}", i.production, i.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                "COMMENT: tokens cannot be recursive, but `COMMENT` refers to itself",
+                "RAW_STRING: repetition count `n` depends on an earlier repetition",
+            ]
+        );
+        assert!(
+            ts.grammar_js
+                .contains("  externals: $ => [\n    $.COMMENT,\n    $.RAW_STRING,\n  ],")
+        );
+        assert!(ts.grammar_js.contains(
+            "HEX: $ => token(seq('\\\\u{', /[0-9a-f]/, optional(seq(/[0-9a-f]/, optional(/[0-9a-f]/))), '}')),"
+        ));
+        assert!(
+            ts.report()
+                .contains("must be implemented in an external scanner (2)")
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! Index of where each production is used.
+
+use crate::parameters::names_in;
+use crate::{ExpressionId, ExpressionKind, Grammar, Span};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// A reference to a production from the expression of another production.
+#[derive(Clone, Debug)]
+pub struct Use {
+    /// The name of the production that contains the reference.
+    pub production: String,
+    /// The path to the chapter where that production is defined, relative to
+    /// the book's `src` directory.
+    pub path: PathBuf,
+    /// The ID of the nonterminal expression.
+    pub id: ExpressionId,
+    /// Location of the nonterminal.
+    pub span: Span,
+}
+
+/// A reverse-dependency index of the grammar, created with
+/// [`Grammar::used_by`].
+#[derive(Clone, Debug, Default)]
+pub struct UsedBy {
+    uses: HashMap<String, Vec<Use>>,
+}
+
+impl Grammar {
+    /// Returns an index of where each production is used.
+    pub fn used_by(&self) -> UsedBy {
+        let mut uses: HashMap<String, Vec<Use>> = HashMap::new();
+        for name in &self.name_order {
+            let prod = &self.productions[name];
+            if prod.is_instance() {
+                continue;
+            }
+            prod.expression.visit(&mut |e| {
+                if let ExpressionKind::Nt(nt) = &e.kind {
+                    // `CommaList<Expression>` uses both `CommaList` and
+                    // `Expression`.
+                    for used in names_in(nt) {
+                        if prod.parameters.iter().any(|p| p == used) {
+                            continue;
+                        }
+                        uses.entry(used.to_string()).or_default().push(Use {
+                            production: name.clone(),
+                            path: prod.path.clone(),
+                            id: e.id.clone(),
+                            span: e.span.clone(),
+                        });
+                    }
+                }
+            });
+        }
+        UsedBy { uses }
+    }
+}
+
+impl UsedBy {
+    /// Every reference to the given production.
+    ///
+    /// These are in the order of [`Grammar::name_order`], and then in the
+    /// order they appear in each production.
+    pub fn uses(&self, name: &str) -> &[Use] {
+        self.uses.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// The names of the other productions that use the given production,
+    /// without duplicates.
+    pub fn productions(&self, name: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for u in self.uses(name) {
+            if u.production != name && !names.contains(&u.production.as_str()) {
+                names.push(&u.production);
+            }
+        }
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Grammar;
+
+    #[test]
+    fn used_by() {
+        let g = Grammar::grammar_from_str(
+            "@root A -> B C B\n\nB -> `b` | C\n\nC -> `(` C? `)`",
+            "test",
+        )
+        .unwrap();
+        let used_by = g.used_by();
+        assert_eq!(used_by.productions("A"), Vec::<&str>::new());
+        assert_eq!(used_by.productions("B"), ["A"]);
+        assert_eq!(used_by.productions("C"), ["A", "B"]);
+        let uses: Vec<_> = used_by
+            .uses("C")
+            .iter()
+            .map(|u| (u.production.as_str(), u.span.start.column))
+            .collect();
+        assert_eq!(uses, [("A", 14), ("B", 12), ("C", 10)]);
+    }
+
+    #[test]
+    fn used_by_arguments() {
+        let g =
+            Grammar::grammar_from_str("@root A -> L<B>\n\nL<T> -> T+\n\nB -> `b`", "test").unwrap();
+        let used_by = g.used_by();
+        assert_eq!(used_by.productions("L"), ["A"]);
+        assert_eq!(used_by.productions("B"), ["A"]);
+        assert!(used_by.uses("T").is_empty());
+        assert!(used_by.uses("L<B>").is_empty());
+    }
+}
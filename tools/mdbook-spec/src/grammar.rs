@@ -1,9 +1,10 @@
 //! Support for rendering the grammar.
 
-use diagnostics::{Diagnostics, warn_or_err};
-use grammar::{GRAMMAR_RE, Grammar};
+use diagnostics::{Diagnostics, bug, warn_or_err};
+use grammar::{GRAMMAR_RE, GRAMMAR_TEST_RE, Grammar, Production, UsedBy, split_arguments};
 use mdbook_preprocessor::book::Chapter;
 use regex::{Captures, Regex};
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
 use std::sync::LazyLock;
@@ -15,15 +16,43 @@ static NAMES_RE: LazyLock<Regex> = LazyLock::new(|| {
     // For match rule names, we support standard ASCII identifiers
     // or non-ASCII characters (such as `⊥`).  This must be
     // kept in sync with `is_name_start` and `is_name_continue` in
-    // `tools/grammar/src/parser.rs`.
-    Regex::new(r"(?m)^(?:@root )?([A-Za-z0-9_]+|[^\x00-\x7F])(?: \([^)]+\))? ->").unwrap()
+    // `tools/grammar/src/parser.rs`. The name may be preceded by
+    // annotations like `@root` and `@edition(2021..)`, and followed by
+    // parameters like `<T>`.
+    Regex::new(
+        r"(?m)^(?:@(?:root|edition\([^)]*\)) )*([A-Za-z0-9_]+|[^\x00-\x7F])(?:<[^>\n]+>)?(?: \([^)]+\))? ->",
+    )
+    .unwrap()
 });
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct RenderCtx {
     md_link_map: HashMap<String, String>,
     rr_link_map: HashMap<String, String>,
+    /// Where each production is used, for the "Used by" list.
+    used_by: UsedBy,
     for_summary: bool,
+    /// The parameters of the production being rendered, which are not
+    /// linked.
+    parameters: Vec<String>,
+}
+
+impl RenderCtx {
+    /// Returns the context for rendering the given production.
+    fn for_production(&self, prod: &Production) -> Cow<'_, RenderCtx> {
+        if prod.parameters == self.parameters {
+            return Cow::Borrowed(self);
+        }
+        Cow::Owned(RenderCtx {
+            parameters: prod.parameters.clone(),
+            ..self.clone()
+        })
+    }
+
+    /// Whether the name is a parameter of the production being rendered.
+    fn is_parameter(&self, name: &str) -> bool {
+        self.parameters.iter().any(|p| p == name)
+    }
 }
 
 #[cfg(test)]
@@ -32,7 +61,9 @@ impl RenderCtx {
         RenderCtx {
             md_link_map: HashMap::new(),
             rr_link_map: HashMap::new(),
+            used_by: UsedBy::default(),
             for_summary: false,
+            parameters: Vec::new(),
         }
     }
 }
@@ -52,8 +83,29 @@ pub fn insert_grammar(grammar: &Grammar, chapter: &Chapter, diag: &mut Diagnosti
         })
         .to_string();
 
-    // Make all production names easily linkable.
+    // The tests of a chapter are in the same order as its blocks.
     let is_summary = is_summary(chapter);
+    let mut tests = grammar
+        .tests
+        .iter()
+        .filter(|test| chapter.path.as_ref() == Some(&test.span.path));
+    content = GRAMMAR_TEST_RE
+        .replace_all(&content, |_: &Captures<'_>| {
+            let Some(test) = tests.next() else {
+                bug!("grammar-test block in {:?} was not loaded", chapter.path);
+            };
+            let (name, _) = split_arguments(&test.production);
+            let dest = match link_map.get(name) {
+                Some(path) => format!("{path}#{}", render_markdown::markdown_id(name, is_summary)),
+                None => "missing".to_string(),
+            };
+            let mut output = String::new();
+            render_markdown::render_grammar_test(test, &dest, &mut output);
+            output
+        })
+        .to_string();
+
+    // Make all production names easily linkable.
     for (name, path) in &link_map {
         let id = render_markdown::markdown_id(name, is_summary);
         if is_summary {
@@ -131,6 +183,7 @@ fn make_relative_link_map(grammar: &Grammar, chapter: &Chapter) -> HashMap<Strin
     grammar
         .productions
         .values()
+        .filter(|p| !p.is_instance())
         .map(|p| {
             let relative = pathdiff::diff_paths(&p.path, current_path).unwrap();
             // Adjust paths for Windows.
@@ -182,7 +235,9 @@ fn render_names(
     let render_ctx = RenderCtx {
         md_link_map: update_link_map(render_markdown::markdown_id),
         rr_link_map: update_link_map(render_railroad::railroad_id),
+        used_by: grammar.used_by(),
         for_summary,
+        parameters: Vec::new(),
     };
 
     if let Err(e) = render_markdown::render_markdown(grammar, &render_ctx, &names, &mut output) {
@@ -226,10 +281,16 @@ pub fn is_summary(chapter: &Chapter) -> bool {
 pub fn insert_summary(grammar: &Grammar, chapter: &Chapter, diag: &mut Diagnostics) -> String {
     let link_map = make_relative_link_map(grammar, chapter);
     let mut seen = HashSet::new();
-    let categories: Vec<_> = grammar
+    // Instances of productions with parameters are not written in the book.
+    let productions: Vec<_> = grammar
         .name_order
         .iter()
-        .map(|name| &grammar.productions[name].category)
+        .map(|name| &grammar.productions[name])
+        .filter(|p| !p.is_instance())
+        .collect();
+    let categories: Vec<_> = productions
+        .iter()
+        .map(|p| &p.category)
         .filter(|cat| seen.insert(*cat))
         .collect();
     let mut grammar_summary = String::new();
@@ -237,11 +298,10 @@ pub fn insert_summary(grammar: &Grammar, chapter: &Chapter, diag: &mut Diagnosti
         let mut chars = category.chars();
         let cap = chars.next().unwrap().to_uppercase().collect::<String>() + chars.as_str();
         write!(grammar_summary, "\n## {cap} summary\n\n").unwrap();
-        let names: Vec<_> = grammar
-            .name_order
+        let names: Vec<_> = productions
             .iter()
-            .filter(|name| grammar.productions[*name].category == *category)
-            .map(|s| s.as_str())
+            .filter(|p| p.category == *category)
+            .map(|p| p.name.as_str())
             .collect();
         let for_lexer = category == "lexer";
         let s = render_names(grammar, &names, &link_map, for_lexer, chapter, diag);
@@ -3,7 +3,10 @@
 use super::RenderCtx;
 use crate::grammar::Grammar;
 use anyhow::bail;
-use grammar::{Character, Expression, ExpressionKind, Production};
+use grammar::{
+    Character, EditionRange, Expect, Expression, ExpressionKind, GrammarTest, Production,
+    lint_allow, split_arguments,
+};
 use regex::Regex;
 use std::borrow::Cow;
 use std::fmt::Write;
@@ -38,12 +41,22 @@ pub fn markdown_id(name: &str, for_summary: bool) -> String {
 }
 
 fn render_production(prod: &Production, cx: &RenderCtx, output: &mut String) {
+    let cx = &*cx.for_production(prod);
     let dest = cx
         .rr_link_map
         .get(&prod.name)
         .map(|path| path.to_string())
         .unwrap_or_else(|| format!("missing"));
-    for expr in &prod.comments {
+    // `lint-allow` comments are only for the linter, so they are skipped
+    // along with the line break that follows them.
+    let mut comments = prod.comments.iter();
+    while let Some(expr) = comments.next() {
+        if let ExpressionKind::Comment(text) = &expr.kind
+            && lint_allow(text).is_some()
+        {
+            comments.next();
+            continue;
+        }
         render_expression(expr, cx, output);
     }
     write!(
@@ -51,16 +64,87 @@ fn render_production(prod: &Production, cx: &RenderCtx, output: &mut String) {
         "<span class=\"grammar-text grammar-production\" id=\"{id}\" \
            onclick=\"show_railroad()\"\
          >\
-           [{name}]({dest})\
-         </span> → ",
+           [{name}]({dest}){parameters}\
+         </span>",
         id = markdown_id(&prod.name, cx.for_summary),
         name = prod.name,
+        parameters = if prod.is_generic() {
+            format!("&lt;{}&gt;", prod.parameters.join(", "))
+        } else {
+            String::new()
+        },
     )
     .unwrap();
+    if let Some(edition) = &prod.edition {
+        render_edition(edition, output);
+    }
+    output.push_str(" → ");
     render_expression(&prod.expression, cx, output);
+    render_used_by(prod, cx, output);
     output.push('\n');
 }
 
+/// Lists of "Used by" productions longer than this are collapsed, since
+/// productions like `Expression` are used by dozens of others.
+const USED_BY_COLLAPSE_LEN: usize = 10;
+
+/// Renders the list of productions that use this production, so that
+/// readers can navigate upwards through the grammar.
+fn render_used_by(prod: &Production, cx: &RenderCtx, output: &mut String) {
+    let users = cx.used_by.productions(&prod.name);
+    if users.is_empty() {
+        return;
+    }
+    let collapse = users.len() > USED_BY_COLLAPSE_LEN;
+    if collapse {
+        write!(
+            output,
+            "\n\n<details class=\"grammar-used-by\">\n\
+             <summary>Used by {} productions</summary>\n\
+             \n",
+            users.len()
+        )
+        .unwrap();
+    } else {
+        output.push_str("\\\n<span class=\"grammar-used-by\">Used by: ");
+    }
+    for (i, user) in users.iter().enumerate() {
+        if i > 0 {
+            output.push_str(", ");
+        }
+        let dest = cx.md_link_map.get(*user).map_or("missing", |d| d.as_str());
+        write!(output, "[{user}]({dest})").unwrap();
+    }
+    if collapse {
+        output.push_str("\n\n</details>");
+    } else {
+        output.push_str("</span>");
+    }
+}
+
+/// Renders a nonterminal, with a link to the production and to each
+/// production in its arguments.
+fn render_nt(nt: &str, cx: &RenderCtx, output: &mut String) {
+    let (name, args) = split_arguments(nt);
+    if cx.is_parameter(name) {
+        output.push_str(name);
+    } else {
+        let dest = cx.md_link_map.get(name).map_or("missing", |d| d.as_str());
+        write!(output, "[{name}]({dest})").unwrap();
+    }
+    if args.is_empty() {
+        return;
+    }
+    output.push_str("&lt;");
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            output.push_str(", ");
+        }
+        render_nt(arg, cx, output);
+    }
+    output.push_str("&gt;");
+}
+
 fn render_expression(expr: &Expression, cx: &RenderCtx, output: &mut String) {
     match &expr.kind {
         ExpressionKind::Grouped(e) => {
@@ -84,9 +168,19 @@ fn render_expression(expr: &Expression, cx: &RenderCtx, output: &mut String) {
             }
         }
         ExpressionKind::Sequence(es) => {
-            let mut iter = es.iter().peekable();
-            while let Some(e) = iter.next() {
+            // The edition goes after the last element, rather than after a
+            // comment or line break at the end.
+            let last = es
+                .iter()
+                .rposition(|e| !e.is_break() && !matches!(e.kind, ExpressionKind::Comment(_)));
+            let mut iter = es.iter().enumerate().peekable();
+            while let Some((i, e)) = iter.next() {
                 render_expression(e, cx, output);
+                if Some(i) == last
+                    && let Some(edition) = &expr.edition
+                {
+                    render_edition(edition, output);
+                }
                 if iter.peek().is_some() && !e.last_expr().is_break() {
                     output.push(' ');
                 }
@@ -130,8 +224,9 @@ fn render_expression(expr: &Expression, cx: &RenderCtx, output: &mut String) {
             write!(output, "<sup>{name}</sup>").unwrap();
         }
         ExpressionKind::Nt(nt) => {
-            let dest = cx.md_link_map.get(nt).map_or("missing", |d| d.as_str());
-            write!(output, "<span class=\"grammar-text\">[{nt}]({dest})</span>").unwrap();
+            output.push_str("<span class=\"grammar-text\">");
+            render_nt(nt, cx, output);
+            output.push_str("</span>");
         }
         ExpressionKind::Terminal(t) => {
             write!(
@@ -148,6 +243,7 @@ fn render_expression(expr: &Expression, cx: &RenderCtx, output: &mut String) {
             output.push_str("\\\n");
             output.push_str(&"&nbsp;".repeat(*indent));
         }
+        ExpressionKind::Comment(s) if lint_allow(s).is_some() => {}
         ExpressionKind::Comment(s) => {
             write!(output, "<span class=\"grammar-comment\">// {s}</span>").unwrap();
         }
@@ -180,6 +276,14 @@ fn render_expression(expr: &Expression, cx: &RenderCtx, output: &mut String) {
         }
     }
     if let Some(suffix) = &expr.suffix {
+        // Productions in constraints link to their definitions, just like
+        // nonterminals.
+        let suffix = match &expr.constraint {
+            Some(constraint) => Cow::Owned(
+                constraint.render(&|name| cx.md_link_map.get(name).map(|d| d.to_string())),
+            ),
+            None => Cow::Borrowed(suffix),
+        };
         write!(output, "<sub class=\"grammar-text\">{suffix}</sub>").unwrap();
     }
     if !cx.for_summary {
@@ -189,6 +293,21 @@ fn render_expression(expr: &Expression, cx: &RenderCtx, output: &mut String) {
             write!(output, "&ZeroWidthSpace;[^{footnote}]").unwrap();
         }
     }
+    if !matches!(expr.kind, ExpressionKind::Sequence(_))
+        && let Some(edition) = &expr.edition
+    {
+        render_edition(edition, output);
+    }
+}
+
+/// Renders the note for an `@edition(..)` annotation.
+fn render_edition(edition: &EditionRange, output: &mut String) {
+    write!(
+        output,
+        "<sub class=\"grammar-text grammar-edition\">{}</sub>",
+        edition.description()
+    )
+    .unwrap();
 }
 
 fn charset_render_markdown(cx: &RenderCtx, set: &[Expression], output: &mut String) {
@@ -203,6 +322,50 @@ fn charset_render_markdown(cx: &RenderCtx, set: &[Expression], output: &mut Stri
     output.push(']');
 }
 
+/// Renders the examples of a `grammar-test` block, collapsed so that they
+/// don't get in the way of the text.
+///
+/// `dest` is the link to the production.
+pub fn render_grammar_test(test: &GrammarTest, dest: &str, output: &mut String) {
+    let (name, _) = split_arguments(&test.production);
+    write!(
+        output,
+        "<details class=\"grammar-test\">\n\
+         <summary>Examples</summary>\n\
+         \n\
+         Examples of [{name}]({dest}):\n\
+         \n"
+    )
+    .unwrap();
+    for example in &test.examples {
+        let label = match example.expect {
+            Expect::Accept => "Accepted",
+            Expect::Reject => "Rejected",
+        };
+        let source = &example.source;
+        // Use more backticks than the example contains.
+        let ticks = "`".repeat(longest_backtick_run(source) + 1);
+        if source.contains('\n') {
+            let fence = "`".repeat(ticks.len().max(3));
+            writeln!(output, "* {label}:\n\n  {fence}text").unwrap();
+            for line in source.lines() {
+                writeln!(output, "  {line}").unwrap();
+            }
+            writeln!(output, "  {fence}").unwrap();
+        } else if source.starts_with('`') || source.ends_with('`') {
+            writeln!(output, "* {label}: {ticks} {source} {ticks}").unwrap();
+        } else {
+            writeln!(output, "* {label}: {ticks}{source}{ticks}").unwrap();
+        }
+    }
+    output.push_str("\n</details>\n");
+}
+
+/// Returns the length of the longest sequence of backticks in the string.
+fn longest_backtick_run(s: &str) -> usize {
+    s.split(|ch| ch != '`').map(str::len).max().unwrap_or(0)
+}
+
 /// Escapes characters that markdown would otherwise interpret.
 fn markdown_escape(s: &str) -> Cow<'_, str> {
     static ESC_RE: LazyLock<Regex> =
@@ -214,21 +377,11 @@ fn markdown_escape(s: &str) -> Cow<'_, str> {
 mod tests {
     use super::*;
     use grammar::RangeLimit;
-    use std::collections::HashMap;
-
-    /// Creates a minimal `RenderCtx` for testing.
-    fn test_cx() -> RenderCtx {
-        RenderCtx {
-            md_link_map: HashMap::new(),
-            rr_link_map: HashMap::new(),
-            for_summary: false,
-        }
-    }
 
     /// Renders a single expression to a markdown string.
     fn render(kind: ExpressionKind) -> String {
-        let cx = test_cx();
-        let expr = Expression::new_kind(kind, 0);
+        let cx = RenderCtx::for_test();
+        let expr = Expression::new_kind(kind);
         let mut output = String::new();
         render_expression(&expr, &cx, &mut output);
         output
@@ -239,7 +392,7 @@ mod tests {
     #[test]
     fn lookahead_nonterminal() {
         let result = render(ExpressionKind::NegativeLookahead(Box::new(
-            Expression::new_kind(ExpressionKind::Nt("CHAR".to_string()), 0),
+            Expression::new_kind(ExpressionKind::Nt("CHAR".to_string())),
         )));
         assert!(result.contains("!"), "should contain `!` prefix");
         assert!(
@@ -251,7 +404,7 @@ mod tests {
     #[test]
     fn lookahead_terminal() {
         let result = render(ExpressionKind::NegativeLookahead(Box::new(
-            Expression::new_kind(ExpressionKind::Terminal("'".to_string()), 0),
+            Expression::new_kind(ExpressionKind::Terminal("'".to_string())),
         )));
         assert!(result.starts_with("!"), "should start with `!`");
         assert!(
@@ -263,13 +416,10 @@ mod tests {
     #[test]
     fn lookahead_charset() {
         let result = render(ExpressionKind::NegativeLookahead(Box::new(
-            Expression::new_kind(
-                ExpressionKind::Charset(vec![
-                    Expression::new_kind(ExpressionKind::Terminal("e".to_string()), 0),
-                    Expression::new_kind(ExpressionKind::Terminal("E".to_string()), 0),
-                ]),
-                0,
-            ),
+            Expression::new_kind(ExpressionKind::Charset(vec![
+                Expression::new_kind(ExpressionKind::Terminal("e".to_string())),
+                Expression::new_kind(ExpressionKind::Terminal("E".to_string())),
+            ])),
         )));
         assert!(result.starts_with("!"), "should start with `!`");
         assert!(
@@ -281,15 +431,13 @@ mod tests {
     #[test]
     fn lookahead_grouped() {
         // !( `.` | `_` )
-        let inner = ExpressionKind::Grouped(Box::new(Expression::new_kind(
-            ExpressionKind::Alt(vec![
-                Expression::new_kind(ExpressionKind::Terminal(".".to_string()), 0),
-                Expression::new_kind(ExpressionKind::Terminal("_".to_string()), 0),
-            ]),
-            0,
-        )));
+        let inner =
+            ExpressionKind::Grouped(Box::new(Expression::new_kind(ExpressionKind::Alt(vec![
+                Expression::new_kind(ExpressionKind::Terminal(".".to_string())),
+                Expression::new_kind(ExpressionKind::Terminal("_".to_string())),
+            ]))));
         let result = render(ExpressionKind::NegativeLookahead(Box::new(
-            Expression::new_kind(inner, 0),
+            Expression::new_kind(inner),
         )));
         assert!(result.starts_with("!("));
         assert!(result.contains("|"));
@@ -321,7 +469,6 @@ mod tests {
                 Character::Unicode(('\0', "0000".to_string())),
                 Character::Unicode(('\u{007F}', "007F".to_string())),
             ),
-            0,
         )]));
         assert!(result.contains("\\["));
         assert!(result.contains("U+0000"));
@@ -333,7 +480,6 @@ mod tests {
     fn charset_char_range() {
         let result = render(ExpressionKind::Charset(vec![Expression::new_kind(
             ExpressionKind::CharacterRange(Character::Char('a'), Character::Char('z')),
-            0,
         )]));
         assert!(result.contains("\\["));
         assert!(result.contains("grammar-literal"));
@@ -348,7 +494,6 @@ mod tests {
                 Character::Char('a'),
                 Character::Unicode(('\u{007A}', "007A".to_string())),
             ),
-            0,
         )]));
         assert!(result.contains("grammar-literal"));
         assert!(result.contains("U+007A"));
@@ -361,7 +506,6 @@ mod tests {
     fn cut_rendering() {
         let result = render(ExpressionKind::Cut(Box::new(Expression::new_kind(
             ExpressionKind::Nt("Foo".to_string()),
-            0,
         ))));
         assert!(result.starts_with("^ "), "cut should render as `^ ` prefix");
         assert!(result.contains("Foo"));
@@ -372,13 +516,9 @@ mod tests {
     #[test]
     fn neg_expression_rendering() {
         let result = render(ExpressionKind::NegExpression(Box::new(
-            Expression::new_kind(
-                ExpressionKind::Charset(vec![Expression::new_kind(
-                    ExpressionKind::Terminal("a".to_string()),
-                    0,
-                )]),
-                0,
-            ),
+            Expression::new_kind(ExpressionKind::Charset(vec![Expression::new_kind(
+                ExpressionKind::Terminal("a".to_string()),
+            )])),
         )));
         assert!(
             result.starts_with("~"),
@@ -410,7 +550,7 @@ mod tests {
     fn repeat_range_with_name() {
         // A RepeatRange with a name renders as `<sup>n:1..=255</sup>`.
         let result = render(ExpressionKind::RepeatRange {
-            expr: Box::new(Expression::new_kind(ExpressionKind::Nt("x".to_string()), 0)),
+            expr: Box::new(Expression::new_kind(ExpressionKind::Nt("x".to_string()))),
             name: Some("n".to_string()),
             min: Some(1),
             max: Some(255),
@@ -427,7 +567,7 @@ mod tests {
         // A RepeatRange without a name renders with no spurious
         // colon -- just `<sup>2..5</sup>`.
         let result = render(ExpressionKind::RepeatRange {
-            expr: Box::new(Expression::new_kind(ExpressionKind::Nt("x".to_string()), 0)),
+            expr: Box::new(Expression::new_kind(ExpressionKind::Nt("x".to_string()))),
             name: None,
             min: Some(2),
             max: Some(5),
@@ -447,7 +587,7 @@ mod tests {
     fn repeat_range_named_reference() {
         // A RepeatRangeNamed renders as `<sup>n</sup>`.
         let result = render(ExpressionKind::RepeatRangeNamed(
-            Box::new(Expression::new_kind(ExpressionKind::Nt("x".to_string()), 0)),
+            Box::new(Expression::new_kind(ExpressionKind::Nt("x".to_string()))),
             "n".to_string(),
         ));
         assert!(
@@ -455,4 +595,115 @@ mod tests {
             "expected <sup>n</sup>, got: {result}"
         );
     }
+
+    #[test]
+    fn edition() {
+        // The note goes after the last element, not after the line break.
+        let mut expr = Expression::new_kind(ExpressionKind::Sequence(vec![
+            Expression::new_kind(ExpressionKind::Terminal("dyn".to_string())),
+            Expression::new_kind(ExpressionKind::Break(4)),
+        ]));
+        expr.edition = Some("2018..".parse().unwrap());
+        let mut output = String::new();
+        render_expression(&expr, &RenderCtx::for_test(), &mut output);
+        assert!(
+            output.contains(
+                "dyn</span><sub class=\"grammar-text grammar-edition\">\
+                 2018 edition and later</sub> \\\n"
+            ),
+            "expected the edition before the break, got: {output}"
+        );
+    }
+
+    #[test]
+    fn arguments() {
+        let mut cx = RenderCtx::for_test();
+        cx.md_link_map
+            .insert("CommaList".to_string(), "list.md".to_string());
+        cx.parameters = vec!["T".to_string()];
+        let expr = Expression::new_kind(ExpressionKind::Nt("CommaList<T>".to_string()));
+        let mut output = String::new();
+        render_expression(&expr, &cx, &mut output);
+        assert_eq!(
+            output,
+            "<span class=\"grammar-text\">[CommaList](list.md)&lt;T&gt;</span>"
+        );
+    }
+
+    #[test]
+    fn grammar_test() {
+        let example = |expect, source: &str| grammar::Example {
+            source: source.to_string(),
+            expect,
+            span: Default::default(),
+        };
+        let test = GrammarTest {
+            production: "LetStatement".to_string(),
+            examples: vec![
+                example(Expect::Accept, "let x = 1;"),
+                example(Expect::Reject, "let x = `a`"),
+                example(Expect::Accept, "let x = {\n    1\n};"),
+            ],
+            span: Default::default(),
+        };
+        let mut output = String::new();
+        render_grammar_test(&test, "statements.md#grammar-LetStatement", &mut output);
+        assert_eq!(
+            output,
+            "<details class=\"grammar-test\">\n\
+             <summary>Examples</summary>\n\
+             \n\
+             Examples of [LetStatement](statements.md#grammar-LetStatement):\n\
+             \n\
+             * Accepted: `let x = 1;`\n\
+             * Rejected: `` let x = `a` ``\n\
+             * Accepted:\n\
+             \n  ```text\n  let x = {\n      1\n  };\n  ```\n\
+             \n\
+             </details>\n"
+        );
+    }
+
+    /// Returns the rendered "Used by" list of `name` in `grammar`.
+    fn render_used_by_for(grammar: &str, name: &str) -> String {
+        let grammar = grammar::Grammar::grammar_from_str(grammar, "test").unwrap();
+        let mut cx = RenderCtx::for_test();
+        cx.used_by = grammar.used_by();
+        let mut output = String::new();
+        render_used_by(&grammar.productions[name], &cx, &mut output);
+        output
+    }
+
+    #[test]
+    fn used_by() {
+        assert_eq!(
+            render_used_by_for("@root A -> B C\n\nB -> C\n\nC -> `c`", "C"),
+            "\\\n<span class=\"grammar-used-by\">Used by: [A](missing), [B](missing)</span>"
+        );
+        assert_eq!(render_used_by_for("@root A -> `a`", "A"), "");
+    }
+
+    #[test]
+    fn used_by_collapsed() {
+        let users: Vec<String> = (0..=USED_BY_COLLAPSE_LEN)
+            .map(|i| format!("@root U{i} -> C"))
+            .collect();
+        let grammar = format!("{}\n\nC -> `c`", users.join("\n\n"));
+        let links: Vec<String> = (0..=USED_BY_COLLAPSE_LEN)
+            .map(|i| format!("[U{i}](missing)"))
+            .collect();
+        assert_eq!(
+            render_used_by_for(&grammar, "C"),
+            format!(
+                "\n\n<details class=\"grammar-used-by\">\n\
+                 <summary>Used by {} productions</summary>\n\
+                 \n\
+                 {}\n\
+                 \n\
+                 </details>",
+                USED_BY_COLLAPSE_LEN + 1,
+                links.join(", ")
+            )
+        );
+    }
 }
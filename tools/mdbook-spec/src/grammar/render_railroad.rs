@@ -3,7 +3,10 @@
 use super::RenderCtx;
 use crate::grammar::Grammar;
 use anyhow::bail;
-use grammar::{Character, Expression, ExpressionKind, Production, RangeLimit};
+use grammar::{
+    Character, Constraint, Exclusion, Expression, ExpressionKind, Production, RangeLimit,
+    split_arguments,
+};
 use railroad::*;
 use regex::Regex;
 use std::fmt::Write;
@@ -65,6 +68,7 @@ pub fn railroad_id(name: &str, for_summary: bool) -> String {
 }
 
 fn render_production(prod: &Production, cx: &RenderCtx, output: &mut String) {
+    let cx = &*cx.for_production(prod);
     let mut dia = make_diagram(prod, cx, false);
     // If the diagram is very wide, try stacking it to reduce the width.
     // This 900 is somewhat arbitrary based on looking at productions that
@@ -93,10 +97,15 @@ fn make_diagram(prod: &Production, cx: &RenderCtx, stack: bool) -> Diagram<Box<d
         .unwrap_or_else(|| format!("missing"));
     let seq: Sequence<Box<dyn Node>> =
         Sequence::new(vec![Box::new(SimpleStart), n.unwrap(), Box::new(SimpleEnd)]);
-    let vert = VerticalGrid::<Box<dyn Node>>::new(vec![
-        Box::new(Link::new(Comment::new(prod.name.clone()), dest)),
-        Box::new(seq),
-    ]);
+    let mut rows: Vec<Box<dyn Node>> = vec![Box::new(Link::new(
+        Comment::new(prod.name_with_parameters()),
+        dest,
+    ))];
+    if let Some(edition) = &prod.edition {
+        rows.push(Box::new(Comment::new(edition.description())));
+    }
+    rows.push(Box::new(seq));
+    let vert = VerticalGrid::<Box<dyn Node>>::new(rows);
 
     Diagram::new(Box::new(vert))
 }
@@ -240,7 +249,6 @@ fn render_expression(expr: &Expression, cx: &RenderCtx, stack: bool) -> Option<B
                             max: Some(*b),
                             limit: *limit,
                         },
-                        0, // Synthetic expression for rendering
                     )));
                     break 'cont &state;
                 }
@@ -293,16 +301,13 @@ fn render_expression(expr: &Expression, cx: &RenderCtx, stack: bool) -> Option<B
                     for _ in 0..(a - 1) {
                         es.push(*e.clone());
                     }
-                    es.push(Expression::new_kind(
-                        ExpressionKind::RepeatRange {
-                            expr: e.clone(),
-                            name: None,
-                            min: Some(1),
-                            max: b.map(|x| x - (a - 1)),
-                            limit: *limit,
-                        },
-                        0,
-                    ));
+                    es.push(Expression::new_kind(ExpressionKind::RepeatRange {
+                        expr: e.clone(),
+                        name: None,
+                        min: Some(1),
+                        max: b.map(|x| x - (a - 1)),
+                        limit: *limit,
+                    }));
                     state = ExpressionKind::Sequence(es);
                     break 'cont &state;
                 }
@@ -367,11 +372,32 @@ fn render_expression(expr: &Expression, cx: &RenderCtx, stack: bool) -> Option<B
     } else {
         n
     };
-    if let Some(suffix) = &expr.suffix {
-        let suffix = strip_markdown(suffix);
-        let lbox = LabeledBox::new(n, Comment::new(suffix));
-        return Some(Box::new(lbox));
-    }
+    let n = if let Some(suffix) = &expr.suffix {
+        if let Some(Constraint::Except(exclusions, _)) = &expr.constraint {
+            let mut choices: Vec<_> = exclusions
+                .iter()
+                .map(|exclusion| node_for_exclusion(cx, exclusion))
+                .collect();
+            let label = if choices.len() == 1 {
+                choices.pop().unwrap()
+            } else {
+                Box::new(bounded_multichoice(choices))
+            };
+            Box::new(Except::new(n, label)) as Box<dyn Node>
+        } else {
+            let suffix = strip_markdown(suffix);
+            Box::new(LabeledBox::new(n, Comment::new(suffix)))
+        }
+    } else {
+        n
+    };
+    // Label an `@edition(..)` alternative with the editions it applies to.
+    let n = if let Some(edition) = &expr.edition {
+        let lbox = LabeledBox::new(n, Comment::new(edition.description()));
+        Box::new(lbox) as Box<dyn Node>
+    } else {
+        n
+    };
     // Note: Footnotes aren't supported. They could be added as a comment
     // on a vertical stack or a LabeledBox or something like that, but I
     // don't feel like bothering.
@@ -392,16 +418,35 @@ fn bounded_multichoice(inp: Vec<Box<dyn Node>>) -> MultiChoice<Box<dyn Node>> {
     MultiChoice::new(groups.collect())
 }
 
-fn node_for_nt(cx: &RenderCtx, name: &str) -> Box<dyn Node> {
+/// Renders a nonterminal, including any arguments, with a link to the
+/// production.
+fn node_for_nt(cx: &RenderCtx, nt: &str) -> Box<dyn Node> {
+    let (name, _) = split_arguments(nt);
+    let n = NonTerminal::new(nt.to_string());
+    if cx.is_parameter(name) {
+        return Box::new(n);
+    }
     let dest = cx
         .rr_link_map
         .get(name)
         .map(|path| path.to_string())
         .unwrap_or_else(|| format!("missing"));
-    let n = NonTerminal::new(name.to_string());
     Box::new(Link::new(n, dest))
 }
 
+/// Renders one of the exclusions of an `except` suffix.
+fn node_for_exclusion(cx: &RenderCtx, exclusion: &Exclusion) -> Box<dyn Node> {
+    match exclusion {
+        Exclusion::Terminal(t) => Box::new(Terminal::new(t.clone())),
+        Exclusion::Production(name) => node_for_nt(cx, name),
+        Exclusion::Delimiters => Box::new(Comment::new("delimiters".to_string())),
+        Exclusion::Keywords(classes) => {
+            Box::new(Comment::new(format!("{} keyword", classes.join(" or "))))
+        }
+        Exclusion::EndsWith(t) => Box::new(Comment::new(format!("ending with {t}"))),
+    }
+}
+
 /// Removes some markdown so it can be rendered as text.
 fn strip_markdown(s: &str) -> String {
     // Right now this just removes markdown linkifiers, but more can be added if needed.
@@ -464,16 +509,13 @@ mod tests {
 
     /// Build a `RepeatRange` expression wrapping a nonterminal `e`.
     fn range_expr(min: Option<u32>, max: Option<u32>, limit: RangeLimit) -> Expression {
-        Expression::new_kind(
-            ExpressionKind::RepeatRange {
-                expr: Box::new(Expression::new_kind(ExpressionKind::Nt("e".to_string()), 0)),
-                name: None,
-                min,
-                max,
-                limit,
-            },
-            0,
-        )
+        Expression::new_kind(ExpressionKind::RepeatRange {
+            expr: Box::new(Expression::new_kind(ExpressionKind::Nt("e".to_string()))),
+            name: None,
+            min,
+            max,
+            limit,
+        })
     }
 
     #[test]
@@ -495,6 +537,34 @@ mod tests {
 
     // -- RepeatRange tests --
 
+    #[test]
+    fn test_except_suffix() {
+        // `e _except `x` or [f]_` renders the exclusions as diagrams.
+        let mut expr = Expression::new_kind(ExpressionKind::Nt("e".to_string()));
+        expr.set_suffix(Some("except `x` or [f]".to_string()));
+        let svg = render_to_svg(&expr).unwrap();
+        assert!(
+            svg.contains("exceptbox"),
+            "expected an except box, got: {svg}"
+        );
+        assert!(
+            !svg.contains("except `x`"),
+            "suffix should not be rendered as text, got: {svg}"
+        );
+    }
+
+    #[test]
+    fn test_edition() {
+        // `@edition(2018..) e` is labeled with the editions.
+        let mut expr = Expression::new_kind(ExpressionKind::Nt("e".to_string()));
+        expr.edition = Some("2018..".parse().unwrap());
+        let svg = render_to_svg(&expr).unwrap();
+        assert!(
+            svg.contains("2018 edition and later"),
+            "expected the edition label, got: {svg}"
+        );
+    }
+
     #[test]
     fn test_empty_exclusive_equal() {
         // `e{2..2}` (half-open, min == max) renders as empty.
@@ -565,13 +635,9 @@ mod tests {
 
     #[test]
     fn lookahead_nonterminal() {
-        let expr = Expression::new_kind(
-            ExpressionKind::NegativeLookahead(Box::new(Expression::new_kind(
-                ExpressionKind::Nt("CHAR".to_string()),
-                0,
-            ))),
-            0,
-        );
+        let expr = Expression::new_kind(ExpressionKind::NegativeLookahead(Box::new(
+            Expression::new_kind(ExpressionKind::Nt("CHAR".to_string())),
+        )));
         let svg = render_to_svg(&expr).unwrap();
         assert!(
             svg.contains("not followed by"),
@@ -582,13 +648,9 @@ mod tests {
 
     #[test]
     fn lookahead_terminal() {
-        let expr = Expression::new_kind(
-            ExpressionKind::NegativeLookahead(Box::new(Expression::new_kind(
-                ExpressionKind::Terminal("CR".to_string()),
-                0,
-            ))),
-            0,
-        );
+        let expr = Expression::new_kind(ExpressionKind::NegativeLookahead(Box::new(
+            Expression::new_kind(ExpressionKind::Terminal("CR".to_string())),
+        )));
         let svg = render_to_svg(&expr).unwrap();
         assert!(svg.contains("not followed by"));
         assert!(svg.contains("CR"));
@@ -596,16 +658,12 @@ mod tests {
 
     #[test]
     fn lookahead_charset() {
-        let expr = Expression::new_kind(
-            ExpressionKind::NegativeLookahead(Box::new(Expression::new_kind(
-                ExpressionKind::Charset(vec![
-                    Expression::new_kind(ExpressionKind::Terminal("e".to_string()), 0),
-                    Expression::new_kind(ExpressionKind::Terminal("E".to_string()), 0),
-                ]),
-                0,
-            ))),
-            0,
-        );
+        let expr = Expression::new_kind(ExpressionKind::NegativeLookahead(Box::new(
+            Expression::new_kind(ExpressionKind::Charset(vec![
+                Expression::new_kind(ExpressionKind::Terminal("e".to_string())),
+                Expression::new_kind(ExpressionKind::Terminal("E".to_string())),
+            ])),
+        )));
         let svg = render_to_svg(&expr).unwrap();
         assert!(svg.contains("not followed by"));
         assert!(svg.contains("e"));
@@ -616,17 +674,17 @@ mod tests {
 
     #[test]
     fn unicode_4_digit() {
-        let expr = Expression::new_kind(ExpressionKind::Unicode(('\t', "0009".to_string())), 0);
+        let expr = Expression::new_kind(ExpressionKind::Unicode(('\t', "0009".to_string())));
         let svg = render_to_svg(&expr).unwrap();
         assert!(svg.contains("U+0009"), "should render Unicode code point");
     }
 
     #[test]
     fn unicode_6_digit() {
-        let expr = Expression::new_kind(
-            ExpressionKind::Unicode(('\u{10FFFF}', "10FFFF".to_string())),
-            0,
-        );
+        let expr = Expression::new_kind(ExpressionKind::Unicode((
+            '\u{10FFFF}',
+            "10FFFF".to_string(),
+        )));
         let svg = render_to_svg(&expr).unwrap();
         assert!(svg.contains("U+10FFFF"));
     }
@@ -635,16 +693,12 @@ mod tests {
 
     #[test]
     fn charset_unicode_range() {
-        let expr = Expression::new_kind(
-            ExpressionKind::Charset(vec![Expression::new_kind(
-                ExpressionKind::CharacterRange(
-                    Character::Unicode(('\0', "0000".to_string())),
-                    Character::Unicode(('\u{007F}', "007F".to_string())),
-                ),
-                0,
-            )]),
-            0,
-        );
+        let expr = Expression::new_kind(ExpressionKind::Charset(vec![Expression::new_kind(
+            ExpressionKind::CharacterRange(
+                Character::Unicode(('\0', "0000".to_string())),
+                Character::Unicode(('\u{007F}', "007F".to_string())),
+            ),
+        )]));
         let svg = render_to_svg(&expr).unwrap();
         assert!(svg.contains("U+0000"));
         assert!(svg.contains("U+007F"));
@@ -652,13 +706,9 @@ mod tests {
 
     #[test]
     fn charset_char_range() {
-        let expr = Expression::new_kind(
-            ExpressionKind::Charset(vec![Expression::new_kind(
-                ExpressionKind::CharacterRange(Character::Char('a'), Character::Char('z')),
-                0,
-            )]),
-            0,
-        );
+        let expr = Expression::new_kind(ExpressionKind::Charset(vec![Expression::new_kind(
+            ExpressionKind::CharacterRange(Character::Char('a'), Character::Char('z')),
+        )]));
         let svg = render_to_svg(&expr).unwrap();
         assert!(svg.contains("a"));
         assert!(svg.contains("z"));
@@ -668,13 +718,9 @@ mod tests {
 
     #[test]
     fn cut_rendering() {
-        let expr = Expression::new_kind(
-            ExpressionKind::Cut(Box::new(Expression::new_kind(
-                ExpressionKind::Nt("Foo".to_string()),
-                0,
-            ))),
-            0,
-        );
+        let expr = Expression::new_kind(ExpressionKind::Cut(Box::new(Expression::new_kind(
+            ExpressionKind::Nt("Foo".to_string()),
+        ))));
         let svg = render_to_svg(&expr).unwrap();
         assert!(
             svg.contains("no backtracking"),
@@ -687,16 +733,11 @@ mod tests {
 
     #[test]
     fn neg_expression_rendering() {
-        let expr = Expression::new_kind(
-            ExpressionKind::NegExpression(Box::new(Expression::new_kind(
-                ExpressionKind::Charset(vec![Expression::new_kind(
-                    ExpressionKind::Terminal("a".to_string()),
-                    0,
-                )]),
-                0,
-            ))),
-            0,
-        );
+        let expr = Expression::new_kind(ExpressionKind::NegExpression(Box::new(
+            Expression::new_kind(ExpressionKind::Charset(vec![Expression::new_kind(
+                ExpressionKind::Terminal("a".to_string()),
+            )])),
+        )));
         let svg = render_to_svg(&expr).unwrap();
         assert!(
             svg.contains("with the exception of"),
@@ -710,13 +751,10 @@ mod tests {
     fn repeat_range_named_reference() {
         // RepeatRangeNamed renders with a "repeat exactly n times"
         // label.
-        let expr = Expression::new_kind(
-            ExpressionKind::RepeatRangeNamed(
-                Box::new(Expression::new_kind(ExpressionKind::Nt("x".to_string()), 0)),
-                "n".to_string(),
-            ),
-            0,
-        );
+        let expr = Expression::new_kind(ExpressionKind::RepeatRangeNamed(
+            Box::new(Expression::new_kind(ExpressionKind::Nt("x".to_string()))),
+            "n".to_string(),
+        ));
         let svg = render_to_svg(&expr).unwrap();
         assert!(
             svg.contains("repeat exactly n times"),
@@ -727,16 +765,13 @@ mod tests {
     #[test]
     fn repeat_range_with_name_renders() {
         // A named RepeatRange should display the name as a label.
-        let expr = Expression::new_kind(
-            ExpressionKind::RepeatRange {
-                expr: Box::new(Expression::new_kind(ExpressionKind::Nt("e".to_string()), 0)),
-                name: Some("n".to_string()),
-                min: Some(2),
-                max: Some(5),
-                limit: RangeLimit::Closed,
-            },
-            0,
-        );
+        let expr = Expression::new_kind(ExpressionKind::RepeatRange {
+            expr: Box::new(Expression::new_kind(ExpressionKind::Nt("e".to_string()))),
+            name: Some("n".to_string()),
+            min: Some(2),
+            max: Some(5),
+            limit: RangeLimit::Closed,
+        });
         let svg = render_to_svg(&expr).unwrap();
         assert!(
             svg.contains("repeat count n"),
@@ -748,16 +783,13 @@ mod tests {
     fn repeat_range_with_name_optional() {
         // `e{k:0..=5}` decomposes to Optional(RepeatRange).  The
         // name label should still appear on the outermost node.
-        let expr = Expression::new_kind(
-            ExpressionKind::RepeatRange {
-                expr: Box::new(Expression::new_kind(ExpressionKind::Nt("e".to_string()), 0)),
-                name: Some("k".to_string()),
-                min: Some(0),
-                max: Some(5),
-                limit: RangeLimit::Closed,
-            },
-            0,
-        );
+        let expr = Expression::new_kind(ExpressionKind::RepeatRange {
+            expr: Box::new(Expression::new_kind(ExpressionKind::Nt("e".to_string()))),
+            name: Some("k".to_string()),
+            min: Some(0),
+            max: Some(5),
+            limit: RangeLimit::Closed,
+        });
         let svg = render_to_svg(&expr).unwrap();
         assert!(
             svg.contains("repeat count k"),
@@ -769,16 +801,13 @@ mod tests {
     fn repeat_range_without_name_no_label() {
         // An unnamed RepeatRange should not have a "repeat count"
         // label.
-        let expr = Expression::new_kind(
-            ExpressionKind::RepeatRange {
-                expr: Box::new(Expression::new_kind(ExpressionKind::Nt("e".to_string()), 0)),
-                name: None,
-                min: Some(2),
-                max: Some(5),
-                limit: RangeLimit::Closed,
-            },
-            0,
-        );
+        let expr = Expression::new_kind(ExpressionKind::RepeatRange {
+            expr: Box::new(Expression::new_kind(ExpressionKind::Nt("e".to_string()))),
+            name: None,
+            min: Some(2),
+            max: Some(5),
+            limit: RangeLimit::Closed,
+        });
         let svg = render_to_svg(&expr).unwrap();
         assert!(
             !svg.contains("repeat count"),
@@ -790,16 +819,13 @@ mod tests {
     fn repeat_range_with_name_identity() {
         // `e{n:1..=1}` renders as plain `e` but should still
         // display the name label.
-        let expr = Expression::new_kind(
-            ExpressionKind::RepeatRange {
-                expr: Box::new(Expression::new_kind(ExpressionKind::Nt("e".to_string()), 0)),
-                name: Some("n".to_string()),
-                min: Some(1),
-                max: Some(1),
-                limit: RangeLimit::Closed,
-            },
-            0,
-        );
+        let expr = Expression::new_kind(ExpressionKind::RepeatRange {
+            expr: Box::new(Expression::new_kind(ExpressionKind::Nt("e".to_string()))),
+            name: Some("n".to_string()),
+            min: Some(1),
+            max: Some(1),
+            limit: RangeLimit::Closed,
+        });
         let svg = render_to_svg(&expr).unwrap();
         assert!(
             svg.contains("repeat count n"),
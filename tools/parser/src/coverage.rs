@@ -1,34 +1,45 @@
 //! Support for recording and rendering coverage of the grammar.
 
-use grammar::{Character, Expression, ExpressionKind, Grammar};
+use grammar::{Character, Expression, ExpressionId, ExpressionKind, Grammar};
+use std::collections::HashMap;
 
+/// Coverage of the grammar, keyed by the stable [`ExpressionId`] of each
+/// expression so that the data from different builds can be compared.
 #[derive(Default)]
 pub struct Coverage {
     /// Count of the repetitions for each expression.
     ///
-    /// The index is the expression ID. The value is the number of times a
-    /// particular number of repetitions was found for that expression (index
-    /// N means it matched N repetitions of the given number of times).
-    pub match_count: Vec<Vec<u32>>,
+    /// The value is the number of times a particular number of repetitions
+    /// was found for that expression (index N means it matched N
+    /// repetitions of the given number of times).
+    pub match_count: HashMap<ExpressionId, Vec<u32>>,
 
     /// Count of how often an expression failed to match its input.
-    ///
-    /// The index is the expression ID, the count is the number of times it failed.
-    pub no_match_count: Vec<u32>,
+    pub no_match_count: HashMap<ExpressionId, u32>,
 
     /// Count of how often the expression caused a `ParseError`.
-    ///
-    /// The index is the expression ID, the count is the number of times it caused an error.
-    pub parse_error: Vec<u32>,
+    pub parse_error: HashMap<ExpressionId, u32>,
+}
+
+/// Adds one to the count for an expression.
+fn increment(counts: &mut HashMap<ExpressionId, u32>, id: &ExpressionId) {
+    match counts.get_mut(id) {
+        Some(count) => *count += 1,
+        None => {
+            counts.insert(id.clone(), 1);
+        }
+    }
 }
 
 impl Coverage {
     /// Marks a node as being matched.
-    pub fn cov_match(&mut self, id: u32, count: u32) {
-        if self.match_count.len() < (id + 1) as usize {
-            self.match_count.resize((id + 1) as usize, Vec::new());
+    pub fn cov_match(&mut self, id: &ExpressionId, count: u32) {
+        // Avoids cloning the ID, since this is called for every expression
+        // that is parsed.
+        if !self.match_count.contains_key(id) {
+            self.match_count.insert(id.clone(), Vec::new());
         }
-        let ns = self.match_count.get_mut(id as usize).unwrap();
+        let ns = self.match_count.get_mut(id).unwrap();
         if ns.len() < (count + 1) as usize {
             ns.resize((count + 1) as usize, 0);
         }
@@ -36,28 +47,19 @@ impl Coverage {
     }
 
     /// Marks a node that failed to match its input.
-    pub fn cov_no_match(&mut self, id: u32) {
-        if self.no_match_count.len() < (id + 1) as usize {
-            self.no_match_count.resize((id + 1) as usize, 0);
-        }
-        *self.no_match_count.get_mut(id as usize).unwrap() += 1;
+    pub fn cov_no_match(&mut self, id: &ExpressionId) {
+        increment(&mut self.no_match_count, id);
     }
 
     /// Marks a node that caused a `ParseError`.
-    pub fn cov_parse_error(&mut self, id: u32) {
-        if self.parse_error.len() < (id + 1) as usize {
-            self.parse_error.resize((id + 1) as usize, 0);
-        }
-        *self.parse_error.get_mut(id as usize).unwrap() += 1;
+    pub fn cov_parse_error(&mut self, id: &ExpressionId) {
+        increment(&mut self.parse_error, id);
     }
 
     /// Merge one `Coverage` into this one.
     pub fn merge(&mut self, other: Coverage) {
-        if self.match_count.len() < other.match_count.len() {
-            self.match_count.resize(other.match_count.len(), Vec::new());
-        }
-        for (id, counts) in other.match_count.into_iter().enumerate() {
-            let this = self.match_count.get_mut(id).unwrap();
+        for (id, counts) in other.match_count {
+            let this = self.match_count.entry(id).or_default();
             if this.len() < counts.len() {
                 this.resize(counts.len(), 0);
             }
@@ -65,19 +67,11 @@ impl Coverage {
                 this[count] += value;
             }
         }
-
-        if self.no_match_count.len() < other.no_match_count.len() {
-            self.no_match_count.resize(other.no_match_count.len(), 0);
-        }
-        for (id, value) in other.no_match_count.into_iter().enumerate() {
-            self.no_match_count[id] += value;
+        for (id, value) in other.no_match_count {
+            *self.no_match_count.entry(id).or_default() += value;
         }
-
-        if self.parse_error.len() < other.parse_error.len() {
-            self.parse_error.resize(other.parse_error.len(), 0);
-        }
-        for (id, value) in other.parse_error.into_iter().enumerate() {
-            self.parse_error[id] += value;
+        for (id, value) in other.parse_error {
+            *self.parse_error.entry(id).or_default() += value;
         }
     }
 
@@ -89,10 +83,10 @@ impl Coverage {
         std::fs::write("coverage.html", html).expect("failed to write coverage.html");
     }
 
-    fn get_coverage_status(&self, id: u32, kind: &ExpressionKind) -> CoverageStatus {
-        let match_count = self.match_count.get(id as usize);
-        let no_match = self.no_match_count.get(id as usize).copied().unwrap_or(0);
-        let parse_error = self.parse_error.get(id as usize).copied().unwrap_or(0);
+    fn get_coverage_status(&self, id: &ExpressionId, kind: &ExpressionKind) -> CoverageStatus {
+        let match_count = self.match_count.get(id);
+        let no_match = self.no_match_count.get(id).copied().unwrap_or(0);
+        let parse_error = self.parse_error.get(id).copied().unwrap_or(0);
 
         let has_matches = match_count
             .map(|counts| counts.iter().any(|&c| c > 0))
@@ -233,6 +227,9 @@ impl Coverage {
             "<span class=\"production-name\">{}</span>",
             html_escape(&prod.name)
         ));
+        if let Some(edition) = &prod.edition {
+            output.push_str(&format!("<sub>{}</sub>", edition.description()));
+        }
         output.push_str(" → ");
         self.render_expression(&prod.expression, output, span_stack);
         output.push_str("</div>\n");
@@ -266,11 +263,11 @@ impl Coverage {
             return;
         }
 
-        let status = self.get_coverage_status(expr.id, &expr.kind);
+        let status = self.get_coverage_status(&expr.id, &expr.kind);
         let bg_color = status.color();
-        let has_error = self.parse_error.get(expr.id as usize).copied().unwrap_or(0) > 0;
+        let has_error = self.parse_error.get(&expr.id).copied().unwrap_or(0) > 0;
 
-        let tooltip = self.generate_tooltip(expr.id);
+        let tooltip = self.generate_tooltip(expr);
 
         let span = format!(
             "<span class=\"expr\" style=\"background-color: {};\" \
@@ -291,6 +288,9 @@ impl Coverage {
         if let Some(suffix) = &expr.suffix {
             output.push_str(&format!("<sub>{}</sub>", html_escape(suffix)));
         }
+        if let Some(edition) = &expr.edition {
+            output.push_str(&format!("<sub>{}</sub>", edition.description()));
+        }
 
         output.push_str("</span>");
         span_stack.pop();
@@ -430,12 +430,14 @@ impl Coverage {
         }
     }
 
-    fn generate_tooltip(&self, id: u32) -> String {
+    fn generate_tooltip(&self, expr: &Expression) -> String {
+        let id = &expr.id;
         let mut tooltip = String::new();
 
         tooltip.push_str(&format!("ID: {}\\n", id));
+        tooltip.push_str(&format!("Source: {}\\n", expr.span));
 
-        if let Some(counts) = self.match_count.get(id as usize) {
+        if let Some(counts) = self.match_count.get(id) {
             if counts.iter().any(|&c| c > 0) {
                 tooltip.push_str("Match counts:\\n");
                 for (n, &count) in counts.iter().enumerate() {
@@ -447,12 +449,12 @@ impl Coverage {
             }
         }
 
-        let no_match = self.no_match_count.get(id as usize).copied().unwrap_or(0);
+        let no_match = self.no_match_count.get(id).copied().unwrap_or(0);
         if no_match > 0 {
             tooltip.push_str(&format!("No match: {}\\n", no_match));
         }
 
-        let parse_error = self.parse_error.get(id as usize).copied().unwrap_or(0);
+        let parse_error = self.parse_error.get(id).copied().unwrap_or(0);
         if parse_error > 0 {
             tooltip.push_str(&format!("Parse errors: {}\\n", parse_error));
         }
@@ -0,0 +1,128 @@
+//! Runs the examples from the `grammar-test` blocks in the book.
+
+use super::ParseError;
+use crate::coverage::Coverage;
+use crate::parser::{Failure, ParseOptions, SourceIndex, parse_production};
+use diagnostics::{Diagnostics, warn_or_err};
+use grammar::{Edition, Example, Expect, Grammar};
+
+/// Parses every example in [`Grammar::tests`] with its production, and
+/// reports the examples that were not accepted or rejected as expected.
+pub fn check_examples(grammar: &Grammar, diag: &mut Diagnostics) {
+    for test in &grammar.tests {
+        if !grammar.productions.contains_key(&test.production) {
+            // Reported when the grammar is loaded.
+            continue;
+        }
+        for example in &test.examples {
+            check_example(grammar, &test.production, example, diag);
+        }
+    }
+}
+
+fn check_example(grammar: &Grammar, production: &str, example: &Example, diag: &mut Diagnostics) {
+    let src = &example.source;
+    match (example.expect, parse_example(grammar, production, src)) {
+        (Expect::Accept, Err(e)) => {
+            warn_or_err!(
+                diag,
+                "{}: expected `{production}` to accept `{src}`, but it failed: {}",
+                example.span,
+                e.display(src)
+            );
+        }
+        (Expect::Reject, Ok(())) => {
+            warn_or_err!(
+                diag,
+                "{}: expected `{production}` to reject `{src}`, but it was accepted",
+                example.span
+            );
+        }
+        _ => {}
+    }
+}
+
+/// Parses all of the source with the given production.
+///
+/// Productions in the `lexer` category are parsed from the characters of
+/// the source, and all others are parsed from its tokens. Examples are
+/// parsed in the latest edition.
+pub fn parse_example(grammar: &Grammar, production: &str, src: &str) -> Result<(), ParseError> {
+    let edition = Edition::default();
+    let prod = &grammar.productions[production];
+    if prod.category != "lexer" {
+        return crate::tree::parse(grammar, src, production, edition).map(|_| ());
+    }
+    let mut coverage = Coverage::default();
+    let mut failure = Failure::default();
+    let result = parse_production(
+        grammar,
+        &mut coverage,
+        prod,
+        &src,
+        SourceIndex(0),
+        ParseOptions {
+            edition,
+            memo: None,
+            failure: Some(&mut failure),
+        },
+    )?;
+    match result {
+        Some((_, next_index)) if next_index.0 == src.len() => Ok(()),
+        Some((_, next_index)) => {
+            failure.advance_to(next_index);
+            Err(failure.to_error(&src))
+        }
+        None => Err(failure.to_error(&src)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_example, check_examples, parse_example};
+    use diagnostics::Diagnostics;
+    use grammar::{Example, Expect, Span};
+
+    fn example(source: &str, expect: Expect) -> Example {
+        Example {
+            source: source.to_string(),
+            expect,
+            span: Span::default(),
+        }
+    }
+
+    #[test]
+    fn empty_example() {
+        let grammar = grammar::load_grammar_with_frontmatter(&mut Diagnostics::capture());
+        assert!(parse_example(&grammar, "Item", "").is_err());
+        assert!(parse_example(&grammar, "IDENTIFIER", "").is_err());
+
+        let mut diag = Diagnostics::capture();
+        check_example(&grammar, "Item", &example("", Expect::Accept), &mut diag);
+        assert_eq!(diag.messages().len(), 1);
+        assert!(
+            diag.messages()[0].contains("expected `Item` to accept ``, but it failed"),
+            "{:?}",
+            diag.messages()
+        );
+
+        let mut diag = Diagnostics::capture();
+        check_example(&grammar, "Item", &example("", Expect::Reject), &mut diag);
+        assert_eq!(diag.count, 0);
+    }
+
+    /// Runs the examples in the book, with `cargo xtask grammar-test`.
+    #[test]
+    fn book_examples() {
+        let grammar = grammar::load_grammar_with_frontmatter(&mut Diagnostics::capture());
+        let mut diag = Diagnostics::capture();
+        check_examples(&grammar, &mut diag);
+        let count: usize = grammar.tests.iter().map(|test| test.examples.len()).sum();
+        assert!(
+            diag.messages().is_empty(),
+            "{} problems found in {count} grammar examples:\n{}",
+            diag.count,
+            diag.messages().join("\n")
+        );
+    }
+}
@@ -2,8 +2,9 @@
 
 use super::{Node, ParseError};
 use crate::coverage::Coverage;
-use crate::parser::{SourceIndex, parse_production};
-use grammar::{ExpressionKind, Grammar, Production};
+use crate::parser::{ParseOptions, SourceIndex, parse_production};
+use grammar::{Edition, ExpressionKind, Grammar, Production};
+use std::rc::Rc;
 use tracing::debug;
 
 #[derive(Clone)]
@@ -19,14 +20,16 @@ pub struct Tokens {
     pub frontmatter: Option<Node>,
 }
 
+/// Tokenizes the source with the lexer rules of the given edition.
 pub fn tokenize(
     grammar: &Grammar,
     coverage: &mut Coverage,
     original_src: &str,
+    edition: Edition,
 ) -> Result<Tokens, ParseError> {
     let (normalized_src, removed_indices) = normalize_crlf(original_src);
 
-    tokenize_normalized(grammar, coverage, &normalized_src)
+    tokenize_normalized(grammar, coverage, &normalized_src, edition)
         .map(|mut tokens| {
             for token in &mut tokens.tokens {
                 adjust_node(&removed_indices, token);
@@ -71,7 +74,7 @@ fn adjust_node(removed_indices: &[usize], node: &mut Node) {
     node.range.start = map_offset(removed_indices, node.range.start);
     node.range.end = map_offset(removed_indices, node.range.end);
     for child in &mut node.children.0 {
-        adjust_node(removed_indices, child);
+        adjust_node(removed_indices, Rc::make_mut(child));
     }
 }
 
@@ -80,6 +83,7 @@ fn tokenize_normalized(
     grammar: &Grammar,
     coverage: &mut Coverage,
     src: &str,
+    edition: Edition,
 ) -> Result<Tokens, ParseError> {
     let top_prods = get_top_prods(grammar);
 
@@ -90,10 +94,10 @@ fn tokenize_normalized(
     }
 
     let shebang;
-    (shebang, index) = parse_shebang(grammar, coverage, src, index)?;
+    (shebang, index) = parse_shebang(grammar, coverage, src, index, edition)?;
     let frontmatter;
-    (frontmatter, index) = parse_frontmatter(grammar, coverage, src, index)?;
-    let tokens = parse_tokens(grammar, coverage, &top_prods, src, index)?;
+    (frontmatter, index) = parse_frontmatter(grammar, coverage, src, index, edition)?;
+    let tokens = parse_tokens(grammar, coverage, &top_prods, src, index, edition)?;
     validate_delimiters_balanced(&tokens, src)?;
 
     debug!("lexing complete");
@@ -145,9 +149,17 @@ fn parse_shebang(
     coverage: &mut Coverage,
     src: &str,
     index: SourceIndex,
+    edition: Edition,
 ) -> Result<(Option<Node>, SourceIndex), ParseError> {
     let shebang = grammar.productions.get("SHEBANG").unwrap();
-    if let Some((node, next_index)) = parse_production(grammar, coverage, shebang, &src, index)? {
+    if let Some((node, next_index)) = parse_production(
+        grammar,
+        coverage,
+        shebang,
+        &src,
+        index,
+        ParseOptions::edition(edition),
+    )? {
         Ok((Some(node), next_index))
     } else {
         Ok((None, index))
@@ -159,14 +171,22 @@ fn parse_frontmatter(
     coverage: &mut Coverage,
     src: &str,
     index: SourceIndex,
+    edition: Edition,
 ) -> Result<(Option<Node>, SourceIndex), ParseError> {
     let frontmatter = grammar.productions.get("FRONTMATTER").unwrap();
-    if let Some((node, next_index)) = parse_production(grammar, coverage, frontmatter, &src, index)
-        .map_err(|e| ParseError {
-            message: format!("invalid frontmatter: {}", e.message),
-            byte_offset: e.byte_offset,
-        })?
-    {
+    if let Some((node, next_index)) = parse_production(
+        grammar,
+        coverage,
+        frontmatter,
+        &src,
+        index,
+        ParseOptions::edition(edition),
+    )
+    .map_err(|e| ParseError {
+        message: format!("invalid frontmatter: {}", e.message),
+        byte_offset: e.byte_offset,
+        expected: e.expected,
+    })? {
         Ok((Some(node), next_index))
     } else {
         Ok((None, index))
@@ -180,14 +200,20 @@ fn parse_tokens(
     top_prods: &[&Production],
     src: &str,
     mut index: SourceIndex,
+    edition: Edition,
 ) -> Result<Vec<Node>, ParseError> {
     let mut tokens = Vec::new();
     let whitespace = grammar.productions.get("WHITESPACE").unwrap();
 
     while index.0 < src.len() {
-        if let Some((_node, next_index)) =
-            parse_production(grammar, coverage, whitespace, &src, index)?
-        {
+        if let Some((_node, next_index)) = parse_production(
+            grammar,
+            coverage,
+            whitespace,
+            &src,
+            index,
+            ParseOptions::edition(edition),
+        )? {
             index = next_index;
             continue;
         }
@@ -195,9 +221,14 @@ fn parse_tokens(
         let mut matched_token = None;
         for token_prod in top_prods {
             debug!("try top-level token `{}`", token_prod.name);
-            if let Some((node, next_index)) =
-                parse_production(grammar, coverage, token_prod, &src, index)?
-                && node.byte_len() > 0
+            if let Some((node, next_index)) = parse_production(
+                grammar,
+                coverage,
+                token_prod,
+                &src,
+                index,
+                ParseOptions::edition(edition),
+            )? && node.byte_len() > 0
             {
                 index = next_index;
                 matched_token = Some(node);
@@ -214,6 +245,7 @@ fn parse_tokens(
                 return Err(ParseError {
                     byte_offset: index.0,
                     message: String::from("no tokens matched"),
+                    expected: Vec::new(),
                 });
             }
         }
@@ -232,6 +264,7 @@ fn validate_delimiters_balanced(tokens: &[Node], src: &str) -> Result<(), ParseE
                     return Err(ParseError {
                         byte_offset: token.range.start,
                         message: "unbalanced `)`".to_string(),
+                        expected: Vec::new(),
                     });
                 }
             }
@@ -240,6 +273,7 @@ fn validate_delimiters_balanced(tokens: &[Node], src: &str) -> Result<(), ParseE
                     return Err(ParseError {
                         byte_offset: token.range.start,
                         message: "unbalanced `]`".to_string(),
+                        expected: Vec::new(),
                     });
                 }
             }
@@ -248,6 +282,7 @@ fn validate_delimiters_balanced(tokens: &[Node], src: &str) -> Result<(), ParseE
                     return Err(ParseError {
                         byte_offset: token.range.start,
                         message: "unbalanced `}`".to_string(),
+                        expected: Vec::new(),
                     });
                 }
             }
@@ -258,6 +293,7 @@ fn validate_delimiters_balanced(tokens: &[Node], src: &str) -> Result<(), ParseE
         return Err(ParseError {
             byte_offset: offset,
             message: "unclosed delimiter".to_string(),
+            expected: Vec::new(),
         });
     }
     Ok(())
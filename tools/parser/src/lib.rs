@@ -1,20 +1,80 @@
 //! Rust parser based on the Reference grammar.
 
+use std::fmt;
 use std::ops::Range;
-use std::str::FromStr;
+use std::rc::Rc;
 
 pub mod coverage;
+pub mod examples;
 pub mod lexer;
+pub mod memo;
 mod parser;
 pub mod tree;
 
+pub use grammar::Edition;
+
 #[derive(Clone, Debug)]
 pub struct ParseError {
     pub byte_offset: usize,
     pub message: String,
+    /// The terminals and productions that would have been accepted at
+    /// `byte_offset`, in sorted order.
+    ///
+    /// This is empty if the error isn't from something failing to match.
+    pub expected: Vec<Expected>,
+}
+
+/// Something that the parser tried to match, for a [`ParseError`].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Expected {
+    /// A terminal, such as `;`.
+    Terminal(String),
+    /// A lexical production, such as `IDENTIFIER`.
+    Production(String),
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Terminal(s) => write!(f, "`{s}`"),
+            Expected::Production(name) => f.write_str(name),
+        }
+    }
 }
 
 impl ParseError {
+    /// Returns an error for when nothing in `expected` matched at
+    /// `byte_offset`.
+    ///
+    /// `found` is the text at `byte_offset`, or `None` at the end of the
+    /// input. The message is in the same style as rustc's, such as
+    /// ``expected one of `;` or `{`, found `=` ``.
+    pub fn from_expected(
+        byte_offset: usize,
+        expected: Vec<Expected>,
+        found: Option<&str>,
+    ) -> ParseError {
+        let found = match found {
+            Some(s) => format!("`{s}`"),
+            None => "end of input".to_string(),
+        };
+        let names: Vec<_> = expected.iter().map(|e| e.to_string()).collect();
+        let message = match names.as_slice() {
+            [] => format!("unexpected {found}"),
+            [e] => format!("expected {e}, found {found}"),
+            [a, b] => format!("expected one of {a} or {b}, found {found}"),
+            [es @ .., last] => format!(
+                "expected one of {}, or {last}, found {found}",
+                es.join(", ")
+            ),
+        };
+        ParseError {
+            byte_offset,
+            message,
+            expected,
+        }
+    }
+
     pub fn display(&self, src: &str) -> String {
         let s = &src[self.byte_offset..];
         match s.char_indices().nth(100) {
@@ -24,27 +84,6 @@ impl ParseError {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Eq)]
-pub enum Edition {
-    Edition2015,
-    Edition2018,
-    Edition2021,
-    Edition2024,
-}
-
-impl FromStr for Edition {
-    type Err = ();
-    fn from_str(s: &str) -> Result<Self, ()> {
-        match s {
-            "2015" => Ok(Edition::Edition2015),
-            "2018" => Ok(Edition::Edition2018),
-            "2021" => Ok(Edition::Edition2021),
-            "2024" => Ok(Edition::Edition2024),
-            _ => Err(()),
-        }
-    }
-}
-
 /// A parsed section of source corresponding to some grammar expression.
 #[derive(Clone, Debug, Default)]
 pub struct Node {
@@ -87,8 +126,11 @@ impl Node {
 }
 
 /// Abstraction over a sequence of nodes.
+///
+/// The nodes are shared, so that cloning them, such as for the memo table,
+/// doesn't copy the subtrees.
 #[derive(Clone, Debug, Default)]
-pub struct Nodes(pub Vec<Node>);
+pub struct Nodes(pub Vec<Rc<Node>>);
 
 impl Nodes {
     fn new(name: String, range: Range<usize>) -> Nodes {
@@ -97,12 +139,16 @@ impl Nodes {
             range,
             children: Nodes::default(),
         };
-        Nodes(vec![node])
+        Nodes(vec![Rc::new(node)])
     }
 
     /// Converts this `Nodes` to one with a single `Node`.
     fn wrap(self, name: String, start: usize) -> Nodes {
-        Nodes(vec![Node::with_children(name.to_string(), start, self)])
+        Nodes(vec![Rc::new(Node::with_children(
+            name.to_string(),
+            start,
+            self,
+        ))])
     }
 
     fn extend(&mut self, other: Nodes) {
@@ -1,4 +1,5 @@
 use diagnostics::Diagnostics;
+use parser::Edition;
 use parser::coverage::Coverage;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
@@ -23,7 +24,7 @@ fn main() {
     let mut diag = Diagnostics::new();
     let grammar = grammar::load_grammar(&mut diag);
     let mut coverage = Coverage::default();
-    let ts = match parser::lexer::tokenize(&grammar, &mut coverage, src) {
+    let ts = match parser::lexer::tokenize(&grammar, &mut coverage, src, Edition::default()) {
         Ok(ts) => ts,
         Err(e) => {
             eprintln!("error: {}", e.display(src));
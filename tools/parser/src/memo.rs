@@ -0,0 +1,153 @@
+//! Packrat memoization of the results of parsing productions.
+//!
+//! The parser is a backtracking interpreter, so alternatives, optional
+//! expressions, and lookaheads can parse the same production at the same
+//! position many times. A [`Memo`] records the result of each production
+//! at each position so that it is only parsed once, at the cost of keeping
+//! every result in memory.
+//!
+//! Memoization is opt-in with [`crate::tree::parse_with_memo`].
+//!
+//! When a result comes from the memo table, the coverage of the
+//! expressions inside the production is not recorded again, so coverage
+//! counts are lower than without memoization.
+//!
+//! Productions are only memoized when there are no named repetitions in
+//! the environment, since the result could depend on them. This is always
+//! the case for the syntactic productions, which are the ones that are
+//! parsed again when backtracking.
+
+use super::{Expected, Node, Nodes, ParseError};
+use crate::parser::{Failure, SourceIndex};
+use grammar::Production;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::size_of;
+use std::rc::Rc;
+
+/// A table of the results of parsing productions in one source.
+#[derive(Debug, Default)]
+pub struct Memo {
+    table: HashMap<MemoKey, Rc<MemoEntry>>,
+    hits: u64,
+    misses: u64,
+    /// Approximate number of bytes used by the keys and entries.
+    bytes: usize,
+}
+
+/// What a memoized result depends on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct MemoKey {
+    /// The address of the production, which identifies it for as long as
+    /// the grammar is borrowed by the parse.
+    production: usize,
+    index: usize,
+}
+
+/// The result of parsing a production.
+#[derive(Debug)]
+pub(crate) struct MemoEntry {
+    pub(crate) result: Result<Option<(Nodes, SourceIndex)>, ParseError>,
+    /// The named repetitions that the production added to the environment.
+    pub(crate) env: Vec<(String, u32)>,
+    /// The furthest failure inside the production.
+    pub(crate) failure: Failure,
+}
+
+/// Statistics about the use of a [`Memo`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoStats {
+    /// Number of results in the table.
+    pub entries: usize,
+    /// Number of times a production was found in the table.
+    pub hits: u64,
+    /// Number of times a production was parsed because it was not in the
+    /// table.
+    pub misses: u64,
+    /// Approximate number of bytes used by the table.
+    pub bytes: usize,
+}
+
+impl Memo {
+    /// Returns the statistics of the table.
+    pub fn stats(&self) -> MemoStats {
+        MemoStats {
+            entries: self.table.len(),
+            hits: self.hits,
+            misses: self.misses,
+            bytes: self.bytes,
+        }
+    }
+
+    /// Removes all results and resets the statistics.
+    ///
+    /// Indexes are only meaningful for one source, and productions for one
+    /// grammar, so this must be called before parsing a different source.
+    pub fn clear(&mut self) {
+        *self = Memo::default();
+    }
+
+    /// Returns the key for parsing `production` at `index`.
+    pub(crate) fn key(production: &Production, index: SourceIndex) -> MemoKey {
+        MemoKey {
+            production: std::ptr::from_ref(production) as usize,
+            index: index.0,
+        }
+    }
+
+    /// Returns the memoized result for the key, if there is one.
+    pub(crate) fn get(&mut self, key: &MemoKey) -> Option<Rc<MemoEntry>> {
+        match self.table.get(key) {
+            Some(entry) => {
+                self.hits += 1;
+                Some(Rc::clone(entry))
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Records the result for the key.
+    pub(crate) fn insert(&mut self, key: MemoKey, entry: MemoEntry) {
+        self.bytes += size_of::<MemoKey>() + entry_bytes(&entry);
+        self.table.insert(key, Rc::new(entry));
+    }
+}
+
+impl fmt::Display for MemoStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} entries, {} hits, {} misses, {:.1} KiB",
+            self.entries,
+            self.hits,
+            self.misses,
+            self.bytes as f64 / 1024.0
+        )
+    }
+}
+
+fn env_bytes(env: &[(String, u32)]) -> usize {
+    env.iter()
+        .map(|(name, _)| size_of::<(String, u32)>() + name.len())
+        .sum()
+}
+
+fn entry_bytes(entry: &MemoEntry) -> usize {
+    // The nodes are shared with the parse tree and the other entries, so
+    // only the references to them are counted.
+    let result = match &entry.result {
+        Ok(Some((nodes, _))) => nodes.0.len() * size_of::<Rc<Node>>(),
+        Ok(None) => 0,
+        Err(e) => e.message.len(),
+    };
+    let failure: usize = entry
+        .failure
+        .expected
+        .iter()
+        .map(|(Expected::Terminal(s) | Expected::Production(s))| size_of::<Expected>() + s.len())
+        .sum();
+    size_of::<Rc<MemoEntry>>() + size_of::<MemoEntry>() + result + env_bytes(&entry.env) + failure
+}
@@ -1,23 +1,115 @@
 //! The generic interpreter of the Reference grammar.
 
-use super::{Node, Nodes, ParseError};
+use super::{Expected, Node, Nodes, ParseError};
 use crate::coverage::Coverage;
-use grammar::{Expression, ExpressionKind, Grammar, Production, RangeLimit};
-use std::collections::HashMap;
+use crate::memo::{Memo, MemoEntry};
+use grammar::{
+    Constraint, DELIMITERS, Edition, Exclusion, Expression, ExpressionKind, Grammar, Lookahead,
+    Production, RangeLimit, UnicodeProperty, keyword_production,
+};
+use std::collections::{BTreeSet, HashMap};
 use std::ops::Range;
+use std::rc::Rc;
 use tracing::instrument;
 
-/// This stores named repetitions.
-///
-/// The key is the name, and the value is the number of repetitions that
-/// happened.
-#[derive(Debug, Default)]
-struct Environment {
+/// The state carried through the parse of a production.
+#[derive(Debug)]
+struct Environment<'m> {
+    /// This stores named repetitions.
+    ///
+    /// The key is the name, and the value is the number of repetitions that
+    /// happened.
     map: HashMap<String, u32>,
+    /// The edition being parsed, which selects the alternatives and
+    /// productions with an `@edition(..)` annotation.
+    edition: Edition,
+    /// The table of production results, if memoization is enabled.
+    memo: Option<&'m mut Memo>,
+    /// The furthest failure so far.
+    ///
+    /// This is `None` when failures aren't recorded, such as inside a
+    /// lookahead.
+    failure: Option<Failure>,
+}
+
+impl Environment<'_> {
+    /// Returns an environment without any named repetitions, that shares
+    /// the edition and memo table of this one.
+    ///
+    /// Failures are not recorded in the nested environment.
+    fn nested(&mut self) -> Environment<'_> {
+        Environment {
+            map: HashMap::new(),
+            edition: self.edition,
+            memo: self.memo.as_deref_mut(),
+            failure: None,
+        }
+    }
+
+    /// Records that `expected` did not match at `index`.
+    fn expected(&mut self, index: SourceIndex, expected: impl FnOnce() -> Expected) {
+        if let Some(failure) = &mut self.failure {
+            failure.record(index, expected);
+        }
+    }
+}
+
+/// The furthest index where the parser failed to match, and what it tried
+/// to match there.
+///
+/// Only terminals and lexical productions are recorded, since the failure
+/// of any other production is the failure of the terminals and lexical
+/// productions inside it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Failure {
+    pub(crate) index: SourceIndex,
+    pub(crate) expected: BTreeSet<Expected>,
+}
+
+impl Failure {
+    fn record(&mut self, index: SourceIndex, expected: impl FnOnce() -> Expected) {
+        if index < self.index {
+            return;
+        }
+        if index > self.index {
+            self.index = index;
+            self.expected.clear();
+        }
+        self.expected.insert(expected());
+    }
+
+    fn merge(&mut self, other: &Failure) {
+        if other.index < self.index {
+            return;
+        }
+        if other.index > self.index {
+            *self = other.clone();
+        } else {
+            self.expected.extend(other.expected.iter().cloned());
+        }
+    }
+
+    /// Moves the failure to `index` if it is before it, forgetting what
+    /// was expected.
+    pub(crate) fn advance_to(&mut self, index: SourceIndex) {
+        if self.index < index {
+            self.index = index;
+            self.expected.clear();
+        }
+    }
+
+    /// Returns an error describing the failure.
+    pub(crate) fn to_error(&self, src: &dyn Source) -> ParseError {
+        ParseError::from_expected(
+            src.index_to_bytes(self.index),
+            self.expected.iter().cloned().collect(),
+            src.get_element(self.index).map(|(s, _)| s),
+        )
+    }
 }
 
 /// A wrapper around an index for referring to elements in a [`Source`].
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Default, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
 pub(crate) struct SourceIndex(pub(crate) usize);
 
 /// Abstracts different kinds of sources for the parser.
@@ -48,7 +140,7 @@ pub(crate) trait Source {
     ///
     /// This is essentially a hack to create a boundary between the lexer and
     /// the tree parser.
-    fn get_node(&self, index: SourceIndex) -> Option<&Node>;
+    fn get_node(&self, index: SourceIndex) -> Option<&Rc<Node>>;
 
     /// Returns the byte offset of the start of the given element.
     ///
@@ -92,7 +184,7 @@ impl Source for &str {
         SourceIndex(index.0 + bytes)
     }
 
-    fn get_node(&self, _index: SourceIndex) -> Option<&Node> {
+    fn get_node(&self, _index: SourceIndex) -> Option<&Rc<Node>> {
         None
     }
 
@@ -101,6 +193,29 @@ impl Source for &str {
     }
 }
 
+/// Options for [`parse_production`].
+#[derive(Debug, Default)]
+pub(crate) struct ParseOptions<'a> {
+    /// The edition being parsed. The alternatives and productions that
+    /// aren't in it don't match.
+    pub(crate) edition: Edition,
+    /// If given, the results of the productions are memoized in it.
+    pub(crate) memo: Option<&'a mut Memo>,
+    /// If given, the furthest failure is recorded in it.
+    pub(crate) failure: Option<&'a mut Failure>,
+}
+
+impl ParseOptions<'_> {
+    /// Returns the options for parsing in `edition`, without memoizing
+    /// results or recording failures.
+    pub(crate) fn edition(edition: Edition) -> Self {
+        ParseOptions {
+            edition,
+            ..ParseOptions::default()
+        }
+    }
+}
+
 /// Parse a production and return the Node with name from the production.
 pub(crate) fn parse_production(
     grammar: &Grammar,
@@ -108,16 +223,27 @@ pub(crate) fn parse_production(
     prod: &Production,
     src: &dyn Source,
     index: SourceIndex,
+    options: ParseOptions<'_>,
 ) -> Result<Option<(Node, SourceIndex)>, ParseError> {
-    let r = parse(
-        grammar,
-        coverage,
-        &prod.expression,
-        src,
-        index,
-        &mut Environment::default(),
-    )?
-    .map(|(children, next_index)| {
+    let ParseOptions {
+        edition,
+        memo,
+        failure,
+    } = options;
+    let env = &mut Environment {
+        map: HashMap::new(),
+        edition,
+        memo,
+        failure: failure.is_some().then(Failure::default),
+    };
+    if !prod.in_edition(env.edition) {
+        return Ok(None);
+    }
+    let r = parse(grammar, coverage, &prod.expression, src, index, env);
+    if let Some(failure) = failure {
+        failure.merge(env.failure.as_ref().unwrap());
+    }
+    let r = r?.map(|(children, next_index)| {
         let children = Node::with_children(prod.name.clone(), src.index_to_bytes(index), children);
         (children, next_index)
     });
@@ -150,28 +276,48 @@ fn parse(
     } else {
         tracing::debug!("eof");
     }
-    let cov_match = |coverage: &mut Coverage, count| coverage.cov_match(e.id, count as u32);
-    let cov_no_match = |coverage: &mut Coverage| coverage.cov_no_match(e.id);
-    let cov_parse_error = |coverage: &mut Coverage| coverage.cov_parse_error(e.id);
+    if !e.in_edition(env.edition) {
+        coverage.cov_no_match(&e.id);
+        return Ok(None);
+    }
+    let Some((nodes, next_index)) = parse_kind(grammar, coverage, e, src, index, env)? else {
+        return Ok(None);
+    };
+    if check_constraint(grammar, coverage, e, src, index..next_index, &nodes, env)? {
+        Ok(Some((nodes, next_index)))
+    } else {
+        coverage.cov_no_match(&e.id);
+        Ok(None)
+    }
+}
+
+/// Parses the kind of an expression, without checking its suffix.
+fn parse_kind(
+    grammar: &Grammar,
+    coverage: &mut Coverage,
+    e: &Expression,
+    src: &dyn Source,
+    index: SourceIndex,
+    env: &mut Environment,
+) -> Result<Option<(Nodes, SourceIndex)>, ParseError> {
+    let cov_match = |coverage: &mut Coverage, count| coverage.cov_match(&e.id, count as u32);
+    let cov_no_match = |coverage: &mut Coverage| coverage.cov_no_match(&e.id);
+    let cov_parse_error = |coverage: &mut Coverage| coverage.cov_parse_error(&e.id);
     match &e.kind {
-        ExpressionKind::Grouped(group) => {
-            assert_eq!(e.suffix, None);
-            match parse(grammar, coverage, group, src, index, env)? {
-                Some((nodes, i)) => {
-                    cov_match(coverage, 1);
-                    Ok(Some((
-                        nodes.wrap(format!("Group({group})"), src.index_to_bytes(index)),
-                        i,
-                    )))
-                }
-                None => {
-                    cov_no_match(coverage);
-                    Ok(None)
-                }
+        ExpressionKind::Grouped(group) => match parse(grammar, coverage, group, src, index, env)? {
+            Some((nodes, i)) => {
+                cov_match(coverage, 1);
+                Ok(Some((
+                    nodes.wrap(format!("Group({group})"), src.index_to_bytes(index)),
+                    i,
+                )))
             }
-        }
+            None => {
+                cov_no_match(coverage);
+                Ok(None)
+            }
+        },
         ExpressionKind::Alt(es) => {
-            assert_eq!(e.suffix, None);
             for e in es {
                 if let Some(r) = parse(grammar, coverage, e, src, index, env)? {
                     cov_match(coverage, 1);
@@ -182,7 +328,6 @@ fn parse(
             Ok(None)
         }
         ExpressionKind::Sequence(es) => {
-            assert_eq!(e.suffix, None);
             let mut current = index;
             let mut children = Vec::new();
             for e in es {
@@ -206,25 +351,24 @@ fn parse(
             cov_match(coverage, 1);
             Ok(Some((Nodes(children), current)))
         }
-        ExpressionKind::Optional(opt) => {
-            assert_eq!(e.suffix, None);
-            match parse(grammar, coverage, opt, src, index, env)? {
-                Some((children, next_index)) => {
-                    cov_match(coverage, 1);
-                    Ok(Some((
-                        children.wrap(format!("Optional({opt})"), src.index_to_bytes(index)),
-                        next_index,
-                    )))
-                }
-                None => {
-                    cov_match(coverage, 0);
-                    Ok(Some((Nodes::default(), index)))
-                }
+        ExpressionKind::Optional(opt) => match parse(grammar, coverage, opt, src, index, env)? {
+            Some((children, next_index)) => {
+                cov_match(coverage, 1);
+                Ok(Some((
+                    children.wrap(format!("Optional({opt})"), src.index_to_bytes(index)),
+                    next_index,
+                )))
             }
-        }
+            None => {
+                cov_match(coverage, 0);
+                Ok(Some((Nodes::default(), index)))
+            }
+        },
         ExpressionKind::NegativeLookahead(n) => {
-            assert_eq!(e.suffix, None);
-            match parse(grammar, coverage, n, src, index, env)? {
+            let failure = env.failure.take();
+            let r = parse(grammar, coverage, n, src, index, env);
+            env.failure = failure;
+            match r? {
                 Some(_) => {
                     cov_match(coverage, 1);
                     Ok(None)
@@ -236,7 +380,6 @@ fn parse(
             }
         }
         ExpressionKind::Repeat(r) => {
-            assert_eq!(e.suffix, None);
             let mut current = index;
             let mut children = Nodes::default();
             while current < src.len() {
@@ -255,7 +398,6 @@ fn parse(
             )))
         }
         ExpressionKind::RepeatPlus(r) => {
-            assert_eq!(e.suffix, None);
             let mut current = index;
             let mut children = Nodes::default();
             while current < src.len() {
@@ -318,31 +460,6 @@ fn parse(
             }
 
             let start_byte_offset = src.index_to_bytes(index);
-            match e.suffix.as_deref() {
-                Some("valid hex char value") => {
-                    let end = src.index_to_bytes(current);
-                    let len = end - start_byte_offset;
-                    let (hex, _) = src.get_substring(index, len).unwrap();
-                    let hex_no_underscores = hex.replace('_', "");
-                    let value = u32::from_str_radix(&hex_no_underscores, 16).map_err(|_| {
-                        cov_parse_error(coverage);
-                        ParseError {
-                            byte_offset: start_byte_offset,
-                            message: format!("invalid hex value: {hex}"),
-                        }
-                    })?;
-                    if char::from_u32(value).is_none() {
-                        cov_parse_error(coverage);
-                        return Err(ParseError {
-                            byte_offset: start_byte_offset,
-                            message: format!("invalid Unicode scalar value: {hex}"),
-                        });
-                    }
-                }
-                Some(s) => panic!("unknown suffix {s:?}"),
-                None => {}
-            }
-
             cov_match(coverage, children.0.len());
             Ok(Some((
                 children.wrap(format!("RepatRange({r})"), start_byte_offset),
@@ -350,7 +467,6 @@ fn parse(
             )))
         }
         ExpressionKind::RepeatRangeNamed(r, name) => {
-            assert_eq!(e.suffix, None);
             let Some(count) = env.map.get(name) else {
                 panic!("expected {name} in environment for {r}");
             };
@@ -382,102 +498,40 @@ fn parse(
                 cov_no_match(coverage);
                 return Ok(None);
             };
-            let len = nodes.byte_len();
-            let (matched, _) = src.get_substring(index, len).unwrap();
-            match e.suffix.as_deref() {
-                Some("except `b` or `c` or `r` or `br` or `cr`") => {
-                    if matches!(matched, "b" | "c" | "r" | "br" | "cr") {
-                        cov_no_match(coverage);
-                        return Ok(None);
-                    }
-                }
-                Some("except `b`") => {
-                    if matched == "b" {
-                        cov_no_match(coverage);
-                        return Ok(None);
-                    }
-                }
-                Some("except `r` or `br` or `cr`") => {
-                    if matches!(matched, "r" | "br" | "cr") {
-                        cov_no_match(coverage);
-                        return Ok(None);
-                    }
-                }
-                Some("except `r`") => {
-                    if matched == "r" {
-                        cov_no_match(coverage);
-                        return Ok(None);
-                    }
-                }
-                Some(
-                    "except a [strict][lex.keywords.strict] or [reserved][lex.keywords.reserved] keyword",
-                ) => {
-                    let strict = grammar.productions.get("STRICT_KEYWORDS").unwrap();
-                    let reserved = grammar.productions.get("RESERVED_KEYWORDS").unwrap();
-                    for e in [&strict.expression, &reserved.expression] {
-                        if let Ok(Some((nodes, _))) = parse(grammar, coverage, e, src, index, env)
-                            && nodes.byte_len() > 0
-                        {
-                            cov_no_match(coverage);
-                            return Ok(None);
-                        }
-                    }
-                }
-                Some("except [delimiters][lex.token.delim]") => {
-                    if matches!(matched, "{" | "}" | "[" | "]" | "(" | ")") {
-                        cov_no_match(coverage);
-                        return Ok(None);
-                    }
-                }
-                Some(suffix) => panic!("unknown suffix {suffix:?}"),
-                None => {}
-            }
             cov_match(coverage, 1);
             Ok(Some((nodes, next_index)))
         }
         ExpressionKind::Terminal(s) => {
-            let Some((next_s, range)) = src.get_substring(index, s.len()) else {
+            let Some((_, range)) = src
+                .get_substring(index, s.len())
+                .filter(|(next_s, _)| *next_s == s.as_str())
+            else {
+                env.expected(index, || Expected::Terminal(s.clone()));
                 cov_no_match(coverage);
                 return Ok(None);
             };
-            if next_s != s {
-                cov_no_match(coverage);
-                return Ok(None);
-            }
             let next_index = src.advance(index, s.len());
-            match e.suffix.as_deref() {
-                Some("immediately followed by LF") => {
-                    if let Some((next_s, _)) = src.get_element(next_index)
-                        && next_s != "\n"
-                    {
-                        cov_no_match(coverage);
-                        return Ok(None);
-                    }
-                }
-                Some(suffix) => panic!("unknown suffix {suffix:?}"),
-                None => {}
-            }
             let nodes = Nodes::new(format!("Terminal {s:?}"), range);
             cov_match(coverage, 1);
             Ok(Some((nodes, next_index)))
         }
-        ExpressionKind::Prose(s) => {
-            assert_eq!(e.suffix, None);
-            match match_prose(s, src, index) {
-                Some(r) => {
-                    cov_match(coverage, 1);
-                    Ok(Some(r))
-                }
-                None => {
-                    cov_no_match(coverage);
-                    Ok(None)
-                }
+        ExpressionKind::Prose(s) => match match_prose(s, src, index) {
+            Ok(Some(r)) => {
+                cov_match(coverage, 1);
+                Ok(Some(r))
             }
-        }
+            Ok(None) => {
+                cov_no_match(coverage);
+                Ok(None)
+            }
+            Err(e) => {
+                cov_parse_error(coverage);
+                Err(e)
+            }
+        },
         ExpressionKind::Break(_) => unreachable!(),
         ExpressionKind::Comment(_) => unreachable!(),
         ExpressionKind::Charset(chars) => {
-            assert_eq!(e.suffix, None);
             for ch in chars {
                 if let Some(r) = parse(grammar, coverage, ch, src, index, env)? {
                     cov_match(coverage, 1);
@@ -506,7 +560,6 @@ fn parse(
             Ok(None)
         }
         ExpressionKind::NegExpression(neg) => {
-            assert_eq!(e.suffix, None);
             match parse(grammar, coverage, neg, src, index, env)? {
                 Some(_) => {
                     cov_no_match(coverage);
@@ -525,24 +578,21 @@ fn parse(
                 }
             }
         }
-        ExpressionKind::Cut(inner) => {
-            assert_eq!(e.suffix, None);
-            match parse(grammar, coverage, inner, src, index, env)? {
-                Some(r) => {
-                    cov_match(coverage, 1);
-                    Ok(Some(r))
-                }
-                None => {
-                    cov_parse_error(coverage);
-                    Err(ParseError {
-                        byte_offset: src.index_to_bytes(index),
-                        message: format!("expected {}", inner),
-                    })
-                }
+        ExpressionKind::Cut(inner) => match parse(grammar, coverage, inner, src, index, env)? {
+            Some(r) => {
+                cov_match(coverage, 1);
+                Ok(Some(r))
             }
-        }
+            None => {
+                cov_parse_error(coverage);
+                Err(ParseError {
+                    byte_offset: src.index_to_bytes(index),
+                    message: format!("expected {}", inner),
+                    expected: Vec::new(),
+                })
+            }
+        },
         ExpressionKind::Unicode((ch, s)) => {
-            assert_eq!(e.suffix, None);
             let mut buf = [0u8; 4];
             let c_str = ch.encode_utf8(&mut buf);
             if let Some((next_s, range)) = src.get_element(index)
@@ -571,15 +621,28 @@ fn parse_nt(
     coverage: &mut Coverage,
 ) -> Result<Option<(Nodes, SourceIndex)>, ParseError> {
     let prod = grammar.productions.get(prod_name).unwrap();
+    if !prod.in_edition(env.edition) {
+        return Ok(None);
+    }
     // If this matches a lexer token, don't parse it and use the token
     // directly. The lexer rules are incompatible when reading tokens.
     let (nodes, next_index) = if let Some(node) = src.get_node(index)
         && node.name == prod.name
     {
-        (Nodes(vec![node.clone()]), SourceIndex(index.0 + 1))
+        (Nodes(vec![Rc::clone(node)]), SourceIndex(index.0 + 1))
     } else {
-        let nodes = parse(grammar, coverage, &prod.expression, src, index, env)?;
-        let Some((nodes, next_index)) = nodes else {
+        let lexical = prod.category == "lexer";
+        // The failures inside a lexical production are reported as the
+        // production itself.
+        let failure = if lexical { env.failure.take() } else { None };
+        let nodes = parse_memoized(grammar, coverage, prod, src, index, env);
+        if lexical {
+            env.failure = failure;
+        }
+        let Some((nodes, next_index)) = nodes? else {
+            if lexical {
+                env.expected(index, || Expected::Production(prod.name.clone()));
+            }
             return Ok(None);
         };
         (
@@ -590,41 +653,172 @@ fn parse_nt(
     Ok(Some((nodes, next_index)))
 }
 
-fn match_prose(prose: &str, src: &dyn Source, index: SourceIndex) -> Option<(Nodes, SourceIndex)> {
-    let next_as_ch = || {
-        src.get_element(index).and_then(|(next, range)| {
-            let mut chars = next.chars();
-            let ch = chars.next().unwrap();
-            if chars.next().is_some() {
-                None
-            } else {
-                Some((ch, range))
-            }
-        })
+/// Parses the expression of a production, or returns the result from the
+/// memo table if it has already been parsed at this index.
+fn parse_memoized(
+    grammar: &Grammar,
+    coverage: &mut Coverage,
+    prod: &Production,
+    src: &dyn Source,
+    index: SourceIndex,
+    env: &mut Environment,
+) -> Result<Option<(Nodes, SourceIndex)>, ParseError> {
+    let Some(memo) = &mut env.memo else {
+        return parse(grammar, coverage, &prod.expression, src, index, env);
     };
+    // The result could depend on the named repetitions.
+    if !env.map.is_empty() {
+        return parse(grammar, coverage, &prod.expression, src, index, env);
+    }
+    let key = Memo::key(prod, index);
+    if let Some(entry) = memo.get(&key) {
+        env.map.extend(entry.env.iter().cloned());
+        if let Some(failure) = &mut env.failure {
+            failure.merge(&entry.failure);
+        }
+        return entry.result.clone();
+    }
+    // Always record the failures of this production separately, so that
+    // they can be replayed from the memo table even if they aren't recorded
+    // now.
+    let outer = env.failure.replace(Failure::default());
+    let result = parse(grammar, coverage, &prod.expression, src, index, env);
+    let failure = std::mem::replace(&mut env.failure, outer).unwrap();
+    if let Some(outer) = &mut env.failure {
+        outer.merge(&failure);
+    }
+    // There were no named repetitions before, so these were added by this
+    // production.
+    let added = env
+        .map
+        .iter()
+        .map(|(name, count)| (name.clone(), *count))
+        .collect();
+    if let Some(memo) = &mut env.memo {
+        memo.insert(
+            key,
+            MemoEntry {
+                result: result.clone(),
+                env: added,
+                failure,
+            },
+        );
+    }
+    result
+}
 
-    match prose {
-        "`XID_Start` defined by Unicode" => {
-            if let Some((ch, range)) = next_as_ch() {
-                unicode_ident::is_xid_start(ch).then(|| {
-                    let nodes = Nodes::new(format!("Prose: {prose}"), range);
-                    (nodes, src.advance(index, ch.len_utf8()))
-                })
-            } else {
-                None
+/// Checks the constraint from the suffix of an expression that matched
+/// `nodes` over `range`.
+///
+/// Returns whether the constraint is satisfied.
+fn check_constraint(
+    grammar: &Grammar,
+    coverage: &mut Coverage,
+    e: &Expression,
+    src: &dyn Source,
+    range: Range<SourceIndex>,
+    nodes: &Nodes,
+    env: &mut Environment,
+) -> Result<bool, ParseError> {
+    // Unrecognized suffixes are reported when the grammar is loaded, and
+    // don't restrict what the expression matches.
+    let Some(constraint) = &e.constraint else {
+        return Ok(true);
+    };
+    let Range {
+        start: index,
+        end: next_index,
+    } = range;
+    let (matched, _) = src.get_substring(index, nodes.byte_len()).unwrap();
+    // Whether the production matches exactly what the expression matched.
+    let matches_production = |coverage: &mut Coverage, env: &mut Environment, name: &str| {
+        matches!(
+            parse_nt(grammar, name, src, index, &mut env.nested(), coverage),
+            Ok(Some((_, i))) if i == next_index
+        )
+    };
+    // Whether the production or terminal matches after the expression.
+    let matches_next =
+        |coverage: &mut Coverage, env: &mut Environment, lookahead: &Lookahead| match lookahead {
+            Lookahead::Terminal(s) => src
+                .get_substring(next_index, s.len())
+                .is_some_and(|(next, _)| next == s),
+            Lookahead::Production(name) => matches!(
+                parse_nt(grammar, name, src, next_index, &mut env.nested(), coverage),
+                Ok(Some(_))
+            ),
+        };
+    let ok = match constraint {
+        Constraint::Except(exclusions, _) => !exclusions.iter().any(|exclusion| match exclusion {
+            Exclusion::Terminal(s) => matched == s,
+            Exclusion::Production(name) => matches_production(coverage, env, name),
+            Exclusion::Delimiters => DELIMITERS.contains(&matched),
+            Exclusion::Keywords(classes) => classes
+                .iter()
+                .any(|class| matches_production(coverage, env, &keyword_production(class))),
+            Exclusion::EndsWith(s) => matched.ends_with(s.as_str()),
+        }),
+        Constraint::FollowedBy(lookahead) => matches_next(coverage, env, lookahead),
+        Constraint::NotFollowedBy(lookahead) => !matches_next(coverage, env, lookahead),
+        Constraint::HexCharValue => {
+            let byte_offset = src.index_to_bytes(index);
+            let value = u32::from_str_radix(&matched.replace('_', ""), 16).map_err(|_| {
+                coverage.cov_parse_error(&e.id);
+                ParseError {
+                    byte_offset,
+                    message: format!("invalid hex value: {matched}"),
+                    expected: Vec::new(),
+                }
+            })?;
+            if char::from_u32(value).is_none() {
+                coverage.cov_parse_error(&e.id);
+                return Err(ParseError {
+                    byte_offset,
+                    message: format!("invalid Unicode scalar value: {matched}"),
+                    expected: Vec::new(),
+                });
             }
+            true
         }
-        "`XID_Continue` defined by Unicode" => {
-            if let Some((ch, range)) = next_as_ch() {
-                unicode_ident::is_xid_continue(ch).then(|| {
-                    let nodes = Nodes::new(format!("Prose: {prose}"), range);
-                    (nodes, src.advance(index, ch.len_utf8()))
-                })
-            } else {
-                None
-            }
+        Constraint::Property(_) => {
+            coverage.cov_parse_error(&e.id);
+            return Err(ParseError {
+                byte_offset: src.index_to_bytes(index),
+                message: format!("`{constraint}` can only be used as prose, not as a suffix"),
+                expected: Vec::new(),
+            });
         }
+    };
+    Ok(ok)
+}
 
-        p => panic!("unknown prose {p}"),
+fn match_prose(
+    prose: &str,
+    src: &dyn Source,
+    index: SourceIndex,
+) -> Result<Option<(Nodes, SourceIndex)>, ParseError> {
+    // Unrecognized prose is reported when the grammar is loaded.
+    let Ok(Constraint::Property(property)) = Constraint::parse_prose(prose) else {
+        return Err(ParseError {
+            byte_offset: src.index_to_bytes(index),
+            message: format!("unknown prose `{prose}`"),
+            expected: Vec::new(),
+        });
+    };
+    let Some((next, range)) = src.get_element(index) else {
+        return Ok(None);
+    };
+    let mut chars = next.chars();
+    let ch = chars.next().unwrap();
+    if chars.next().is_some() {
+        return Ok(None);
     }
+    let has_property = match property {
+        UnicodeProperty::XidStart => unicode_ident::is_xid_start(ch),
+        UnicodeProperty::XidContinue => unicode_ident::is_xid_continue(ch),
+    };
+    Ok(has_property.then(|| {
+        let nodes = Nodes::new(format!("Prose: {prose}"), range);
+        (nodes, src.advance(index, ch.len_utf8()))
+    }))
 }
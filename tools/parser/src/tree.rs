@@ -1,16 +1,18 @@
 //! Parser that can take Rust source and generate a parse tree.
 
-use super::{Node, ParseError};
+use super::{Node, Nodes, ParseError};
 use crate::coverage::Coverage;
 use crate::lexer::tokenize;
+use crate::memo::Memo;
 use crate::parser::parse_production;
-use crate::parser::{Source, SourceIndex};
-use grammar::Grammar;
+use crate::parser::{Failure, ParseOptions, Source, SourceIndex};
+use grammar::{Edition, Grammar};
 use std::ops::Range;
+use std::rc::Rc;
 
 struct TokenSource<'src> {
     src: &'src str,
-    tokens: Vec<Node>,
+    tokens: Vec<Rc<Node>>,
 }
 
 impl Source for TokenSource<'_> {
@@ -44,12 +46,14 @@ impl Source for TokenSource<'_> {
         SourceIndex(index.0 + 1)
     }
 
-    fn get_node(&self, index: SourceIndex) -> Option<&Node> {
+    fn get_node(&self, index: SourceIndex) -> Option<&Rc<Node>> {
         self.tokens.get(index.0)
     }
 
     fn index_to_bytes(&self, index: SourceIndex) -> usize {
-        if index.0 == self.tokens.len() {
+        if self.tokens.is_empty() {
+            0
+        } else if index.0 == self.tokens.len() {
             self.tokens[index.0 - 1].range.end
         } else {
             self.tokens[index.0].range.start
@@ -57,33 +61,219 @@ impl Source for TokenSource<'_> {
     }
 }
 
-/// Parse Rust source for the given named production, and return a [`Node`] tree.
-pub fn parse(grammar: &Grammar, src: &str, production: &str) -> Result<Node, ParseError> {
+/// Parse Rust source in the given edition for the given named production,
+/// and return a [`Node`] tree.
+pub fn parse(
+    grammar: &Grammar,
+    src: &str,
+    production: &str,
+    edition: Edition,
+) -> Result<Node, ParseError> {
+    parse_inner(grammar, src, production, edition, None)
+}
+
+/// Like [`parse`], but memoizes the results of the productions in `memo`,
+/// so that backtracking doesn't parse the same production at the same
+/// token again.
+///
+/// The memo table is cleared first, and afterwards [`Memo::stats`] reports
+/// how it was used.
+pub fn parse_with_memo(
+    grammar: &Grammar,
+    src: &str,
+    production: &str,
+    edition: Edition,
+    memo: &mut Memo,
+) -> Result<Node, ParseError> {
+    memo.clear();
+    parse_inner(grammar, src, production, edition, Some(memo))
+}
+
+fn parse_inner(
+    grammar: &Grammar,
+    src: &str,
+    production: &str,
+    edition: Edition,
+    memo: Option<&mut Memo>,
+) -> Result<Node, ParseError> {
     let mut coverage = Coverage::default();
 
     let krate = grammar.productions.get(production).unwrap();
 
-    let tokens = tokenize(grammar, &mut coverage, src)?;
+    let token_source = token_source(grammar, &mut coverage, src, edition)?;
+
+    let mut failure = Failure::default();
+    let result = parse_production(
+        grammar,
+        &mut coverage,
+        krate,
+        &token_source,
+        SourceIndex(0),
+        ParseOptions {
+            edition,
+            memo,
+            failure: Some(&mut failure),
+        },
+    )?;
+    match result {
+        Some((node, next_index)) if next_index == token_source.len() => Ok(node),
+        Some((_, next_index)) => {
+            failure.advance_to(next_index);
+            Err(failure.to_error(&token_source))
+        }
+        None => Err(failure.to_error(&token_source)),
+    }
+}
+
+/// Parse Rust source for the given named production, recovering from
+/// errors.
+///
+/// When the production fails to match, the error is reported at the
+/// furthest token that the parser reached, along with what it expected
+/// there. The parser then skips to the end of the item or statement that
+/// failed (the next `;` or `}` that isn't nested in a delimiter), and
+/// parses the production again from there. The skipped tokens are in an
+/// `ERROR` node in the returned tree.
+///
+/// If the source fails to tokenize, the tree is empty and the error from
+/// the lexer is the only error.
+pub fn parse_recovering(
+    grammar: &Grammar,
+    src: &str,
+    production: &str,
+    edition: Edition,
+) -> (Node, Vec<ParseError>) {
+    let mut coverage = Coverage::default();
+    let prod = grammar.productions.get(production).unwrap();
+    let token_source = match token_source(grammar, &mut coverage, src, edition) {
+        Ok(token_source) => token_source,
+        Err(e) => return (Node::new(production.to_string(), 0..0), vec![e]),
+    };
+    let len = token_source.len();
+
+    let mut children = Vec::new();
+    let mut errors = Vec::new();
+    let mut index = SourceIndex(0);
+    loop {
+        let mut failure = Failure::default();
+        let result = parse_production(
+            grammar,
+            &mut coverage,
+            prod,
+            &token_source,
+            index,
+            ParseOptions {
+                edition,
+                memo: None,
+                failure: Some(&mut failure),
+            },
+        );
+        // Where to skip from.
+        let skip_from = match result {
+            Ok(Some((node, next_index))) => {
+                children.extend(node.children.0);
+                if next_index == len {
+                    break;
+                }
+                failure.advance_to(next_index);
+                errors.push(failure.to_error(&token_source));
+                next_index
+            }
+            Ok(None) => {
+                failure.advance_to(index);
+                errors.push(failure.to_error(&token_source));
+                index
+            }
+            Err(e) => {
+                let error_index = token_source
+                    .tokens
+                    .partition_point(|t| t.range.start < e.byte_offset);
+                errors.push(e);
+                index.max(SourceIndex(error_index))
+            }
+        };
+        if skip_from == len {
+            break;
+        }
+        index = token_source.skip_to_sync(skip_from);
+        let skipped = &token_source.tokens[skip_from.0..index.0];
+        children.push(Rc::new(Node::new(
+            "ERROR".to_string(),
+            skipped[0].range.start..skipped.last().unwrap().range.end,
+        )));
+        if index == len {
+            break;
+        }
+    }
+    let node = Node::with_children(production.to_string(), 0, Nodes(children));
+    (node, errors)
+}
+
+/// Tokenizes the source, without comments.
+fn token_source<'src>(
+    grammar: &Grammar,
+    coverage: &mut Coverage,
+    src: &'src str,
+    edition: Edition,
+) -> Result<TokenSource<'src>, ParseError> {
+    let tokens = tokenize(grammar, coverage, src, edition)?;
 
     // Strip comments.
     let tokens = tokens
         .tokens
         .into_iter()
         .filter(|token| !matches!(token.name.as_str(), "LINE_COMMENT" | "BLOCK_COMMENT"))
+        .map(Rc::new)
         .collect();
 
-    let token_source = TokenSource { src, tokens };
+    Ok(TokenSource { src, tokens })
+}
 
-    match parse_production(grammar, &mut coverage, krate, &token_source, SourceIndex(0))? {
-        Some((node, next_index)) => {
-            if next_index < token_source.len() {
-                return Err(ParseError {
-                    message: format!("{production} production failed to parse all tokens"),
-                    byte_offset: token_source.index_to_bytes(next_index),
-                });
+impl TokenSource<'_> {
+    /// Returns the index just after the next `;` or `}` starting at `index`
+    /// that isn't nested in a delimiter opened after `index`, or just after a
+    /// closing delimiter for one opened before `index`.
+    ///
+    /// Always skips at least one token.
+    fn skip_to_sync(&self, index: SourceIndex) -> SourceIndex {
+        let mut depth = 0;
+        for (i, token) in self.tokens.iter().enumerate().skip(index.0) {
+            match &self.src[token.range.clone()] {
+                "(" | "[" | "{" => depth += 1,
+                ")" | "]" | "}" if depth == 0 => return SourceIndex(i + 1),
+                "}" if depth == 1 => return SourceIndex(i + 1),
+                ")" | "]" | "}" => depth -= 1,
+                ";" if depth == 0 => return SourceIndex(i + 1),
+                _ => {}
             }
-            Ok(node)
         }
-        None => panic!("input did not match {production}"),
+        self.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, parse_recovering};
+    use diagnostics::Diagnostics;
+    use grammar::{Edition, Grammar};
+
+    fn grammar() -> Grammar {
+        grammar::load_grammar_with_frontmatter(&mut Diagnostics::capture())
+    }
+
+    #[test]
+    fn parse_empty() {
+        let grammar = grammar();
+        let e = parse(&grammar, "", "Item", Edition::default()).unwrap_err();
+        assert_eq!(e.byte_offset, 0);
+    }
+
+    #[test]
+    fn parse_recovering_empty() {
+        let grammar = grammar();
+        let (node, errors) = parse_recovering(&grammar, "", "Crate", Edition::default());
+        assert_eq!(node.name, "Crate");
+        assert_eq!(node.range, 0..0);
+        assert!(errors.is_empty(), "{errors:?}");
     }
 }
@@ -8,11 +8,12 @@ type Result<T> = std::result::Result<T, Box<dyn Error>>;
 fn main() -> Result<()> {
     let mut args = std::env::args().skip(1);
     let cmd = args.next();
-    const OPTIONS: &str = "mdbook-test, linkcheck, style-check, test-all";
+    const OPTIONS: &str = "mdbook-test, linkcheck, style-check, grammar-test, test-all";
     match cmd.as_deref() {
         Some("test-all") => {
             mdbook_test()?;
             style_check()?;
+            grammar_test()?;
             fmt()?;
             linkcheck(args)?;
             cargo_test()?;
@@ -21,6 +22,7 @@ fn main() -> Result<()> {
         Some("mdbook-test") => mdbook_test()?,
         Some("linkcheck") => linkcheck(args)?,
         Some("style-check") => style_check()?,
+        Some("grammar-test") => grammar_test()?,
         Some("-h" | "--help") => eprintln!("valid options: {OPTIONS}"),
         Some(x) => {
             eprintln!("error: unknown command `{x}` (valid options: {OPTIONS})");
@@ -64,6 +66,26 @@ fn style_check() -> Result<()> {
     Ok(())
 }
 
+fn grammar_test() -> Result<()> {
+    eprintln!("Running grammar tests...");
+    let status = Command::new("cargo")
+        .args([
+            "test",
+            "--package=parser",
+            "--lib",
+            "--",
+            "examples::tests::book_examples",
+            "--exact",
+        ])
+        .current_dir(root_dir())
+        .status()
+        .expect("cargo should be installed");
+    if !status.success() {
+        return Err("grammar tests failed".into());
+    }
+    Ok(())
+}
+
 fn fmt() -> Result<()> {
     eprintln!("Checking code formatting...");
     let status = Command::new("cargo")